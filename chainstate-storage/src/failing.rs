@@ -0,0 +1,481 @@
+//! Storage wrapper injecting failures, for testing error handling of storage users
+//!
+//! [FailingStorage] wraps a storage (or a storage transaction) and makes its operations fail
+//! according to a [Schedule] configured separately for reads, writes and commits. The schedule is
+//! kept in [Faults] shared between the storage and all its transactions, so it can be adjusted
+//! by the test while the storage is in use, e.g. after it has been moved into chainstate.
+
+use std::sync::{Arc, Mutex};
+
+use chainstate_types::address_index::{AddressHistoryEntry, AddressIndexKey};
+use chainstate_types::block_index::BlockIndex;
+use common::chain::block::Block;
+use common::chain::transaction::{Transaction, TxMainChainPosition};
+use common::chain::OutPoint;
+use common::primitives::{BlockHeight, Id};
+use crypto::random::{rngs::StdRng, Rng, SeedableRng};
+use storage::error::Recoverable;
+use storage::traits;
+use utxo::{BlockUndo, Utxo};
+
+use crate::{
+    BlockchainStorage, BlockchainStorageRead, BlockchainStorageWrite, Transactional, UndoRead,
+    UndoWrite, UtxoRead, UtxoWrite,
+};
+
+/// Kind of storage operation subject to failures
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Operation {
+    /// Any query on the storage or a transaction
+    Read,
+    /// Any modification of the storage or a transaction
+    Write,
+    /// Committing a read-write transaction
+    Commit,
+}
+
+/// When operations of given kind fail, counted from the moment the schedule is set
+#[derive(Debug, PartialEq, Clone)]
+pub enum Schedule {
+    /// Operations never fail
+    Never,
+    /// All operations fail
+    Always,
+    /// The next `n` operations fail, the subsequent ones succeed
+    Next(usize),
+    /// Only the operation with given index fails (0 being the next operation)
+    Nth(usize),
+    /// Each operation fails with given probability, using a pseudo-random generator with given
+    /// seed so the outcome is reproducible
+    Random { probability: f64, seed: u64 },
+}
+
+// Failure state for one kind of operation
+struct Fault {
+    schedule: Schedule,
+    error: Recoverable,
+    rng: Option<StdRng>,
+    // Number of operations since the schedule has been set
+    seen: usize,
+    // Total number of operations and injected failures
+    total: usize,
+    failed: usize,
+}
+
+impl Fault {
+    fn new() -> Self {
+        Self {
+            schedule: Schedule::Never,
+            error: Recoverable::Unknown,
+            rng: None,
+            seen: 0,
+            total: 0,
+            failed: 0,
+        }
+    }
+
+    fn set(&mut self, schedule: Schedule, error: Recoverable) {
+        self.rng = match schedule {
+            Schedule::Random { seed, .. } => Some(StdRng::seed_from_u64(seed)),
+            _ => None,
+        };
+        self.schedule = schedule;
+        self.error = error;
+        self.seen = 0;
+    }
+
+    fn check(&mut self) -> crate::Result<()> {
+        let index = self.seen;
+        self.seen += 1;
+        self.total += 1;
+
+        let fail = match &self.schedule {
+            Schedule::Never => false,
+            Schedule::Always => true,
+            Schedule::Next(n) => index < *n,
+            Schedule::Nth(n) => index == *n,
+            Schedule::Random { probability, .. } => {
+                let rng = self.rng.as_mut().expect("random schedule to have a generator");
+                rng.gen_bool(*probability)
+            }
+        };
+
+        if fail {
+            self.failed += 1;
+            Err(crate::Error::Storage(self.error))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Failure schedules for all kinds of operations, shared by a storage and its transactions
+pub struct Faults {
+    read: Mutex<Fault>,
+    write: Mutex<Fault>,
+    commit: Mutex<Fault>,
+}
+
+impl Faults {
+    fn new() -> Self {
+        Self {
+            read: Mutex::new(Fault::new()),
+            write: Mutex::new(Fault::new()),
+            commit: Mutex::new(Fault::new()),
+        }
+    }
+
+    fn fault(&self, op: Operation) -> std::sync::MutexGuard<'_, Fault> {
+        let fault = match op {
+            Operation::Read => &self.read,
+            Operation::Write => &self.write,
+            Operation::Commit => &self.commit,
+        };
+        fault.lock().expect("fault state lock poisoned")
+    }
+
+    /// Make operations of given kind fail with given error according to given schedule.
+    pub fn set(&self, op: Operation, schedule: Schedule, error: Recoverable) {
+        self.fault(op).set(schedule, error)
+    }
+
+    /// Stop injecting failures into any operations.
+    pub fn clear(&self) {
+        for op in [Operation::Read, Operation::Write, Operation::Commit] {
+            self.fault(op).set(Schedule::Never, Recoverable::Unknown)
+        }
+    }
+
+    /// Total number of operations of given kind performed so far, including the failed ones
+    pub fn count(&self, op: Operation) -> usize {
+        self.fault(op).total
+    }
+
+    /// Number of failures of given kind injected so far
+    pub fn failures(&self, op: Operation) -> usize {
+        self.fault(op).failed
+    }
+
+    fn check(&self, op: Operation) -> crate::Result<()> {
+        self.fault(op).check()
+    }
+}
+
+/// Storage or storage transaction wrapper failing operations according to [Faults]
+pub struct FailingStorage<T> {
+    inner: T,
+    faults: Arc<Faults>,
+}
+
+impl<T> FailingStorage<T> {
+    /// Wrap given storage. No failures are injected until configured using [Self::faults].
+    pub fn new(inner: T) -> Self {
+        Self {
+            inner,
+            faults: Arc::new(Faults::new()),
+        }
+    }
+
+    /// Failure schedules of this storage
+    pub fn faults(&self) -> &Arc<Faults> {
+        &self.faults
+    }
+
+    /// Get the wrapped storage
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+}
+
+impl<T: Clone> Clone for FailingStorage<T> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            faults: Arc::clone(&self.faults),
+        }
+    }
+}
+
+impl<'t, S: Transactional<'t>> Transactional<'t> for FailingStorage<S> {
+    type TransactionRo = FailingStorage<S::TransactionRo>;
+    type TransactionRw = FailingStorage<S::TransactionRw>;
+
+    fn transaction_ro<'s: 't>(&'s self) -> Self::TransactionRo {
+        FailingStorage {
+            inner: self.inner.transaction_ro(),
+            faults: Arc::clone(&self.faults),
+        }
+    }
+
+    fn transaction_rw<'s: 't>(&'s self) -> Self::TransactionRw {
+        FailingStorage {
+            inner: self.inner.transaction_rw(),
+            faults: Arc::clone(&self.faults),
+        }
+    }
+}
+
+impl<S: BlockchainStorage> BlockchainStorage for FailingStorage<S> {
+    fn stats(&self) -> crate::Result<crate::Stats> {
+        self.faults.check(Operation::Read)?;
+        self.inner.stats()
+    }
+}
+
+macro_rules! delegate_with_faults {
+    ($(fn $f:ident $args:tt -> $ret:ty;)*) => {
+        $(delegate_with_faults!(@SELF [$f ($ret)] $args);)*
+    };
+    (@SELF $done:tt (&self $(, $($rest:tt)*)?)) => {
+        delegate_with_faults!(@BODY Read $done ($($($rest)*)?));
+    };
+    (@SELF $done:tt (&mut self $(, $($rest:tt)*)?)) => {
+        delegate_with_faults!(@BODY Write mut $done ($($($rest)*)?));
+    };
+    (@BODY $op:ident $($mut:ident)?
+        [$f:ident ($ret:ty)]
+        ($($arg:ident: $aty:ty),* $(,)?)
+    ) => {
+        fn $f(&$($mut)? self $(, $arg: $aty)*) -> $ret {
+            self.faults.check(Operation::$op)?;
+            self.inner.$f($($arg),*)
+        }
+    };
+}
+
+impl<T: BlockchainStorageRead> BlockchainStorageRead for FailingStorage<T> {
+    delegate_with_faults! {
+        fn get_storage_version(&self) -> crate::Result<u32>;
+        fn get_best_block_id(&self) -> crate::Result<Option<Id<Block>>>;
+        fn get_block_index(&self, id: &Id<Block>) -> crate::Result<Option<BlockIndex>>;
+        fn get_block_indices(&self) -> crate::Result<Vec<BlockIndex>>;
+        fn get_block_children(&self, id: &Id<Block>) -> crate::Result<Vec<Id<Block>>>;
+        fn get_block(&self, id: Id<Block>) -> crate::Result<Option<Block>>;
+
+        fn get_mainchain_tx_index(
+            &self,
+            tx_id: &Id<Transaction>,
+        ) -> crate::Result<Option<TxMainChainPosition>>;
+
+        fn get_is_mainchain_tx_index_enabled(&self) -> crate::Result<Option<bool>>;
+
+        fn get_mainchain_tx_by_position(
+            &self,
+            tx_index: &TxMainChainPosition,
+        ) -> crate::Result<Option<Transaction>>;
+
+        fn get_block_id_by_height(
+            &self,
+            height: &BlockHeight,
+        ) -> crate::Result<Option<Id<Block>>>;
+
+        fn get_address_history(
+            &self,
+            key: &AddressIndexKey,
+            skip: usize,
+            limit: usize,
+        ) -> crate::Result<Vec<AddressHistoryEntry>>;
+
+        fn get_is_address_index_enabled(&self) -> crate::Result<Option<bool>>;
+    }
+}
+
+impl<T: BlockchainStorageWrite> BlockchainStorageWrite for FailingStorage<T> {
+    delegate_with_faults! {
+        fn set_storage_version(&mut self, version: u32) -> crate::Result<()>;
+        fn set_best_block_id(&mut self, id: &Id<Block>) -> crate::Result<()>;
+        fn set_block_index(&mut self, block_index: &BlockIndex) -> crate::Result<()>;
+        fn add_block(&mut self, block: &Block) -> crate::Result<()>;
+        fn del_block(&mut self, id: Id<Block>) -> crate::Result<()>;
+
+        fn set_mainchain_tx_index(
+            &mut self,
+            tx_id: &Id<Transaction>,
+            tx_pos: &TxMainChainPosition,
+        ) -> crate::Result<()>;
+
+        fn del_mainchain_tx_index(&mut self, tx_id: &Id<Transaction>) -> crate::Result<()>;
+
+        fn set_is_mainchain_tx_index_enabled(&mut self, enabled: bool) -> crate::Result<()>;
+
+        fn set_block_id_at_height(
+            &mut self,
+            height: &BlockHeight,
+            block_id: &Id<Block>,
+        ) -> crate::Result<()>;
+
+        fn del_block_id_at_height(&mut self, height: &BlockHeight) -> crate::Result<()>;
+
+        fn set_address_history_entry(
+            &mut self,
+            key: &AddressIndexKey,
+            entry: &AddressHistoryEntry,
+        ) -> crate::Result<()>;
+
+        fn del_address_history_entry(
+            &mut self,
+            key: &AddressIndexKey,
+            block_height: BlockHeight,
+            outpoint: &OutPoint,
+        ) -> crate::Result<()>;
+
+        fn set_is_address_index_enabled(&mut self, enabled: bool) -> crate::Result<()>;
+    }
+}
+
+impl<T: UtxoRead> UtxoRead for FailingStorage<T> {
+    delegate_with_faults! {
+        fn get_utxo(&self, outpoint: &OutPoint) -> crate::Result<Option<Utxo>>;
+        fn get_best_block_for_utxos(&self) -> crate::Result<Option<Id<Block>>>;
+    }
+}
+
+impl<T: UtxoWrite> UtxoWrite for FailingStorage<T> {
+    delegate_with_faults! {
+        fn add_utxo(&mut self, outpoint: &OutPoint, entry: Utxo) -> crate::Result<()>;
+        fn del_utxo(&mut self, outpoint: &OutPoint) -> crate::Result<()>;
+        fn set_best_block_for_utxos(&mut self, block_id: &Id<Block>) -> crate::Result<()>;
+    }
+}
+
+impl<T: UndoRead> UndoRead for FailingStorage<T> {
+    delegate_with_faults! {
+        fn get_undo_data(&self, id: Id<Block>) -> crate::Result<Option<BlockUndo>>;
+    }
+}
+
+impl<T: UndoWrite> UndoWrite for FailingStorage<T> {
+    delegate_with_faults! {
+        fn add_undo_data(&mut self, id: Id<Block>, undo: &BlockUndo) -> crate::Result<()>;
+        fn del_undo_data(&mut self, id: Id<Block>) -> crate::Result<()>;
+    }
+}
+
+impl<T: traits::TransactionRw<Error = crate::Error>> traits::TransactionRw for FailingStorage<T> {
+    type Error = crate::Error;
+
+    /// Commit the transaction. If a failure is injected, the transaction is aborted instead.
+    fn commit(self) -> crate::Result<()> {
+        match self.faults.check(Operation::Commit) {
+            Ok(()) => self.inner.commit(),
+            Err(e) => {
+                self.inner.abort()?;
+                Err(e)
+            }
+        }
+    }
+
+    fn abort(self) -> crate::Result<()> {
+        self.inner.abort()
+    }
+}
+
+impl<T: traits::TransactionRo<Error = crate::Error>> traits::TransactionRo for FailingStorage<T> {
+    type Error = crate::Error;
+
+    fn finalize(self) -> crate::Result<()> {
+        self.inner.finalize()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Store;
+    use common::primitives::H256;
+    use storage::traits::TransactionRw;
+
+    fn failing_store() -> FailingStorage<Store> {
+        FailingStorage::new(Store::new_empty().unwrap())
+    }
+
+    #[test]
+    fn schedules() {
+        let store = failing_store();
+        let faults = store.faults();
+        let read = || store.get_storage_version().is_ok();
+
+        assert!((0..3).all(|_| read()));
+
+        faults.set(Operation::Read, Schedule::Next(2), Recoverable::Unknown);
+        let results: Vec<_> = (0..4).map(|_| read()).collect();
+        assert_eq!(results, [false, false, true, true]);
+
+        faults.set(Operation::Read, Schedule::Nth(2), Recoverable::Unknown);
+        let results: Vec<_> = (0..4).map(|_| read()).collect();
+        assert_eq!(results, [true, true, false, true]);
+
+        faults.set(Operation::Read, Schedule::Always, Recoverable::Unknown);
+        assert!((0..3).all(|_| !read()));
+
+        faults.clear();
+        assert!(read());
+
+        assert_eq!(faults.count(Operation::Read), 15);
+        assert_eq!(faults.failures(Operation::Read), 6);
+        assert_eq!(faults.count(Operation::Write), 0);
+    }
+
+    #[test]
+    fn random_schedule_is_reproducible() {
+        let run = || {
+            let store = failing_store();
+            let schedule = Schedule::Random {
+                probability: 0.5,
+                seed: 42,
+            };
+            store.faults().set(Operation::Read, schedule, Recoverable::Unknown);
+            (0..100).map(|_| store.get_storage_version().is_ok()).collect::<Vec<_>>()
+        };
+
+        let results = run();
+        assert_eq!(results, run());
+        assert!(results.contains(&true));
+        assert!(results.contains(&false));
+    }
+
+    #[test]
+    fn reported_errors() {
+        let mut store = failing_store();
+        let id = Id::new(H256::random());
+
+        let faults = Arc::clone(store.faults());
+        faults.set(
+            Operation::Write,
+            Schedule::Next(1),
+            Recoverable::TemporarilyUnavailable,
+        );
+        assert_eq!(
+            store.set_best_block_id(&id),
+            Err(crate::Error::Storage(Recoverable::TemporarilyUnavailable))
+        );
+        assert_eq!(store.get_best_block_id(), Ok(None));
+        assert_eq!(store.set_best_block_id(&id), Ok(()));
+        assert_eq!(store.get_best_block_id(), Ok(Some(id)));
+    }
+
+    #[test]
+    fn failed_commit_discards_changes() {
+        let store = failing_store();
+        let id = Id::new(H256::random());
+
+        store.faults().set(
+            Operation::Commit,
+            Schedule::Next(1),
+            Recoverable::TransactionFailed,
+        );
+
+        let mut tx = store.transaction_rw();
+        assert_eq!(tx.set_best_block_id(&id), Ok(()));
+        assert_eq!(
+            tx.commit(),
+            Err(crate::Error::Storage(Recoverable::TransactionFailed))
+        );
+        assert_eq!(store.get_best_block_id(), Ok(None));
+
+        let mut tx = store.transaction_rw();
+        assert_eq!(tx.set_best_block_id(&id), Ok(()));
+        assert_eq!(tx.commit(), Ok(()));
+        assert_eq!(store.get_best_block_id(), Ok(Some(id)));
+    }
+}
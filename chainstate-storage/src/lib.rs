@@ -1,21 +1,26 @@
 //! Application-level interface for the persistent blockchain storage.
 
+use chainstate_types::address_index::{AddressHistoryEntry, AddressIndexKey};
 use chainstate_types::block_index::BlockIndex;
 use common::chain::block::Block;
-use common::chain::transaction::{Transaction, TxMainChainIndex, TxMainChainPosition};
+use common::chain::transaction::{Transaction, TxMainChainPosition};
 use common::chain::OutPoint;
-use common::chain::OutPointSourceId;
 use common::primitives::{BlockHeight, Id};
 use storage::traits;
 use utxo::{BlockUndo, Utxo};
 
+#[cfg(any(test, feature = "failing"))]
+pub mod failing;
 #[cfg(any(test, feature = "mock"))]
 pub mod mock;
 mod store;
 mod utxo_db;
 
+pub use storage::stats::{MapStats, Stats};
 pub use storage::transaction::{TransactionRo, TransactionRw};
+pub use store::migration::STORAGE_VERSION;
 pub use store::Store;
+pub use utxo_db::UtxoDBImpl;
 
 /// Blockchain storage error
 #[derive(Debug, Ord, PartialOrd, PartialEq, Eq, Clone, Copy, thiserror::Error)]
@@ -30,11 +35,61 @@ impl From<storage::Error> for Error {
     }
 }
 
+/// Error opening blockchain storage
+#[derive(Debug, Ord, PartialOrd, PartialEq, Eq, Clone, Copy, thiserror::Error)]
+pub enum OpenError {
+    #[error("Storage error: {0}")]
+    Storage(#[from] storage::Error),
+    #[error("Storage version {0} is newer than version {1} supported by this software")]
+    VersionTooNew(u32, u32),
+    #[error("No migration available from storage version {0}")]
+    MissingMigration(u32),
+}
+
+/// Inconsistency found when checking blockchain storage against its best block
+#[derive(Debug, PartialEq, Eq, Clone, thiserror::Error)]
+pub enum ConsistencyError {
+    #[error("Storage error: {0}")]
+    Storage(#[from] Error),
+    #[error("Best block not set")]
+    NoBestBlock,
+    #[error("Block index not found for block {0}")]
+    MissingBlockIndex(Id<Block>),
+    #[error("Block {0} not found")]
+    MissingBlock(Id<Block>),
+    #[error("Block {0} has unexpected height {1}")]
+    UnexpectedHeight(Id<Block>, BlockHeight),
+    #[error("Mainchain block at height {0} is {1:?}, expected {2}")]
+    MainchainMismatch(BlockHeight, Option<Id<Block>>, Id<Block>),
+    #[error("Mainchain block {1} at height {0} is above the best block")]
+    BlockAboveBestBlock(BlockHeight, Id<Block>),
+}
+
+/// Error backing up blockchain storage
+#[derive(Debug, PartialEq, Eq, Clone, thiserror::Error)]
+pub enum BackupError {
+    #[error("Backup file already exists")]
+    TargetExists,
+    #[error("Storage error: {0}")]
+    Storage(#[from] storage::Error),
+}
+
+/// Error restoring blockchain storage from a backup
+#[derive(Debug, PartialEq, Eq, Clone, thiserror::Error)]
+pub enum RestoreError {
+    #[error("Target directory is not empty")]
+    TargetNotEmpty,
+    #[error("Cannot open restored storage: {0}")]
+    Open(#[from] OpenError),
+    #[error("Restored storage is inconsistent: {0}")]
+    Inconsistent(#[from] ConsistencyError),
+}
+
 /// Possibly failing result of blockchain storage query
 pub type Result<T> = core::result::Result<T, Error>;
 
 /// Queries on persistent blockchain data
-pub trait BlockchainStorageRead {
+pub trait BlockchainStorageRead: UtxoRead + UndoRead {
     /// Get storage version
     fn get_storage_version(&self) -> crate::Result<u32>;
 
@@ -43,14 +98,23 @@ pub trait BlockchainStorageRead {
 
     fn get_block_index(&self, block_index: &Id<Block>) -> crate::Result<Option<BlockIndex>>;
 
+    /// Get indices of all the blocks known to the storage, in no particular order
+    fn get_block_indices(&self) -> crate::Result<Vec<BlockIndex>>;
+
+    /// Get the IDs of the known blocks whose parent is given block
+    fn get_block_children(&self, id: &Id<Block>) -> crate::Result<Vec<Id<Block>>>;
+
     /// Get block by its hash
     fn get_block(&self, id: Id<Block>) -> crate::Result<Option<Block>>;
 
-    /// Get outputs state for given transaction in the mainchain
+    /// Get the position of given mainchain transaction, if the transaction index is maintained
     fn get_mainchain_tx_index(
         &self,
-        tx_id: &OutPointSourceId,
-    ) -> crate::Result<Option<TxMainChainIndex>>;
+        tx_id: &Id<Transaction>,
+    ) -> crate::Result<Option<TxMainChainPosition>>;
+
+    /// Get whether the mainchain transaction index is maintained, if it has been decided yet
+    fn get_is_mainchain_tx_index_enabled(&self) -> crate::Result<Option<bool>>;
 
     /// Get transaction by block ID and position
     fn get_mainchain_tx_by_position(
@@ -60,17 +124,29 @@ pub trait BlockchainStorageRead {
 
     /// Get mainchain block by its height
     fn get_block_id_by_height(&self, height: &BlockHeight) -> crate::Result<Option<Id<Block>>>;
+
+    /// Get the mainchain outputs paid to given address, oldest first, skipping `skip` outputs and
+    /// returning at most `limit` of them
+    fn get_address_history(
+        &self,
+        key: &AddressIndexKey,
+        skip: usize,
+        limit: usize,
+    ) -> crate::Result<Vec<AddressHistoryEntry>>;
+
+    /// Get whether the address index is maintained, if it has been decided yet
+    fn get_is_address_index_enabled(&self) -> crate::Result<Option<bool>>;
 }
 
 /// Modifying operations on persistent blockchain data
-pub trait BlockchainStorageWrite: BlockchainStorageRead {
+pub trait BlockchainStorageWrite: BlockchainStorageRead + UtxoWrite + UndoWrite {
     /// Set storage version
     fn set_storage_version(&mut self, version: u32) -> crate::Result<()>;
 
     /// Set the hash of the best block
     fn set_best_block_id(&mut self, id: &Id<Block>) -> crate::Result<()>;
 
-    // Set the block index
+    /// Set the block index. The block is also recorded as a child of its parent.
     fn set_block_index(&mut self, block_index: &BlockIndex) -> crate::Result<()>;
 
     /// Add a new block into the database
@@ -79,15 +155,18 @@ pub trait BlockchainStorageWrite: BlockchainStorageRead {
     /// Remove block from the database
     fn del_block(&mut self, id: Id<Block>) -> crate::Result<()>;
 
-    /// Set state of the outputs of given transaction
+    /// Set the position of given mainchain transaction
     fn set_mainchain_tx_index(
         &mut self,
-        tx_id: &OutPointSourceId,
-        tx_index: &TxMainChainIndex,
+        tx_id: &Id<Transaction>,
+        tx_pos: &TxMainChainPosition,
     ) -> crate::Result<()>;
 
-    /// Delete outputs state index associated with given transaction
-    fn del_mainchain_tx_index(&mut self, tx_id: &OutPointSourceId) -> crate::Result<()>;
+    /// Delete the position of given transaction
+    fn del_mainchain_tx_index(&mut self, tx_id: &Id<Transaction>) -> crate::Result<()>;
+
+    /// Set whether the mainchain transaction index is maintained
+    fn set_is_mainchain_tx_index_enabled(&mut self, enabled: bool) -> crate::Result<()>;
 
     /// Set the mainchain block at given height to be given block.
     fn set_block_id_at_height(
@@ -98,30 +177,59 @@ pub trait BlockchainStorageWrite: BlockchainStorageRead {
 
     /// Remove block id from given mainchain height
     fn del_block_id_at_height(&mut self, height: &BlockHeight) -> crate::Result<()>;
+
+    /// Add an output to the history of given address, or update its spending status
+    fn set_address_history_entry(
+        &mut self,
+        key: &AddressIndexKey,
+        entry: &AddressHistoryEntry,
+    ) -> crate::Result<()>;
+
+    /// Remove an output created at given height from the history of given address
+    fn del_address_history_entry(
+        &mut self,
+        key: &AddressIndexKey,
+        block_height: BlockHeight,
+        outpoint: &OutPoint,
+    ) -> crate::Result<()>;
+
+    /// Set whether the address index is maintained
+    fn set_is_address_index_enabled(&mut self, enabled: bool) -> crate::Result<()>;
 }
 
-/// Queries to get the Utxo
-// this is not exposed outside the crate, because we only want this to be accessible
-// using the UtxoDB.
-pub(crate) trait UtxoRead {
+/// Queries on the UTXO set
+pub trait UtxoRead {
+    /// Get the unspent output at given outpoint
     fn get_utxo(&self, outpoint: &OutPoint) -> crate::Result<Option<Utxo>>;
+
+    /// Get the ID of the block the UTXO set corresponds to
     fn get_best_block_for_utxos(&self) -> crate::Result<Option<Id<Block>>>;
 }
 
-/// Queries to update the Utxo
-// this is not exposed outside the crate, because we only want this to be accessible
-// using the UtxoDB.
-pub(crate) trait UtxoWrite: UtxoRead {
+/// Modifying operations on the UTXO set
+pub trait UtxoWrite: UtxoRead {
+    /// Add an unspent output at given outpoint
     fn add_utxo(&mut self, outpoint: &OutPoint, entry: Utxo) -> crate::Result<()>;
+
+    /// Remove the unspent output at given outpoint
     fn del_utxo(&mut self, outpoint: &OutPoint) -> crate::Result<()>;
+
+    /// Set the ID of the block the UTXO set corresponds to
     fn set_best_block_for_utxos(&mut self, block_id: &Id<Block>) -> crate::Result<()>;
 }
 
-pub(crate) trait UndoRead {
+/// Queries on the data needed to disconnect blocks
+pub trait UndoRead {
+    /// Get the outputs spent by given block
     fn get_undo_data(&self, id: Id<Block>) -> crate::Result<Option<BlockUndo>>;
 }
-pub(crate) trait UndoWrite: UndoRead {
+
+/// Modifying operations on the data needed to disconnect blocks
+pub trait UndoWrite: UndoRead {
+    /// Set the outputs spent by given block
     fn add_undo_data(&mut self, id: Id<Block>, undo: &BlockUndo) -> crate::Result<()>;
+
+    /// Remove the outputs spent by given block
     fn del_undo_data(&mut self, id: Id<Block>) -> crate::Result<()>;
 }
 
@@ -140,4 +248,7 @@ pub trait Transactional<'t> {
     fn transaction_rw<'s: 't>(&'s self) -> Self::TransactionRw;
 }
 
-pub trait BlockchainStorage: BlockchainStorageWrite + for<'tx> Transactional<'tx> {}
+pub trait BlockchainStorage: BlockchainStorageWrite + for<'tx> Transactional<'tx> {
+    /// Get size and usage statistics of each column in the storage
+    fn stats(&self) -> crate::Result<Stats>;
+}
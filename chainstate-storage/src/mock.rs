@@ -1,11 +1,12 @@
 //! A mock version of the blockchian storage.
 
+use chainstate_types::address_index::{AddressHistoryEntry, AddressIndexKey};
 use chainstate_types::block_index::BlockIndex;
 use common::chain::block::Block;
-use common::chain::transaction::{
-    OutPointSourceId, Transaction, TxMainChainIndex, TxMainChainPosition,
-};
+use common::chain::transaction::{Transaction, TxMainChainPosition};
+use common::chain::OutPoint;
 use common::primitives::{BlockHeight, Id};
+use utxo::{BlockUndo, Utxo};
 
 mockall::mock! {
     /// A mock object for blockchain storage
@@ -15,13 +16,16 @@ mockall::mock! {
         fn get_storage_version(&self) -> crate::Result<u32>;
         fn get_best_block_id(&self) -> crate::Result<Option<Id<Block>>>;
         fn get_block_index(&self, id: &Id<Block>) -> crate::Result<Option<BlockIndex>>;
+        fn get_block_indices(&self) -> crate::Result<Vec<BlockIndex>>;
+        fn get_block_children(&self, id: &Id<Block>) -> crate::Result<Vec<Id<Block>>>;
         fn get_block(&self, id: Id<Block>) -> crate::Result<Option<Block>>;
 
         fn get_mainchain_tx_index(
             &self,
-            tx_id: &OutPointSourceId,
-        ) -> crate::Result<Option<TxMainChainIndex>>;
+            tx_id: &Id<Transaction>,
+        ) -> crate::Result<Option<TxMainChainPosition>>;
 
+        fn get_is_mainchain_tx_index_enabled(&self) -> crate::Result<Option<bool>>;
 
         fn get_mainchain_tx_by_position(
             &self,
@@ -32,6 +36,24 @@ mockall::mock! {
             &self,
             height: &BlockHeight,
         ) -> crate::Result<Option<Id<Block>>>;
+
+        fn get_address_history(
+            &self,
+            key: &AddressIndexKey,
+            skip: usize,
+            limit: usize,
+        ) -> crate::Result<Vec<AddressHistoryEntry>>;
+
+        fn get_is_address_index_enabled(&self) -> crate::Result<Option<bool>>;
+    }
+
+    impl crate::UtxoRead for Store {
+        fn get_utxo(&self, outpoint: &OutPoint) -> crate::Result<Option<Utxo>>;
+        fn get_best_block_for_utxos(&self) -> crate::Result<Option<Id<Block>>>;
+    }
+
+    impl crate::UndoRead for Store {
+        fn get_undo_data(&self, id: Id<Block>) -> crate::Result<Option<BlockUndo>>;
     }
 
     impl crate::BlockchainStorageWrite for Store {
@@ -42,10 +64,12 @@ mockall::mock! {
         fn del_block(&mut self, id: Id<Block>) -> crate::Result<()>;
         fn set_mainchain_tx_index(
             &mut self,
-            tx_id: &OutPointSourceId,
-            tx_index: &TxMainChainIndex,
+            tx_id: &Id<Transaction>,
+            tx_pos: &TxMainChainPosition,
         ) -> crate::Result<()>;
-        fn del_mainchain_tx_index(&mut self, tx_id: &OutPointSourceId) -> crate::Result<()>;
+        fn del_mainchain_tx_index(&mut self, tx_id: &Id<Transaction>) -> crate::Result<()>;
+
+        fn set_is_mainchain_tx_index_enabled(&mut self, enabled: bool) -> crate::Result<()>;
 
         fn set_block_id_at_height(
             &mut self,
@@ -54,6 +78,32 @@ mockall::mock! {
         ) -> crate::Result<()>;
 
         fn del_block_id_at_height(&mut self, height: &BlockHeight) -> crate::Result<()>;
+
+        fn set_address_history_entry(
+            &mut self,
+            key: &AddressIndexKey,
+            entry: &AddressHistoryEntry,
+        ) -> crate::Result<()>;
+
+        fn del_address_history_entry(
+            &mut self,
+            key: &AddressIndexKey,
+            block_height: BlockHeight,
+            outpoint: &OutPoint,
+        ) -> crate::Result<()>;
+
+        fn set_is_address_index_enabled(&mut self, enabled: bool) -> crate::Result<()>;
+    }
+
+    impl crate::UtxoWrite for Store {
+        fn add_utxo(&mut self, outpoint: &OutPoint, entry: Utxo) -> crate::Result<()>;
+        fn del_utxo(&mut self, outpoint: &OutPoint) -> crate::Result<()>;
+        fn set_best_block_for_utxos(&mut self, block_id: &Id<Block>) -> crate::Result<()>;
+    }
+
+    impl crate::UndoWrite for Store {
+        fn add_undo_data(&mut self, id: Id<Block>, undo: &BlockUndo) -> crate::Result<()>;
+        fn del_undo_data(&mut self, id: Id<Block>) -> crate::Result<()>;
     }
 
     impl<'tx> crate::Transactional<'tx> for Store {
@@ -63,7 +113,9 @@ mockall::mock! {
         fn transaction_rw<'st>(&'st self) -> MockStoreTxRw where 'st: 'tx;
     }
 
-    impl crate::BlockchainStorage for Store {}
+    impl crate::BlockchainStorage for Store {
+        fn stats(&self) -> crate::Result<crate::Stats>;
+    }
 }
 
 mockall::mock! {
@@ -74,12 +126,16 @@ mockall::mock! {
         fn get_storage_version(&self) -> crate::Result<u32>;
         fn get_best_block_id(&self) -> crate::Result<Option<Id<Block>>>;
         fn get_block_index(&self, id: &Id<Block>) -> crate::Result<Option<BlockIndex>>;
+        fn get_block_indices(&self) -> crate::Result<Vec<BlockIndex>>;
+        fn get_block_children(&self, id: &Id<Block>) -> crate::Result<Vec<Id<Block>>>;
         fn get_block(&self, id: Id<Block>) -> crate::Result<Option<Block>>;
 
         fn get_mainchain_tx_index(
             &self,
-            tx_id: &OutPointSourceId,
-        ) -> crate::Result<Option<TxMainChainIndex>>;
+            tx_id: &Id<Transaction>,
+        ) -> crate::Result<Option<TxMainChainPosition>>;
+
+        fn get_is_mainchain_tx_index_enabled(&self) -> crate::Result<Option<bool>>;
 
         fn get_mainchain_tx_by_position(
             &self,
@@ -90,6 +146,24 @@ mockall::mock! {
             &self,
             height: &BlockHeight,
         ) -> crate::Result<Option<Id<Block>>>;
+
+        fn get_address_history(
+            &self,
+            key: &AddressIndexKey,
+            skip: usize,
+            limit: usize,
+        ) -> crate::Result<Vec<AddressHistoryEntry>>;
+
+        fn get_is_address_index_enabled(&self) -> crate::Result<Option<bool>>;
+    }
+
+    impl crate::UtxoRead for StoreTxRo {
+        fn get_utxo(&self, outpoint: &OutPoint) -> crate::Result<Option<Utxo>>;
+        fn get_best_block_for_utxos(&self) -> crate::Result<Option<Id<Block>>>;
+    }
+
+    impl crate::UndoRead for StoreTxRo {
+        fn get_undo_data(&self, id: Id<Block>) -> crate::Result<Option<BlockUndo>>;
     }
 
     impl storage::traits::TransactionRo for StoreTxRo {
@@ -107,11 +181,15 @@ mockall::mock! {
         fn get_best_block_id(&self) -> crate::Result<Option<Id<Block>>>;
         fn get_block(&self, id: Id<Block>) -> crate::Result<Option<Block>>;
         fn get_block_index(&self, id: &Id<Block>) -> crate::Result<Option<BlockIndex>>;
+        fn get_block_indices(&self) -> crate::Result<Vec<BlockIndex>>;
+        fn get_block_children(&self, id: &Id<Block>) -> crate::Result<Vec<Id<Block>>>;
 
         fn get_mainchain_tx_index(
             &self,
-            tx_id: &OutPointSourceId,
-        ) -> crate::Result<Option<TxMainChainIndex>>;
+            tx_id: &Id<Transaction>,
+        ) -> crate::Result<Option<TxMainChainPosition>>;
+
+        fn get_is_mainchain_tx_index_enabled(&self) -> crate::Result<Option<bool>>;
 
         fn get_mainchain_tx_by_position(
             &self,
@@ -122,6 +200,24 @@ mockall::mock! {
             &self,
             height: &BlockHeight,
         ) -> crate::Result<Option<Id<Block>>>;
+
+        fn get_address_history(
+            &self,
+            key: &AddressIndexKey,
+            skip: usize,
+            limit: usize,
+        ) -> crate::Result<Vec<AddressHistoryEntry>>;
+
+        fn get_is_address_index_enabled(&self) -> crate::Result<Option<bool>>;
+    }
+
+    impl crate::UtxoRead for StoreTxRw {
+        fn get_utxo(&self, outpoint: &OutPoint) -> crate::Result<Option<Utxo>>;
+        fn get_best_block_for_utxos(&self) -> crate::Result<Option<Id<Block>>>;
+    }
+
+    impl crate::UndoRead for StoreTxRw {
+        fn get_undo_data(&self, id: Id<Block>) -> crate::Result<Option<BlockUndo>>;
     }
 
     impl crate::BlockchainStorageWrite for StoreTxRw {
@@ -132,11 +228,13 @@ mockall::mock! {
         fn del_block(&mut self, id: Id<Block>) -> crate::Result<()>;
         fn set_mainchain_tx_index(
             &mut self,
-            tx_id: &OutPointSourceId,
-            tx_index: &TxMainChainIndex,
+            tx_id: &Id<Transaction>,
+            tx_pos: &TxMainChainPosition,
         ) -> crate::Result<()>;
 
-        fn del_mainchain_tx_index(&mut self, tx_id: &OutPointSourceId) -> crate::Result<()>;
+        fn del_mainchain_tx_index(&mut self, tx_id: &Id<Transaction>) -> crate::Result<()>;
+
+        fn set_is_mainchain_tx_index_enabled(&mut self, enabled: bool) -> crate::Result<()>;
 
         fn set_block_id_at_height(
             &mut self,
@@ -145,6 +243,32 @@ mockall::mock! {
         ) -> crate::Result<()>;
 
         fn del_block_id_at_height(&mut self, height: &BlockHeight) -> crate::Result<()>;
+
+        fn set_address_history_entry(
+            &mut self,
+            key: &AddressIndexKey,
+            entry: &AddressHistoryEntry,
+        ) -> crate::Result<()>;
+
+        fn del_address_history_entry(
+            &mut self,
+            key: &AddressIndexKey,
+            block_height: BlockHeight,
+            outpoint: &OutPoint,
+        ) -> crate::Result<()>;
+
+        fn set_is_address_index_enabled(&mut self, enabled: bool) -> crate::Result<()>;
+    }
+
+    impl crate::UtxoWrite for StoreTxRw {
+        fn add_utxo(&mut self, outpoint: &OutPoint, entry: Utxo) -> crate::Result<()>;
+        fn del_utxo(&mut self, outpoint: &OutPoint) -> crate::Result<()>;
+        fn set_best_block_for_utxos(&mut self, block_id: &Id<Block>) -> crate::Result<()>;
+    }
+
+    impl crate::UndoWrite for StoreTxRw {
+        fn add_undo_data(&mut self, id: Id<Block>, undo: &BlockUndo) -> crate::Result<()>;
+        fn del_undo_data(&mut self, id: Id<Block>) -> crate::Result<()>;
     }
 
     impl storage::traits::TransactionRw for StoreTxRw {
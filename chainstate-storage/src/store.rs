@@ -1,11 +1,13 @@
+use chainstate_types::address_index::{AddressHistoryEntry, AddressIndexKey};
 use chainstate_types::block_index::BlockIndex;
 use common::chain::block::Block;
-use common::chain::transaction::{Transaction, TxMainChainIndex, TxMainChainPosition};
+use common::chain::transaction::{Transaction, TxMainChainPosition};
 use common::chain::OutPoint;
-use common::chain::OutPointSourceId;
 use common::primitives::{BlockHeight, Id, Idable};
 use serialization::{Codec, Decode, DecodeAll, Encode};
-use storage::traits::{self, MapMut, MapRef, TransactionRo, TransactionRw};
+use storage::traits::{
+    self, MapMut, MapRef, MultiMapMut, MultiMapRef, TransactionRo, TransactionRw,
+};
 use utxo::{BlockUndo, Utxo};
 
 use crate::{
@@ -13,6 +15,9 @@ use crate::{
     UndoWrite, UtxoRead, UtxoWrite,
 };
 
+mod backup;
+pub(crate) mod migration;
+
 mod well_known {
     use super::{Block, Codec, Id};
 
@@ -37,6 +42,8 @@ mod well_known {
     declare_entry!(StoreVersion: u32);
     declare_entry!(BestBlockId: Id<Block>);
     declare_entry!(UtxosBestBlockId: Id<Block>);
+    declare_entry!(TxIndexEnabled: bool);
+    declare_entry!(AddressIndexEnabled: bool);
 }
 
 storage::decl_schema! {
@@ -52,10 +59,14 @@ storage::decl_schema! {
         pub DBTxIndex: Single,
         // Storage for block IDs indexed by block height.
         pub DBBlockByHeight: Single,
+        // Storage for the IDs of the children of each block.
+        pub DBBlockChildren: Multi,
         // Store for Utxo Entries
         pub DBUtxo: Single,
         // Store for BlockUndo
-        pub DBBlockUndo: Single
+        pub DBBlockUndo: Single,
+        // Storage for the outputs paid to each address.
+        pub DBAddressIndex: Single
     }
 }
 
@@ -72,7 +83,32 @@ impl Store {
     /// New empty storage
     pub fn new_empty() -> crate::Result<Self> {
         let mut store = Self(storage::Store::default());
-        store.set_storage_version(1)?;
+        store.set_storage_version(migration::STORAGE_VERSION)?;
+        Ok(store)
+    }
+
+    /// Open persistent storage in given directory.
+    ///
+    /// Empty storage is initialized, storage written by an older version of this software is
+    /// migrated to the current version.
+    pub fn open(path: impl AsRef<std::path::Path>) -> Result<Self, crate::OpenError> {
+        Self::open_with_migrations(path, migration::MIGRATIONS, migration::STORAGE_VERSION)
+    }
+
+    fn open_with_migrations(
+        path: impl AsRef<std::path::Path>,
+        migrations: &[migration::Migration],
+        version: u32,
+    ) -> Result<Self, crate::OpenError> {
+        let store = Self(storage::Store::open(path)?);
+        let mut tx = traits::Transactional::transaction_rw(&store.0);
+        match migration::upgrade(&mut tx, migrations, version) {
+            Ok(()) => tx.commit()?,
+            Err(e) => {
+                tx.abort()?;
+                return Err(e);
+            }
+        }
         Ok(store)
     }
 }
@@ -90,7 +126,11 @@ impl<'tx> crate::Transactional<'tx> for Store {
     }
 }
 
-impl BlockchainStorage for Store {}
+impl BlockchainStorage for Store {
+    fn stats(&self) -> crate::Result<crate::Stats> {
+        Ok(traits::Backend::stats(&self.0)?)
+    }
+}
 
 macro_rules! delegate_to_transaction {
     ($(fn $f:ident $args:tt -> $ret:ty;)*) => {
@@ -122,12 +162,16 @@ impl BlockchainStorageRead for Store {
         fn get_storage_version(&self) -> crate::Result<u32>;
         fn get_best_block_id(&self) -> crate::Result<Option<Id<Block>>>;
         fn get_block_index(&self, id: &Id<Block>) -> crate::Result<Option<BlockIndex>>;
+        fn get_block_indices(&self) -> crate::Result<Vec<BlockIndex>>;
+        fn get_block_children(&self, id: &Id<Block>) -> crate::Result<Vec<Id<Block>>>;
         fn get_block(&self, id: Id<Block>) -> crate::Result<Option<Block>>;
 
         fn get_mainchain_tx_index(
             &self,
-            tx_id: &OutPointSourceId,
-        ) -> crate::Result<Option<TxMainChainIndex>>;
+            tx_id: &Id<Transaction>,
+        ) -> crate::Result<Option<TxMainChainPosition>>;
+
+        fn get_is_mainchain_tx_index_enabled(&self) -> crate::Result<Option<bool>>;
 
         fn get_mainchain_tx_by_position(
             &self,
@@ -138,6 +182,15 @@ impl BlockchainStorageRead for Store {
             &self,
             height: &BlockHeight,
         ) -> crate::Result<Option<Id<Block>>>;
+
+        fn get_address_history(
+            &self,
+            key: &AddressIndexKey,
+            skip: usize,
+            limit: usize,
+        ) -> crate::Result<Vec<AddressHistoryEntry>>;
+
+        fn get_is_address_index_enabled(&self) -> crate::Result<Option<bool>>;
     }
 }
 
@@ -164,11 +217,13 @@ impl BlockchainStorageWrite for Store {
 
         fn set_mainchain_tx_index(
             &mut self,
-            tx_id: &OutPointSourceId,
-            tx_index: &TxMainChainIndex,
+            tx_id: &Id<Transaction>,
+            tx_pos: &TxMainChainPosition,
         ) -> crate::Result<()>;
 
-        fn del_mainchain_tx_index(&mut self, tx_id: &OutPointSourceId) -> crate::Result<()>;
+        fn del_mainchain_tx_index(&mut self, tx_id: &Id<Transaction>) -> crate::Result<()>;
+
+        fn set_is_mainchain_tx_index_enabled(&mut self, enabled: bool) -> crate::Result<()>;
 
         fn set_block_id_at_height(
             &mut self,
@@ -177,6 +232,21 @@ impl BlockchainStorageWrite for Store {
         ) -> crate::Result<()>;
 
         fn del_block_id_at_height(&mut self, height: &BlockHeight) -> crate::Result<()>;
+
+        fn set_address_history_entry(
+            &mut self,
+            key: &AddressIndexKey,
+            entry: &AddressHistoryEntry,
+        ) -> crate::Result<()>;
+
+        fn del_address_history_entry(
+            &mut self,
+            key: &AddressIndexKey,
+            block_height: BlockHeight,
+            outpoint: &OutPoint,
+        ) -> crate::Result<()>;
+
+        fn set_is_address_index_enabled(&mut self, enabled: bool) -> crate::Result<()>;
     }
 }
 
@@ -208,6 +278,22 @@ impl<Tx: for<'a> traits::GetMapRef<'a, Schema>> BlockchainStorageRead for StoreT
         self.read::<DBBlockIndex, _, _>(id.as_ref())
     }
 
+    fn get_block_indices(&self) -> crate::Result<Vec<BlockIndex>> {
+        let col = self.0.get::<DBBlockIndex, _>();
+        let iter = col.iter().map_err(crate::Error::from)?;
+        Ok(iter
+            .map(|(_, d)| BlockIndex::decode_all(&mut &*d).expect("Cannot decode a database value"))
+            .collect())
+    }
+
+    fn get_block_children(&self, id: &Id<Block>) -> crate::Result<Vec<Id<Block>>> {
+        let col = self.0.get_multi::<DBBlockChildren, _>();
+        let iter = col.get(id.as_ref()).map_err(crate::Error::from)?;
+        Ok(iter
+            .map(|d| Id::decode_all(&mut &*d).expect("Cannot decode a database value"))
+            .collect())
+    }
+
     /// Get the hash of the best block
     fn get_best_block_id(&self) -> crate::Result<Option<Id<Block>>> {
         self.read_value::<well_known::BestBlockId>()
@@ -219,9 +305,13 @@ impl<Tx: for<'a> traits::GetMapRef<'a, Schema>> BlockchainStorageRead for StoreT
 
     fn get_mainchain_tx_index(
         &self,
-        tx_id: &OutPointSourceId,
-    ) -> crate::Result<Option<TxMainChainIndex>> {
-        self.read::<DBTxIndex, _, _>(&tx_id.encode())
+        tx_id: &Id<Transaction>,
+    ) -> crate::Result<Option<TxMainChainPosition>> {
+        self.read::<DBTxIndex, _, _>(tx_id.as_ref())
+    }
+
+    fn get_is_mainchain_tx_index_enabled(&self) -> crate::Result<Option<bool>> {
+        self.read_value::<well_known::TxIndexEnabled>()
     }
 
     fn get_mainchain_tx_by_position(
@@ -246,6 +336,27 @@ impl<Tx: for<'a> traits::GetMapRef<'a, Schema>> BlockchainStorageRead for StoreT
     fn get_block_id_by_height(&self, height: &BlockHeight) -> crate::Result<Option<Id<Block>>> {
         self.read::<DBBlockByHeight, _, _>(&height.encode())
     }
+
+    fn get_address_history(
+        &self,
+        key: &AddressIndexKey,
+        skip: usize,
+        limit: usize,
+    ) -> crate::Result<Vec<AddressHistoryEntry>> {
+        let col = self.0.get::<DBAddressIndex, _>();
+        let iter = col.prefix_iter(&key.encode()).map_err(crate::Error::from)?;
+        Ok(iter
+            .skip(skip)
+            .take(limit)
+            .map(|(_, d)| {
+                AddressHistoryEntry::decode_all(&mut &*d).expect("Cannot decode a database value")
+            })
+            .collect())
+    }
+
+    fn get_is_address_index_enabled(&self) -> crate::Result<Option<bool>> {
+        self.read_value::<well_known::AddressIndexEnabled>()
+    }
 }
 
 /// Utxo data storage transaction
@@ -283,19 +394,27 @@ impl<Tx: for<'a> traits::GetMapMut<'a, Schema>> BlockchainStorageWrite for Store
     }
 
     fn set_block_index(&mut self, block_index: &BlockIndex) -> crate::Result<()> {
+        if let Some(prev_block_id) = block_index.prev_block_id() {
+            let mut col = self.0.get_multi_mut::<DBBlockChildren, _>();
+            col.put(prev_block_id.encode(), block_index.block_id().encode())?;
+        }
         self.write::<DBBlockIndex, _, _>(block_index.block_id().encode(), block_index)
     }
 
     fn set_mainchain_tx_index(
         &mut self,
-        tx_id: &OutPointSourceId,
-        tx_index: &TxMainChainIndex,
+        tx_id: &Id<Transaction>,
+        tx_pos: &TxMainChainPosition,
     ) -> crate::Result<()> {
-        self.write::<DBTxIndex, _, _>(tx_id.encode(), tx_index)
+        self.write::<DBTxIndex, _, _>(tx_id.encode(), tx_pos)
+    }
+
+    fn del_mainchain_tx_index(&mut self, tx_id: &Id<Transaction>) -> crate::Result<()> {
+        self.0.get_mut::<DBTxIndex, _>().del(tx_id.as_ref()).map_err(Into::into)
     }
 
-    fn del_mainchain_tx_index(&mut self, tx_id: &OutPointSourceId) -> crate::Result<()> {
-        self.0.get_mut::<DBTxIndex, _>().del(&tx_id.encode()).map_err(Into::into)
+    fn set_is_mainchain_tx_index_enabled(&mut self, enabled: bool) -> crate::Result<()> {
+        self.write_value::<well_known::TxIndexEnabled>(&enabled)
     }
 
     fn set_block_id_at_height(
@@ -309,6 +428,29 @@ impl<Tx: for<'a> traits::GetMapMut<'a, Schema>> BlockchainStorageWrite for Store
     fn del_block_id_at_height(&mut self, height: &BlockHeight) -> crate::Result<()> {
         self.0.get_mut::<DBBlockByHeight, _>().del(&height.encode()).map_err(Into::into)
     }
+
+    fn set_address_history_entry(
+        &mut self,
+        key: &AddressIndexKey,
+        entry: &AddressHistoryEntry,
+    ) -> crate::Result<()> {
+        let db_key = address_history_key(key, entry.block_height(), entry.outpoint());
+        self.write::<DBAddressIndex, _, _>(db_key, entry)
+    }
+
+    fn del_address_history_entry(
+        &mut self,
+        key: &AddressIndexKey,
+        block_height: BlockHeight,
+        outpoint: &OutPoint,
+    ) -> crate::Result<()> {
+        let db_key = address_history_key(key, block_height, outpoint);
+        self.0.get_mut::<DBAddressIndex, _>().del(&db_key).map_err(Into::into)
+    }
+
+    fn set_is_address_index_enabled(&mut self, enabled: bool) -> crate::Result<()> {
+        self.write_value::<well_known::AddressIndexEnabled>(&enabled)
+    }
 }
 
 impl<Tx: for<'a> traits::GetMapMut<'a, Schema>> UtxoWrite for StoreTx<Tx> {
@@ -337,6 +479,19 @@ impl<Tx: for<'a> traits::GetMapMut<'a, Schema>> UndoWrite for StoreTx<Tx> {
     }
 }
 
+// Database key of an output in the history of an address. The height is encoded in big endian so
+// that the outputs of an address are iterated over in the order they were created in.
+fn address_history_key(
+    key: &AddressIndexKey,
+    block_height: BlockHeight,
+    outpoint: &OutPoint,
+) -> Vec<u8> {
+    let mut db_key = key.encode();
+    db_key.extend_from_slice(&u64::from(block_height).to_be_bytes());
+    db_key.extend(outpoint.encode());
+    db_key
+}
+
 impl<'a, Tx: traits::GetMapRef<'a, Schema>> StoreTx<Tx> {
     // Read a value from the database and decode it
     fn read<DBIdx, I, T>(&'a self, key: &[u8]) -> crate::Result<Option<T>>
@@ -396,7 +551,7 @@ impl<T: traits::TransactionRo<Error = storage::Error>> traits::TransactionRo for
 #[cfg(test)]
 pub(crate) mod test {
     use super::*;
-    use common::chain::{Destination, OutputPurpose, TxOutput};
+    use common::chain::{Destination, OutPointSourceId, OutputPurpose, TxOutput};
     use common::primitives::{Amount, H256};
     use crypto::key::{KeyKind, PrivateKey};
     use crypto::random::{make_pseudo_rng, Rng};
@@ -408,7 +563,11 @@ pub(crate) mod test {
             let store = Store::new_empty().unwrap();
             let vtx = store.transaction_ro().run(|tx| tx.get_storage_version()).unwrap();
             let vst = store.get_storage_version().unwrap();
-            assert_eq!(vtx, 1, "Default storage version wrong");
+            assert_eq!(
+                vtx,
+                migration::STORAGE_VERSION,
+                "Default storage version wrong"
+            );
             assert_eq!(vtx, vst, "Transaction and non-transaction inconsistency");
         })
     }
@@ -417,10 +576,7 @@ pub(crate) mod test {
     #[cfg(not(loom))]
     fn test_storage_manipulation() {
         use common::{
-            chain::{
-                block::{timestamp::BlockTimestamp, ConsensusData},
-                SpendablePosition,
-            },
+            chain::block::{timestamp::BlockTimestamp, ConsensusData},
             primitives::H256,
         };
 
@@ -446,9 +602,10 @@ pub(crate) mod test {
         let mut store = Store::new_empty().unwrap();
 
         // Storage version manipulation
-        assert_eq!(store.get_storage_version(), Ok(1));
-        assert_eq!(store.set_storage_version(2), Ok(()));
-        assert_eq!(store.get_storage_version(), Ok(2));
+        let version = migration::STORAGE_VERSION;
+        assert_eq!(store.get_storage_version(), Ok(version));
+        assert_eq!(store.set_storage_version(version + 1), Ok(()));
+        assert_eq!(store.get_storage_version(), Ok(version + 1));
 
         // Storte is now empty, the block is not there
         assert_eq!(store.get_block(block0.get_id()), Ok(None));
@@ -493,31 +650,123 @@ pub(crate) mod test {
         assert_eq!(store.get_best_block_id(), Ok(Some(block1.get_id())));
 
         // Chain index operations
-        let idx_tx0 = TxMainChainIndex::new(pos_tx0.into(), 1).expect("Tx index creation failed");
-        let out_id_tx0 = OutPointSourceId::from(tx0.get_id());
-        assert_eq!(store.get_mainchain_tx_index(&out_id_tx0), Ok(None));
-        assert_eq!(store.set_mainchain_tx_index(&out_id_tx0, &idx_tx0), Ok(()));
+        assert_eq!(store.get_is_mainchain_tx_index_enabled(), Ok(None));
+        assert_eq!(store.set_is_mainchain_tx_index_enabled(true), Ok(()));
+        assert_eq!(store.get_is_mainchain_tx_index_enabled(), Ok(Some(true)));
+        let tx0_id = tx0.get_id();
+        assert_eq!(store.get_mainchain_tx_index(&tx0_id), Ok(None));
+        assert_eq!(store.set_mainchain_tx_index(&tx0_id, &pos_tx0), Ok(()));
         assert_eq!(
-            store.get_mainchain_tx_index(&out_id_tx0),
-            Ok(Some(idx_tx0.clone()))
+            store.get_mainchain_tx_index(&tx0_id),
+            Ok(Some(pos_tx0.clone()))
         );
-        assert_eq!(store.del_mainchain_tx_index(&out_id_tx0), Ok(()));
-        assert_eq!(store.get_mainchain_tx_index(&out_id_tx0), Ok(None));
-        assert_eq!(store.set_mainchain_tx_index(&out_id_tx0, &idx_tx0), Ok(()));
+        assert_eq!(store.del_mainchain_tx_index(&tx0_id), Ok(()));
+        assert_eq!(store.get_mainchain_tx_index(&tx0_id), Ok(None));
+        assert_eq!(store.set_mainchain_tx_index(&tx0_id, &pos_tx0), Ok(()));
 
         // Retrieve transactions by ID using the index
-        assert_eq!(
-            store.get_mainchain_tx_index(&OutPointSourceId::from(tx1.get_id())),
-            Ok(None)
+        assert_eq!(store.get_mainchain_tx_index(&tx1.get_id()), Ok(None));
+        let pos = store.get_mainchain_tx_index(&tx0_id).unwrap().unwrap();
+        assert_eq!(store.get_mainchain_tx_by_position(&pos), Ok(Some(tx0)));
+    }
+
+    #[test]
+    #[cfg(not(loom))]
+    fn test_storage_reopen() {
+        use common::chain::block::{timestamp::BlockTimestamp, ConsensusData};
+
+        let block = Block::new(
+            vec![],
+            None,
+            BlockTimestamp::from_int_seconds(12),
+            ConsensusData::None,
+        )
+        .unwrap();
+        let dir = tempfile::tempdir().unwrap();
+
+        {
+            let mut store = Store::open(dir.path()).unwrap();
+            assert_eq!(store.add_block(&block), Ok(()));
+            assert_eq!(store.set_best_block_id(&block.get_id()), Ok(()));
+        }
+
+        let store = Store::open(dir.path()).unwrap();
+        assert_eq!(store.get_storage_version(), Ok(migration::STORAGE_VERSION));
+        assert_eq!(store.get_block(block.get_id()), Ok(Some(block.clone())));
+        assert_eq!(store.get_best_block_id(), Ok(Some(block.get_id())));
+    }
+
+    #[test]
+    #[cfg(not(loom))]
+    fn crash_during_block_commit() {
+        use common::chain::block::{timestamp::BlockTimestamp, ConsensusData};
+
+        let block = Block::new(
+            vec![],
+            None,
+            BlockTimestamp::from_int_seconds(12),
+            ConsensusData::None,
+        )
+        .unwrap();
+        let block_index = BlockIndex::new(
+            &block,
+            common::Uint256::from_u64(1),
+            None,
+            BlockHeight::new(1),
+            block.timestamp(),
         );
-        if let Ok(Some(index)) = store.get_mainchain_tx_index(&out_id_tx0) {
-            if let SpendablePosition::Transaction(ref p) = index.position() {
-                assert_eq!(store.get_mainchain_tx_by_position(p), Ok(Some(tx0)));
+        let outpoint = OutPoint::new(OutPointSourceId::BlockReward(block.get_id()), 0);
+        let utxo = create_rand_utxo(1);
+        let block_undo = create_rand_block_undo(3, 3, BlockHeight::new(1));
+
+        // Persist everything a block connection writes in one transaction
+        let connect_block = |store: &Store| {
+            store.transaction_rw().run(|tx| {
+                tx.add_block(&block)?;
+                tx.set_block_index(&block_index)?;
+                tx.set_best_block_id(&block.get_id())?;
+                tx.add_utxo(&outpoint, utxo.clone())?;
+                tx.set_best_block_for_utxos(&block.get_id())?;
+                tx.add_undo_data(block.get_id(), &block_undo)?;
+                storage::commit(())
+            })
+        };
+
+        for units in 0.. {
+            let dir = tempfile::tempdir().unwrap();
+            let store = Store::open(dir.path()).unwrap();
+            let result = storage::crash::run_crashing_after(units, || connect_block(&store));
+            drop(store);
+
+            let store = Store::open(dir.path()).unwrap();
+            let connected = store.get_best_block_id().unwrap().is_some();
+            if connected {
+                assert_eq!(store.get_block(block.get_id()), Ok(Some(block.clone())));
+                assert_eq!(
+                    store
+                        .get_block_index(&block.get_id())
+                        .map(|bi| bi.map(|bi| bi.block_id().clone())),
+                    Ok(Some(block.get_id()))
+                );
+                assert_eq!(store.get_utxo(&outpoint), Ok(Some(utxo.clone())));
+                assert_eq!(store.get_best_block_for_utxos(), Ok(Some(block.get_id())));
+                assert_eq!(
+                    store.get_undo_data(block.get_id()),
+                    Ok(Some(block_undo.clone()))
+                );
             } else {
-                unreachable!();
-            };
-        } else {
-            unreachable!();
+                assert_eq!(store.get_block(block.get_id()), Ok(None));
+                assert!(store.get_block_index(&block.get_id()).unwrap().is_none());
+                assert_eq!(store.get_utxo(&outpoint), Ok(None));
+                assert_eq!(store.get_best_block_for_utxos(), Ok(None));
+                assert_eq!(store.get_undo_data(block.get_id()), Ok(None));
+            }
+
+            if let Some(result) = result {
+                assert_eq!(result, Ok(()));
+                assert!(connected);
+                break;
+            }
         }
     }
 
@@ -672,7 +921,7 @@ pub(crate) mod test {
             block_undo.push(TxUndo::new(tx_undo));
         }
 
-        BlockUndo::new(block_undo, block_height)
+        BlockUndo::new(None, block_undo, block_height)
     }
 
     #[cfg(not(loom))]
@@ -716,4 +965,65 @@ pub(crate) mod test {
         assert_eq!(store.add_undo_data(id1.clone(), &block_undo1), Ok(()));
         assert_eq!(store.get_undo_data(id1).unwrap().unwrap(), block_undo1);
     }
+
+    #[test]
+    fn address_history_paging() {
+        let mut store = Store::new_empty().unwrap();
+        let (_, pub_key) = PrivateKey::new(KeyKind::RistrettoSchnorr);
+        let key = AddressIndexKey::from_destination(&Destination::PublicKey(pub_key)).unwrap();
+        let other_key = AddressIndexKey::ScriptHash(Id::new(H256::random()));
+
+        // Insert the entries out of order, they are returned ordered by height
+        let entry = |height: u64, spent_by: Option<OutPointSourceId>| {
+            let source_id = OutPointSourceId::Transaction(Id::new(H256::random()));
+            let output = create_rand_utxo(height).output().clone();
+            AddressHistoryEntry::new(
+                OutPoint::new(source_id, 0),
+                output,
+                BlockHeight::new(height),
+                spent_by,
+            )
+        };
+        let entries: Vec<_> = (0..5).map(|height| entry(height, None)).collect();
+        for entry in entries.iter().rev() {
+            assert_eq!(store.set_address_history_entry(&key, entry), Ok(()));
+        }
+        assert_eq!(
+            store.set_address_history_entry(&other_key, &entry(2, None)),
+            Ok(())
+        );
+
+        assert_eq!(store.get_address_history(&key, 0, 10), Ok(entries.clone()));
+        assert_eq!(
+            store.get_address_history(&key, 1, 2),
+            Ok(entries[1..3].to_vec())
+        );
+        assert_eq!(store.get_address_history(&key, 5, 2), Ok(vec![]));
+        assert_eq!(
+            store.get_address_history(&other_key, 0, 10).unwrap().len(),
+            1
+        );
+
+        // Mark an output spent and remove another one
+        let spent = AddressHistoryEntry::new(
+            entries[1].outpoint().clone(),
+            entries[1].output().clone(),
+            entries[1].block_height(),
+            Some(OutPointSourceId::Transaction(Id::new(H256::random()))),
+        );
+        assert_eq!(store.set_address_history_entry(&key, &spent), Ok(()));
+        assert_eq!(
+            store.del_address_history_entry(&key, BlockHeight::new(3), entries[3].outpoint()),
+            Ok(())
+        );
+        assert_eq!(
+            store.get_address_history(&key, 0, 10),
+            Ok(vec![
+                entries[0].clone(),
+                spent,
+                entries[2].clone(),
+                entries[4].clone()
+            ])
+        );
+    }
 }
@@ -0,0 +1,264 @@
+//! Backup and restore of blockchain storage
+//!
+//! A backup is a single file containing a snapshot of the whole storage. It can be taken while
+//! the storage is in use and is restored into an empty directory, which is then checked for
+//! consistency before the storage is handed over to the caller.
+
+use std::path::Path;
+
+use super::{Schema, Store};
+use crate::{
+    BackupError, BlockchainStorageRead, ConsistencyError, OpenError, RestoreError, Transactional,
+};
+use common::primitives::BlockHeight;
+use storage::traits::TransactionRo;
+
+impl Store {
+    /// Write a snapshot of the storage into given file, which must not exist yet.
+    ///
+    /// The snapshot is consistent with a single read-only transaction. The storage can be used
+    /// while the backup is in progress.
+    pub fn backup(&self, path: impl AsRef<Path>) -> Result<(), BackupError> {
+        let path = path.as_ref();
+        // The storage never overwrites files either, this gives a clear error in the common case
+        if path.symlink_metadata().is_ok() {
+            return Err(BackupError::TargetExists);
+        }
+        Ok(self.0.backup(path)?)
+    }
+
+    /// Restore storage from a backup file into given directory, which must be empty.
+    ///
+    /// The restored storage is migrated to the current version and checked for consistency.
+    pub fn restore(backup: impl AsRef<Path>, path: impl AsRef<Path>) -> Result<Self, RestoreError> {
+        let path = path.as_ref();
+        let target_empty = match std::fs::read_dir(path) {
+            Ok(mut entries) => entries.next().is_none(),
+            Err(e) => e.kind() == std::io::ErrorKind::NotFound,
+        };
+        if !target_empty {
+            return Err(RestoreError::TargetNotEmpty);
+        }
+
+        storage::Store::<Schema>::restore(backup, path).map_err(OpenError::from)?;
+        let store = Self::open(path)?;
+        store.check_consistency()?;
+        Ok(store)
+    }
+
+    /// Check the mainchain recorded in the storage is complete and agrees with the best block.
+    pub fn check_consistency(&self) -> Result<(), ConsistencyError> {
+        let tx = self.transaction_ro();
+        let result = check_mainchain(&tx);
+        tx.finalize()?;
+        result
+    }
+}
+
+// Walk the mainchain from the best block to genesis, checking all the blocks, their indices and
+// the height to block ID mapping are present and agree with each other.
+fn check_mainchain(tx: &impl BlockchainStorageRead) -> Result<(), ConsistencyError> {
+    let best_block_id = tx.get_best_block_id()?.ok_or(ConsistencyError::NoBestBlock)?;
+    let mut block_index = tx
+        .get_block_index(&best_block_id)?
+        .ok_or_else(|| ConsistencyError::MissingBlockIndex(best_block_id.clone()))?;
+
+    let above_best = block_index.block_height().next_height();
+    if let Some(id) = tx.get_block_id_by_height(&above_best)? {
+        return Err(ConsistencyError::BlockAboveBestBlock(above_best, id));
+    }
+
+    loop {
+        let block_id = block_index.block_id().clone();
+        let height = block_index.block_height();
+
+        let mainchain_id = tx.get_block_id_by_height(&height)?;
+        if mainchain_id.as_ref() != Some(&block_id) {
+            return Err(ConsistencyError::MainchainMismatch(
+                height,
+                mainchain_id,
+                block_id,
+            ));
+        }
+        if tx.get_block(block_id.clone())?.is_none() {
+            return Err(ConsistencyError::MissingBlock(block_id));
+        }
+
+        let prev_block_id = match block_index.prev_block_id() {
+            Some(prev_block_id) => prev_block_id.clone(),
+            None if height == BlockHeight::new(0) => return Ok(()),
+            None => return Err(ConsistencyError::UnexpectedHeight(block_id, height)),
+        };
+        let prev_index = tx
+            .get_block_index(&prev_block_id)?
+            .ok_or(ConsistencyError::MissingBlockIndex(prev_block_id))?;
+        if prev_index.block_height().next_height() != height {
+            return Err(ConsistencyError::UnexpectedHeight(block_id, height));
+        }
+        block_index = prev_index;
+    }
+}
+
+#[cfg(all(test, not(loom)))]
+mod test {
+    use super::*;
+    use crate::BlockchainStorageWrite;
+    use chainstate_types::block_index::BlockIndex;
+    use common::chain::block::{timestamp::BlockTimestamp, Block, ConsensusData};
+    use common::primitives::{BlockDistance, Id, Idable};
+    use storage::traits::TransactionRw;
+
+    // Extend the mainchain in the store by given number of blocks
+    fn extend_chain(store: &mut Store, count: u64) -> Vec<Id<Block>> {
+        let mut prev = store.get_best_block_id().unwrap();
+        let start_height = match &prev {
+            Some(id) => store.get_block_index(id).unwrap().unwrap().block_height().next_height(),
+            None => BlockHeight::new(0),
+        };
+
+        let mut ids = Vec::new();
+        for i in 0..count {
+            let height = (start_height + BlockDistance::new(i as i64)).unwrap();
+            let block = Block::new(
+                vec![],
+                prev.clone(),
+                BlockTimestamp::from_int_seconds(u64::from(height) as u32),
+                ConsensusData::None,
+            )
+            .unwrap();
+            let block_index = BlockIndex::new(
+                &block,
+                common::Uint256::from_u64(u64::from(height)),
+                None,
+                height,
+                block.timestamp(),
+            );
+            store
+                .transaction_rw()
+                .run(|tx| {
+                    tx.add_block(&block)?;
+                    tx.set_block_index(&block_index)?;
+                    tx.set_block_id_at_height(&height, &block.get_id())?;
+                    tx.set_best_block_id(&block.get_id())?;
+                    storage::commit(())
+                })
+                .unwrap();
+            prev = Some(block.get_id());
+            ids.push(block.get_id());
+        }
+        ids
+    }
+
+    #[test]
+    fn backup_and_restore() {
+        let dir = tempfile::tempdir().unwrap();
+        let backup_path = dir.path().join("backup");
+        let mut store = Store::new_empty().unwrap();
+        let ids = extend_chain(&mut store, 5);
+
+        assert_eq!(store.backup(&backup_path), Ok(()));
+        extend_chain(&mut store, 2);
+        assert_eq!(store.backup(&backup_path), Err(BackupError::TargetExists));
+
+        let restored = Store::restore(&backup_path, dir.path().join("restored")).unwrap();
+        assert_eq!(restored.get_best_block_id(), Ok(ids.last().cloned()));
+        for (height, id) in ids.iter().enumerate() {
+            let height = BlockHeight::new(height as u64);
+            assert_eq!(
+                restored.get_block_id_by_height(&height),
+                Ok(Some(id.clone()))
+            );
+        }
+        assert_eq!(restored.check_consistency(), Ok(()));
+    }
+
+    #[test]
+    fn backup_during_block_processing() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut store = Store::new_empty().unwrap();
+        extend_chain(&mut store, 1);
+
+        let writer = {
+            let mut store = store.clone();
+            std::thread::spawn(move || extend_chain(&mut store, 50))
+        };
+        for i in 0..10 {
+            let backup_path = dir.path().join(format!("backup{}", i));
+            assert_eq!(store.backup(&backup_path), Ok(()));
+            let restored = Store::restore(&backup_path, dir.path().join(format!("restored{}", i)));
+            assert!(
+                restored.is_ok(),
+                "Backup {} inconsistent: {:?}",
+                i,
+                restored.err()
+            );
+        }
+        writer.join().unwrap();
+    }
+
+    #[test]
+    fn restore_into_nonempty_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let backup_path = dir.path().join("backup");
+        let mut store = Store::new_empty().unwrap();
+        extend_chain(&mut store, 1);
+        assert_eq!(store.backup(&backup_path), Ok(()));
+
+        let target = dir.path().join("target");
+        std::fs::create_dir(&target).unwrap();
+        std::fs::write(target.join("file"), b"data").unwrap();
+        assert_eq!(
+            Store::restore(&backup_path, &target).err(),
+            Some(RestoreError::TargetNotEmpty)
+        );
+    }
+
+    #[test]
+    fn restore_inconsistent_backup() {
+        let dir = tempfile::tempdir().unwrap();
+        let backup_path = dir.path().join("backup");
+        let mut store = Store::new_empty().unwrap();
+        let ids = extend_chain(&mut store, 3);
+        store.del_block(ids[1].clone()).unwrap();
+        assert_eq!(store.backup(&backup_path), Ok(()));
+
+        assert_eq!(
+            Store::restore(&backup_path, dir.path().join("restored")).err(),
+            Some(RestoreError::Inconsistent(ConsistencyError::MissingBlock(
+                ids[1].clone()
+            )))
+        );
+    }
+
+    #[test]
+    fn consistency_check() {
+        let mut store = Store::new_empty().unwrap();
+        assert_eq!(
+            store.check_consistency(),
+            Err(ConsistencyError::NoBestBlock)
+        );
+
+        let ids = extend_chain(&mut store, 4);
+        assert_eq!(store.check_consistency(), Ok(()));
+
+        store.set_block_id_at_height(&BlockHeight::new(2), &ids[3]).unwrap();
+        assert_eq!(
+            store.check_consistency(),
+            Err(ConsistencyError::MainchainMismatch(
+                BlockHeight::new(2),
+                Some(ids[3].clone()),
+                ids[2].clone()
+            ))
+        );
+        store.set_block_id_at_height(&BlockHeight::new(2), &ids[2]).unwrap();
+
+        store.set_best_block_id(&ids[2]).unwrap();
+        assert_eq!(
+            store.check_consistency(),
+            Err(ConsistencyError::BlockAboveBestBlock(
+                BlockHeight::new(3),
+                ids[3].clone()
+            ))
+        );
+    }
+}
@@ -0,0 +1,644 @@
+//! Storage schema versioning and migrations
+//!
+//! Each change to the database layout bumps [STORAGE_VERSION] and registers a migration that
+//! upgrades the data from the previous version to the new one. When a store is opened, all the
+//! migrations needed to bring it to the current version are run in a single write transaction,
+//! so either all of them take effect or none does.
+
+use std::ops::Bound;
+
+use super::{
+    well_known, DBBlock, DBBlockByHeight, DBBlockChildren, DBBlockIndex, DBBlockUndo, DBTxIndex,
+    DBUtxo, DBValue, RwTxImpl,
+};
+use crate::OpenError as Error;
+use chainstate_types::block_index::{BlockIndex, BlockStatus};
+use common::chain::{
+    block::Block, signature::Transactable, OutPoint, OutPointSourceId, OutputSpentState,
+    SpendablePosition, Transaction, TxInput, TxMainChainIndex, TxOutput,
+};
+use common::primitives::{BlockHeight, Idable};
+use serialization::{DecodeAll, Encode};
+use storage::traits::{GetMapMut, GetMapRef, MapMut, MapRef, MultiMapMut};
+use utxo::{BlockUndo, TxUndo, Utxo};
+use well_known::Entry;
+
+/// Version of the storage schema used by this software
+pub const STORAGE_VERSION: u32 = 4;
+
+/// Upgrade the data stored in the database by one version
+pub type MigrationFn = for<'tx> fn(&mut RwTxImpl<'tx>) -> storage::Result<()>;
+
+/// A migration step, upgrading storage from version `from_version` to `from_version + 1`
+pub struct Migration {
+    pub from_version: u32,
+    pub description: &'static str,
+    pub run: MigrationFn,
+}
+
+/// Registered migrations, ordered by version
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        from_version: 1,
+        description: "Add validation status to block indices",
+        run: add_block_status,
+    },
+    Migration {
+        from_version: 2,
+        description: "Build the UTXO set and block undo data from the transaction index",
+        run: tx_index_to_utxo_set,
+    },
+    Migration {
+        from_version: 3,
+        description: "Index the children of each block",
+        run: index_block_children,
+    },
+];
+
+// Version 1 block indices had no validation status. It is encoded last, so it can be appended to
+// the old encoding. Mainchain blocks have been connected, the others have only been checked.
+fn add_block_status(tx: &mut RwTxImpl<'_>) -> storage::Result<()> {
+    let entries: Vec<_> = tx
+        .get::<DBBlockIndex, _>()
+        .iter()?
+        .map(|(key, val)| (key.to_vec(), val.to_vec()))
+        .collect();
+
+    for (key, mut data) in entries {
+        data.extend(BlockStatus::HeaderValid.encode());
+        let mut block_index =
+            BlockIndex::decode_all(&mut &data[..]).expect("Cannot decode block index");
+
+        let height = block_index.block_height().encode();
+        let by_height = tx.get::<DBBlockByHeight, _>();
+        if by_height.get(&height)? == Some(block_index.block_id().as_ref()) {
+            block_index.set_status(BlockStatus::FullyValid);
+        }
+        tx.get_mut::<DBBlockIndex, _>().put(key, block_index.encode())?;
+    }
+    Ok(())
+}
+
+fn corrupted() -> storage::Error {
+    storage::Error::Fatal(storage::error::Fatal::DatabaseCorrupted)
+}
+
+fn read_block_index(tx: &RwTxImpl<'_>, id: &[u8]) -> storage::Result<BlockIndex> {
+    let col = tx.get::<DBBlockIndex, _>();
+    let data = col.get(id)?.ok_or_else(corrupted)?;
+    Ok(BlockIndex::decode_all(&mut &data[..]).expect("Cannot decode block index"))
+}
+
+fn read_block(tx: &RwTxImpl<'_>, id: &[u8]) -> storage::Result<Block> {
+    let col = tx.get::<DBBlock, _>();
+    let data = col.get(id)?.ok_or_else(corrupted)?;
+    Ok(Block::decode_all(&mut &data[..]).expect("Cannot decode block"))
+}
+
+// Outputs of a transaction or a block reward indexed in version 2, along with what is needed to
+// turn them into UTXOs
+struct IndexedOutputs {
+    outputs: Vec<TxOutput>,
+    is_block_reward: bool,
+    height: BlockHeight,
+    index: TxMainChainIndex,
+}
+
+fn load_indexed_outputs(
+    tx: &RwTxImpl<'_>,
+    index: TxMainChainIndex,
+) -> storage::Result<IndexedOutputs> {
+    let block_id = index.position().block_id_anyway().clone();
+    let block_index = read_block_index(tx, block_id.as_ref())?;
+    let (outputs, is_block_reward) = match index.position() {
+        SpendablePosition::Transaction(pos) => {
+            let col = tx.get::<DBBlock, _>();
+            let block = col.get(block_id.as_ref())?.ok_or_else(corrupted)?;
+            let begin = pos.byte_offset_in_block() as usize;
+            let end = begin + pos.serialized_size() as usize;
+            let encoded_tx = block.get(begin..end).ok_or_else(corrupted)?;
+            let transaction =
+                Transaction::decode_all(&mut &*encoded_tx).expect("Invalid tx encoding in DB");
+            (transaction.outputs().clone(), false)
+        }
+        SpendablePosition::BlockReward(_) => {
+            let reward = block_index.block_header().block_reward_transactable();
+            (reward.outputs().unwrap_or(&[]).to_vec(), true)
+        }
+    };
+    Ok(IndexedOutputs {
+        outputs,
+        is_block_reward,
+        height: block_index.block_height(),
+        index,
+    })
+}
+
+// The entry following given key in the map, or the first entry if no key is given. Going through
+// a map this way holds a single entry at a time and leaves the transaction free for writes.
+fn next_entry(
+    map: &impl MapRef,
+    after: Option<&[u8]>,
+) -> storage::Result<Option<(Vec<u8>, Vec<u8>)>> {
+    let start = match after {
+        Some(key) => Bound::Excluded(key.to_vec()),
+        None => Bound::Unbounded,
+    };
+    let mut iter = map.range_iter((start, Bound::Unbounded))?;
+    Ok(iter.next().map(|(key, val)| (key.to_vec(), val.to_vec())))
+}
+
+// The UTXOs spent by given inputs, looked up through the version 2 transaction index
+fn spent_utxos(tx: &RwTxImpl<'_>, inputs: &[TxInput]) -> storage::Result<TxUndo> {
+    let utxos = inputs
+        .iter()
+        .map(|input| {
+            let outpoint = input.outpoint();
+            let col = tx.get::<DBTxIndex, _>();
+            let data = col.get(outpoint.tx_id().encode().as_ref())?.ok_or_else(corrupted)?;
+            let index =
+                TxMainChainIndex::decode_all(&mut &data[..]).expect("Cannot decode tx index");
+            let indexed = load_indexed_outputs(tx, index)?;
+            let output = indexed.outputs.get(outpoint.output_index() as usize);
+            let output = output.ok_or_else(corrupted)?.clone();
+            Ok(Utxo::new(output, indexed.is_block_reward, indexed.height))
+        })
+        .collect::<storage::Result<_>>()?;
+    Ok(TxUndo::new(utxos))
+}
+
+// Version 2 tracked spending with a per-transaction bitmap of spent outputs in the transaction
+// index. Version 3 keeps the unspent outputs in the UTXO set and, for each mainchain block, the
+// outputs it spent so that it can be disconnected. The transaction index is dropped.
+fn tx_index_to_utxo_set(tx: &mut RwTxImpl<'_>) -> storage::Result<()> {
+    // Undo data of the mainchain blocks, the genesis spends nothing. The spent outputs are looked
+    // up in the transaction index, so this is done before the index is dropped.
+    let mut key = None;
+    while let Some((height_key, block_id)) =
+        next_entry(&tx.get::<DBBlockByHeight, _>(), key.as_deref())?
+    {
+        let height = BlockHeight::decode_all(&mut &height_key[..]).expect("Cannot decode height");
+        if height != BlockHeight::new(0) {
+            let block = read_block(tx, &block_id)?;
+            let reward_undo = match block.header().block_reward_transactable().inputs() {
+                Some(inputs) => Some(spent_utxos(tx, inputs)?),
+                None => None,
+            };
+            let tx_undos = block
+                .transactions()
+                .iter()
+                .map(|transaction| spent_utxos(tx, transaction.inputs()))
+                .collect::<storage::Result<_>>()?;
+            let undo = BlockUndo::new(reward_undo, tx_undos, height);
+            tx.get_mut::<DBBlockUndo, _>().put(block.get_id().encode(), undo.encode())?;
+        }
+        key = Some(height_key);
+    }
+
+    // The UTXO set, built as the transaction index entries are dropped one by one
+    let mut key = None;
+    while let Some((source_key, data)) = next_entry(&tx.get::<DBTxIndex, _>(), key.as_deref())? {
+        let source_id =
+            OutPointSourceId::decode_all(&mut &source_key[..]).expect("Cannot decode tx index key");
+        let index = TxMainChainIndex::decode_all(&mut &data[..]).expect("Cannot decode tx index");
+        let indexed = load_indexed_outputs(tx, index)?;
+        for (output_index, output) in indexed.outputs.into_iter().enumerate() {
+            let output_index = output_index as u32;
+            let spent_state =
+                indexed.index.get_spent_state(output_index).map_err(|_| corrupted())?;
+            if spent_state == OutputSpentState::Unspent {
+                let outpoint = OutPoint::new(source_id.clone(), output_index);
+                let utxo = Utxo::new(output, indexed.is_block_reward, indexed.height);
+                tx.get_mut::<DBUtxo, _>().put(outpoint.encode(), utxo.encode())?;
+            }
+        }
+        tx.get_mut::<DBTxIndex, _>().del(&source_key)?;
+        key = Some(source_key);
+    }
+
+    let best_block_id =
+        tx.get::<DBValue, _>().get(well_known::BestBlockId::KEY)?.map(ToOwned::to_owned);
+    if let Some(best_block_id) = best_block_id {
+        tx.get_mut::<DBValue, _>()
+            .put(well_known::UtxosBestBlockId::KEY.to_vec(), best_block_id)?;
+    }
+
+    // The new indices start out empty, so they cannot be enabled on a migrated store
+    let mut col = tx.get_mut::<DBValue, _>();
+    col.put(well_known::TxIndexEnabled::KEY.to_vec(), false.encode())?;
+    col.put(
+        well_known::AddressIndexEnabled::KEY.to_vec(),
+        false.encode(),
+    )?;
+
+    Ok(())
+}
+
+// Version 4 records the children of each block so that the descendants of a block can be found
+// without going through all the block indices.
+fn index_block_children(tx: &mut RwTxImpl<'_>) -> storage::Result<()> {
+    let children: Vec<_> = tx
+        .get::<DBBlockIndex, _>()
+        .iter()?
+        .filter_map(|(_, data)| {
+            let block_index =
+                BlockIndex::decode_all(&mut &data[..]).expect("Cannot decode block index");
+            let prev_block_id = block_index.prev_block_id().as_ref()?.encode();
+            Some((prev_block_id, block_index.block_id().encode()))
+        })
+        .collect();
+
+    let mut col = tx.get_multi_mut::<DBBlockChildren, _>();
+    for (prev_block_id, block_id) in children {
+        col.put(prev_block_id, block_id)?;
+    }
+    Ok(())
+}
+
+/// Bring the storage to given version using given migrations.
+///
+/// Storage with no version recorded is considered empty and just gets the version set.
+pub fn upgrade(
+    tx: &mut RwTxImpl<'_>,
+    migrations: &[Migration],
+    target_version: u32,
+) -> Result<(), Error> {
+    let mut version = match get_version(tx)? {
+        Some(version) => version,
+        None => return set_version(tx, target_version).map_err(Into::into),
+    };
+
+    if version > target_version {
+        return Err(Error::VersionTooNew(version, target_version));
+    }
+
+    while version < target_version {
+        let migration = migrations
+            .iter()
+            .find(|m| m.from_version == version)
+            .ok_or(Error::MissingMigration(version))?;
+        logging::log::info!(
+            "Migrating storage from version {} to {}: {}",
+            version,
+            version + 1,
+            migration.description
+        );
+        (migration.run)(tx)?;
+        version += 1;
+        set_version(tx, version)?;
+    }
+
+    Ok(())
+}
+
+fn get_version(tx: &RwTxImpl<'_>) -> storage::Result<Option<u32>> {
+    let col = storage::traits::GetMapRef::get::<DBValue, _>(tx);
+    let version = col
+        .get(well_known::StoreVersion::KEY)?
+        .map(|mut data| u32::decode_all(&mut data).expect("Cannot decode storage version"));
+    Ok(version)
+}
+
+fn set_version(tx: &mut RwTxImpl<'_>, version: u32) -> storage::Result<()> {
+    let mut col = tx.get_mut::<DBValue, _>();
+    col.put(well_known::StoreVersion::KEY.to_vec(), version.encode())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::BlockchainStorageRead;
+    use common::chain::block::Block;
+    use common::primitives::{Id, H256};
+    use storage::traits::{GetMapRef, TransactionRo, TransactionRw, Transactional};
+
+    // Version 1 layout kept the best block ID in a differently named entry, version 2 moved it
+    // to the entry used today.
+    fn move_best_block_id(tx: &mut RwTxImpl<'_>) -> storage::Result<()> {
+        let mut col = tx.get_mut::<DBValue, _>();
+        if let Some(id) = col.get(b"BestBlock")?.map(ToOwned::to_owned) {
+            col.del(b"BestBlock")?;
+            col.put(well_known::BestBlockId::KEY.to_vec(), id)?;
+        }
+        Ok(())
+    }
+
+    // Version 2 layout had an extra counter that version 3 no longer uses.
+    fn drop_counter(tx: &mut RwTxImpl<'_>) -> storage::Result<()> {
+        tx.get_mut::<DBValue, _>().del(b"Counter")
+    }
+
+    fn fail(_tx: &mut RwTxImpl<'_>) -> storage::Result<()> {
+        Err(storage::Error::Fatal(
+            storage::error::Fatal::DatabaseCorrupted,
+        ))
+    }
+
+    const TEST_MIGRATIONS: &[Migration] = &[
+        Migration {
+            from_version: 1,
+            description: "Move best block ID",
+            run: move_best_block_id,
+        },
+        Migration {
+            from_version: 2,
+            description: "Drop counter",
+            run: drop_counter,
+        },
+    ];
+
+    // Create a store at version 1 with the old layout
+    fn create_v1_store(path: &std::path::Path, best_block_id: &Id<Block>) {
+        let store = storage::Store::<super::super::Schema>::open(path).unwrap();
+        let mut tx = store.transaction_rw();
+        let mut col = tx.get_mut::<DBValue, _>();
+        col.put(well_known::StoreVersion::KEY.to_vec(), 1u32.encode()).unwrap();
+        col.put(b"BestBlock".to_vec(), best_block_id.encode()).unwrap();
+        col.put(b"Counter".to_vec(), 5u32.encode()).unwrap();
+        tx.commit().unwrap();
+    }
+
+    #[test]
+    fn migrate_old_store() {
+        let dir = tempfile::tempdir().unwrap();
+        let best_block_id = Id::new(H256::random());
+        create_v1_store(dir.path(), &best_block_id);
+
+        let store = crate::Store::open_with_migrations(dir.path(), TEST_MIGRATIONS, 3).unwrap();
+        assert_eq!(store.get_storage_version(), Ok(3));
+        assert_eq!(store.get_best_block_id(), Ok(Some(best_block_id.clone())));
+        drop(store);
+
+        // Migrations persist and running them again is a no-op
+        let store = crate::Store::open_with_migrations(dir.path(), TEST_MIGRATIONS, 3).unwrap();
+        assert_eq!(store.get_storage_version(), Ok(3));
+        assert_eq!(store.get_best_block_id(), Ok(Some(best_block_id)));
+        let counter = storage::Store::<super::super::Schema>::transaction_ro(&store.0)
+            .run(|tx| Ok(tx.get::<DBValue, _>().get(b"Counter")?.is_some()));
+        assert_eq!(counter, Ok(false));
+    }
+
+    #[test]
+    fn partial_migration() {
+        let dir = tempfile::tempdir().unwrap();
+        let best_block_id = Id::new(H256::random());
+        create_v1_store(dir.path(), &best_block_id);
+
+        let store = crate::Store::open_with_migrations(dir.path(), TEST_MIGRATIONS, 2).unwrap();
+        assert_eq!(store.get_storage_version(), Ok(2));
+        assert_eq!(store.get_best_block_id(), Ok(Some(best_block_id)));
+    }
+
+    #[test]
+    fn version_too_new() {
+        let dir = tempfile::tempdir().unwrap();
+        create_v1_store(dir.path(), &Id::new(H256::random()));
+
+        crate::Store::open_with_migrations(dir.path(), TEST_MIGRATIONS, 3).unwrap();
+        assert_eq!(
+            crate::Store::open_with_migrations(dir.path(), TEST_MIGRATIONS, 2).err(),
+            Some(Error::VersionTooNew(3, 2)),
+        );
+    }
+
+    #[test]
+    fn missing_migration() {
+        let dir = tempfile::tempdir().unwrap();
+        create_v1_store(dir.path(), &Id::new(H256::random()));
+
+        assert_eq!(
+            crate::Store::open_with_migrations(dir.path(), TEST_MIGRATIONS, 4).err(),
+            Some(Error::MissingMigration(3)),
+        );
+    }
+
+    #[test]
+    fn failed_migration_leaves_store_intact() {
+        let dir = tempfile::tempdir().unwrap();
+        let best_block_id = Id::new(H256::random());
+        create_v1_store(dir.path(), &best_block_id);
+
+        let migrations = [
+            Migration {
+                from_version: 1,
+                description: "Move best block ID",
+                run: move_best_block_id,
+            },
+            Migration {
+                from_version: 2,
+                description: "Fail",
+                run: fail,
+            },
+        ];
+        assert_eq!(
+            crate::Store::open_with_migrations(dir.path(), &migrations, 3).err(),
+            Some(Error::Storage(storage::Error::Fatal(
+                storage::error::Fatal::DatabaseCorrupted
+            ))),
+        );
+
+        let store = crate::Store::open_with_migrations(dir.path(), &[], 1).unwrap();
+        assert_eq!(store.get_storage_version(), Ok(1));
+        assert_eq!(store.get_best_block_id(), Ok(None));
+    }
+
+    #[test]
+    fn block_status_added() {
+        use common::chain::block::{timestamp::BlockTimestamp, ConsensusData};
+        use common::primitives::BlockHeight;
+        use common::Uint256;
+
+        let dir = tempfile::tempdir().unwrap();
+        let make_index = |timestamp| {
+            let block = Block::new(
+                vec![],
+                None,
+                BlockTimestamp::from_int_seconds(timestamp),
+                ConsensusData::None,
+            )
+            .unwrap();
+            let height = BlockHeight::new(0);
+            BlockIndex::new(
+                &block,
+                Uint256::from_u64(0),
+                None,
+                height,
+                block.timestamp(),
+            )
+        };
+        let mainchain = make_index(1);
+        let side = make_index(2);
+
+        {
+            let store = storage::Store::<super::super::Schema>::open(dir.path()).unwrap();
+            let mut tx = store.transaction_rw();
+            let mut col = tx.get_mut::<DBValue, _>();
+            col.put(well_known::StoreVersion::KEY.to_vec(), 1u32.encode()).unwrap();
+            let mut col = tx.get_mut::<DBBlockIndex, _>();
+            for block_index in [&mainchain, &side] {
+                // Version 1 encoding lacks the trailing status byte
+                let mut data = block_index.encode();
+                data.pop();
+                col.put(block_index.block_id().encode(), data).unwrap();
+            }
+            let mut col = tx.get_mut::<DBBlockByHeight, _>();
+            col.put(BlockHeight::new(0).encode(), mainchain.block_id().encode()).unwrap();
+            tx.commit().unwrap();
+        }
+
+        let store = crate::Store::open(dir.path()).unwrap();
+        let status = |id| store.get_block_index(id).unwrap().unwrap().status();
+        assert_eq!(status(mainchain.block_id()), BlockStatus::FullyValid);
+        assert_eq!(status(side.block_id()), BlockStatus::HeaderValid);
+    }
+
+    #[test]
+    fn utxo_set_built_from_tx_index() {
+        use crate::{UndoRead, UtxoRead};
+        use common::chain::block::{timestamp::BlockTimestamp, ConsensusData};
+        use common::chain::signature::inputsig::InputWitness;
+        use common::chain::{calculate_tx_index_from_block, Destination, OutputPurpose, Spender};
+        use common::primitives::Amount;
+        use common::Uint256;
+
+        let output = |atoms| {
+            TxOutput::new(
+                Amount::from_atoms(atoms),
+                OutputPurpose::Transfer(Destination::AnyoneCanSpend),
+            )
+        };
+        let make_block = |transactions, prev_block_id| {
+            Block::new(
+                transactions,
+                prev_block_id,
+                BlockTimestamp::from_int_seconds(1),
+                ConsensusData::None,
+            )
+            .unwrap()
+        };
+
+        // genesis creates one output, the next block spends it and creates two more
+        let genesis_tx = Transaction::new(0, vec![], vec![output(10)], 0).unwrap();
+        let genesis = make_block(vec![genesis_tx.clone()], None);
+        let input = TxInput::new(
+            genesis_tx.get_id().into(),
+            0,
+            InputWitness::NoSignature(None),
+        );
+        let block_tx = Transaction::new(0, vec![input], vec![output(4), output(5)], 0).unwrap();
+        let block = make_block(vec![block_tx.clone()], Some(genesis.get_id()));
+
+        let dir = tempfile::tempdir().unwrap();
+        {
+            let store = storage::Store::<super::super::Schema>::open(dir.path()).unwrap();
+            let mut tx = store.transaction_rw();
+            let mut col = tx.get_mut::<DBValue, _>();
+            col.put(well_known::StoreVersion::KEY.to_vec(), 2u32.encode()).unwrap();
+            col.put(
+                well_known::BestBlockId::KEY.to_vec(),
+                block.get_id().encode(),
+            )
+            .unwrap();
+            for (height, block) in [&genesis, &block].into_iter().enumerate() {
+                let height = BlockHeight::new(height as u64);
+                let block_index =
+                    BlockIndex::new(block, Uint256::from_u64(0), None, height, block.timestamp());
+                let id = block.get_id().encode();
+                tx.get_mut::<DBBlock, _>().put(id.clone(), block.encode()).unwrap();
+                tx.get_mut::<DBBlockIndex, _>().put(id.clone(), block_index.encode()).unwrap();
+                tx.get_mut::<DBBlockByHeight, _>().put(height.encode(), id).unwrap();
+            }
+            let mut genesis_tx_index = calculate_tx_index_from_block(&genesis, 0).unwrap();
+            genesis_tx_index.spend(0, Spender::from(block_tx.get_id())).unwrap();
+            let block_tx_index = calculate_tx_index_from_block(&block, 0).unwrap();
+            let mut col = tx.get_mut::<DBTxIndex, _>();
+            for (tx_id, tx_index) in
+                [(genesis_tx.get_id(), genesis_tx_index), (block_tx.get_id(), block_tx_index)]
+            {
+                col.put(OutPointSourceId::from(tx_id).encode(), tx_index.encode()).unwrap();
+            }
+            tx.commit().unwrap();
+        }
+
+        let store = crate::Store::open(dir.path()).unwrap();
+        let outpoint = |tx: &Transaction, index| OutPoint::new(tx.get_id().into(), index);
+        let height = BlockHeight::new(1);
+        assert_eq!(store.get_utxo(&outpoint(&genesis_tx, 0)), Ok(None));
+        assert_eq!(
+            store.get_utxo(&outpoint(&block_tx, 0)),
+            Ok(Some(Utxo::new(output(4), false, height)))
+        );
+        assert_eq!(
+            store.get_utxo(&outpoint(&block_tx, 1)),
+            Ok(Some(Utxo::new(output(5), false, height)))
+        );
+
+        let genesis_utxo = Utxo::new(output(10), false, BlockHeight::new(0));
+        let undo = BlockUndo::new(None, vec![TxUndo::new(vec![genesis_utxo])], height);
+        assert_eq!(store.get_undo_data(block.get_id()), Ok(Some(undo)));
+        assert_eq!(store.get_undo_data(genesis.get_id()), Ok(None));
+
+        assert_eq!(store.get_best_block_for_utxos(), Ok(Some(block.get_id())));
+        assert_eq!(store.get_mainchain_tx_index(&genesis_tx.get_id()), Ok(None));
+        assert_eq!(store.get_is_mainchain_tx_index_enabled(), Ok(Some(false)));
+        assert_eq!(store.get_is_address_index_enabled(), Ok(Some(false)));
+    }
+
+    #[test]
+    fn block_children_indexed() {
+        use common::chain::block::{timestamp::BlockTimestamp, ConsensusData};
+        use common::Uint256;
+
+        let make_index = |prev_block_id, timestamp, height| {
+            let block = Block::new(
+                vec![],
+                prev_block_id,
+                BlockTimestamp::from_int_seconds(timestamp),
+                ConsensusData::None,
+            )
+            .unwrap();
+            let height = BlockHeight::new(height);
+            BlockIndex::new(
+                &block,
+                Uint256::from_u64(0),
+                None,
+                height,
+                block.timestamp(),
+            )
+        };
+        let genesis = make_index(None, 1, 0);
+        let child1 = make_index(Some(genesis.block_id().clone()), 2, 1);
+        let child2 = make_index(Some(genesis.block_id().clone()), 3, 1);
+        let grandchild = make_index(Some(child1.block_id().clone()), 4, 2);
+
+        let dir = tempfile::tempdir().unwrap();
+        {
+            let store = storage::Store::<super::super::Schema>::open(dir.path()).unwrap();
+            let mut tx = store.transaction_rw();
+            let mut col = tx.get_mut::<DBValue, _>();
+            col.put(well_known::StoreVersion::KEY.to_vec(), 3u32.encode()).unwrap();
+            let mut col = tx.get_mut::<DBBlockIndex, _>();
+            for block_index in [&genesis, &child1, &child2, &grandchild] {
+                col.put(block_index.block_id().encode(), block_index.encode()).unwrap();
+            }
+            tx.commit().unwrap();
+        }
+
+        let store = crate::Store::open(dir.path()).unwrap();
+        let mut children = vec![child1.block_id().clone(), child2.block_id().clone()];
+        children.sort();
+        assert_eq!(store.get_block_children(genesis.block_id()), Ok(children));
+        assert_eq!(
+            store.get_block_children(child1.block_id()),
+            Ok(vec![grandchild.block_id().clone()])
+        );
+        assert_eq!(store.get_block_children(grandchild.block_id()), Ok(vec![]));
+    }
+
+    #[test]
+    fn fresh_store_gets_current_version() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = crate::Store::open(dir.path()).unwrap();
+        assert_eq!(store.get_storage_version(), Ok(STORAGE_VERSION));
+    }
+}
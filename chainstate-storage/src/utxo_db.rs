@@ -1,23 +1,21 @@
-#![allow(dead_code)]
-
-use crate::{Error, Store, UndoRead, UndoWrite, UtxoRead, UtxoWrite};
+use crate::{Error, UndoWrite, UtxoWrite};
 use common::chain::block::Block;
 use common::chain::OutPoint;
 use common::primitives::Id;
 use utxo::{utxo_storage::UtxosPersistentStorage, BlockUndo, Utxo};
 
-#[derive(Clone)]
-pub struct UtxoDBImpl {
-    store: Store,
+/// Blockchain storage (or a storage transaction) used as persistent storage for the UTXO set
+pub struct UtxoDBImpl<'a, S> {
+    store: &'a mut S,
 }
 
-impl UtxoDBImpl {
-    pub fn new(store: Store) -> Self {
+impl<'a, S: UtxoWrite + UndoWrite> UtxoDBImpl<'a, S> {
+    pub fn new(store: &'a mut S) -> Self {
         Self { store }
     }
 }
 
-impl UtxosPersistentStorage for UtxoDBImpl {
+impl<'a, S: UtxoWrite + UndoWrite> UtxosPersistentStorage for UtxoDBImpl<'a, S> {
     fn set_utxo(&mut self, outpoint: &OutPoint, entry: Utxo) -> Result<(), utxo::Error> {
         self.store.add_utxo(outpoint, entry).map_err(|e| e.into())
     }
@@ -57,6 +55,7 @@ impl From<Error> for utxo::Error {
 mod test {
     use super::*;
     use crate::store::test::create_rand_block_undo;
+    use crate::Store;
     use common::chain::{Destination, OutPoint, OutPointSourceId, OutputPurpose, TxOutput};
     use common::primitives::{Amount, BlockHeight, H256};
     use crypto::key::{KeyKind, PrivateKey};
@@ -86,8 +85,8 @@ mod test {
     #[cfg(not(loom))]
     #[test]
     fn db_impl_test() {
-        let store = Store::new_empty().expect("should create a store");
-        let mut db_interface = UtxoDBImpl::new(store);
+        let mut store = Store::new_empty().expect("should create a store");
+        let mut db_interface = UtxoDBImpl::new(&mut store);
 
         // utxo checking
         let (utxo, outpoint) = create_utxo(1);
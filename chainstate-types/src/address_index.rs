@@ -0,0 +1,70 @@
+use common::address::pubkeyhash::PublicKeyHash;
+use common::chain::{Destination, OutPoint, OutPointSourceId, TxOutput};
+use common::primitives::{BlockHeight, Id};
+use script::Script;
+use serialization::{Decode, Encode};
+
+/// The key outputs are indexed by in the address index, derived from their destination
+///
+/// Outputs paid to a public key are indexed under the hash of the key, so they are found along
+/// with the outputs paid to the corresponding address.
+#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode)]
+pub enum AddressIndexKey {
+    PublicKeyHash(PublicKeyHash),
+    ScriptHash(Id<Script>),
+}
+
+impl AddressIndexKey {
+    /// Index key for outputs paid to given destination, if such outputs are indexed
+    pub fn from_destination(destination: &Destination) -> Option<Self> {
+        match destination {
+            Destination::Address(pkh) => Some(Self::PublicKeyHash(*pkh)),
+            Destination::PublicKey(pk) => Some(Self::PublicKeyHash(PublicKeyHash::from(pk))),
+            Destination::ScriptHash(id) => Some(Self::ScriptHash(id.clone())),
+            Destination::AnyoneCanSpend => None,
+        }
+    }
+}
+
+/// An output paid to an indexed address, along with the spending status
+#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode)]
+pub struct AddressHistoryEntry {
+    outpoint: OutPoint,
+    output: TxOutput,
+    block_height: BlockHeight,
+    spent_by: Option<OutPointSourceId>,
+}
+
+impl AddressHistoryEntry {
+    pub fn new(
+        outpoint: OutPoint,
+        output: TxOutput,
+        block_height: BlockHeight,
+        spent_by: Option<OutPointSourceId>,
+    ) -> Self {
+        Self {
+            outpoint,
+            output,
+            block_height,
+            spent_by,
+        }
+    }
+
+    pub fn outpoint(&self) -> &OutPoint {
+        &self.outpoint
+    }
+
+    pub fn output(&self) -> &TxOutput {
+        &self.output
+    }
+
+    /// Height of the mainchain block that created the output
+    pub fn block_height(&self) -> BlockHeight {
+        self.block_height
+    }
+
+    /// The transaction or block reward that spent the output in the mainchain, if any
+    pub fn spent_by(&self) -> Option<&OutPointSourceId> {
+        self.spent_by.as_ref()
+    }
+}
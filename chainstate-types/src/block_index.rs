@@ -7,6 +7,30 @@ use serialization::{Decode, Encode};
 
 use common::chain::block::timestamp::BlockTimestamp;
 
+/// Validation status of a block
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode)]
+pub enum BlockStatus {
+    /// The block passed the checks that do not depend on the chain state, it has not been
+    /// connected to the chain yet
+    HeaderValid,
+    /// The block has been connected to the chain, so it is valid in its entirety
+    FullyValid,
+    /// The block failed validation
+    Failed,
+    /// The block descends from a block that failed validation
+    FailedAncestor,
+}
+
+impl BlockStatus {
+    /// Whether the block or one of its ancestors failed validation
+    pub fn is_failed(&self) -> bool {
+        match self {
+            BlockStatus::HeaderValid | BlockStatus::FullyValid => false,
+            BlockStatus::Failed | BlockStatus::FailedAncestor => true,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Encode, Decode)]
 #[allow(dead_code, unused_variables)]
 pub struct BlockIndex {
@@ -16,6 +40,7 @@ pub struct BlockIndex {
     chain_trust: Uint256,
     height: BlockHeight,
     time_max: BlockTimestamp,
+    status: BlockStatus,
 }
 
 impl BlockIndex {
@@ -34,6 +59,7 @@ impl BlockIndex {
             chain_trust,
             height,
             time_max,
+            status: BlockStatus::HeaderValid,
         }
     }
 
@@ -73,6 +99,14 @@ impl BlockIndex {
         self.skip.as_ref()
     }
 
+    pub fn status(&self) -> BlockStatus {
+        self.status
+    }
+
+    pub fn set_status(&mut self, status: BlockStatus) {
+        self.status = status
+    }
+
     pub fn into_block_header(self) -> BlockHeader {
         self.block_header
     }
@@ -1,2 +1,3 @@
+pub mod address_index;
 pub mod block_index;
 pub mod height_skip;
@@ -0,0 +1,174 @@
+// Copyright (c) 2022 RBB S.r.l
+// opensource@mintlayer.org
+// SPDX-License-Identifier: MIT
+// Licensed under the MIT License;
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// 	http://spdx.org/licenses/MIT
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Connecting a block spending many signed inputs, with the signatures checked on the default
+//! worker pool versus a pool with a single thread, which checks them one after another. The
+//! inputs are spent either by one transaction or by as many single-input transactions.
+
+use std::sync::Arc;
+
+use chainstate::{BlockSource, Chainstate, ChainstateConfig};
+use chainstate_storage::Store;
+use common::chain::block::{timestamp::BlockTimestamp, Block, ConsensusData};
+use common::chain::config::create_unit_test_config;
+use common::chain::signature::inputsig::{InputWitness, StandardInputSignature};
+use common::chain::signature::sighashtype::SigHashType;
+use common::chain::{ChainConfig, Destination, OutputPurpose, Transaction, TxInput, TxOutput};
+use common::primitives::{time, Amount, Idable};
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use crypto::key::{KeyKind, PrivateKey};
+
+const INPUT_COUNT: usize = 500;
+
+fn next_block(prev_block: &Block, transactions: Vec<Transaction>) -> Block {
+    Block::new(
+        transactions,
+        Some(prev_block.get_id()),
+        BlockTimestamp::from_duration_since_epoch(time::get()).unwrap(),
+        ConsensusData::None,
+    )
+    .unwrap()
+}
+
+// Signs the inputs of given transaction, all of them spending outputs paid to given key
+fn sign_inputs(
+    unsigned_tx: &Transaction,
+    private_key: &PrivateKey,
+    destination: &Destination,
+) -> Transaction {
+    let inputs = unsigned_tx
+        .inputs()
+        .iter()
+        .enumerate()
+        .map(|(index, input)| {
+            let signature = StandardInputSignature::produce_signature_for_input(
+                private_key,
+                SigHashType::default(),
+                destination.clone(),
+                unsigned_tx,
+                index,
+            )
+            .unwrap();
+            TxInput::new(
+                input.outpoint().tx_id(),
+                input.outpoint().output_index(),
+                InputWitness::Standard(signature),
+            )
+        })
+        .collect();
+    Transaction::new(0, inputs, unsigned_tx.outputs().clone(), 0).unwrap()
+}
+
+// A block paying the genesis output to a key, split into many outputs, and two blocks spending
+// them, with one transaction and with one transaction per output
+fn make_blocks(chain_config: &ChainConfig) -> (Block, Block, Block) {
+    let (private_key, public_key) = PrivateKey::new(KeyKind::RistrettoSchnorr);
+    let destination = Destination::PublicKey(public_key);
+
+    let genesis = chain_config.genesis_block();
+    let genesis_tx = &genesis.transactions()[0];
+    let value = (genesis_tx.outputs()[0].value() / INPUT_COUNT as u128).unwrap();
+    let input = TxInput::new(
+        genesis_tx.get_id().into(),
+        0,
+        InputWitness::NoSignature(None),
+    );
+    let outputs =
+        vec![TxOutput::new(value, OutputPurpose::Transfer(destination.clone())); INPUT_COUNT];
+    let tx_1 = Transaction::new(0, vec![input], outputs, 0).unwrap();
+    let block_1 = next_block(genesis, vec![tx_1.clone()]);
+
+    let unsigned_inputs: Vec<_> = (0..INPUT_COUNT)
+        .map(|index| {
+            TxInput::new(
+                tx_1.get_id().into(),
+                index as u32,
+                InputWitness::NoSignature(None),
+            )
+        })
+        .collect();
+    let outputs = vec![TxOutput::new(
+        Amount::from_atoms(1),
+        OutputPurpose::Transfer(Destination::AnyoneCanSpend),
+    )];
+
+    let unsigned_tx = Transaction::new(0, unsigned_inputs.clone(), outputs.clone(), 0).unwrap();
+    let one_tx_block = next_block(
+        &block_1,
+        vec![sign_inputs(&unsigned_tx, &private_key, &destination)],
+    );
+
+    let transactions = unsigned_inputs
+        .into_iter()
+        .map(|input| {
+            let unsigned_tx = Transaction::new(0, vec![input], outputs.clone(), 0).unwrap();
+            sign_inputs(&unsigned_tx, &private_key, &destination)
+        })
+        .collect();
+    let many_tx_block = next_block(&block_1, transactions);
+
+    (block_1, one_tx_block, many_tx_block)
+}
+
+fn chainstate_with_block(chain_config: &Arc<ChainConfig>, block: &Block) -> Chainstate {
+    let mut chainstate = Chainstate::new(
+        Arc::clone(chain_config),
+        ChainstateConfig::default(),
+        Store::new_empty().unwrap(),
+        None,
+        Default::default(),
+    )
+    .unwrap();
+    chainstate.process_block(block.clone(), BlockSource::Local).unwrap();
+    chainstate
+}
+
+fn signature_verification(c: &mut Criterion) {
+    let chain_config = Arc::new(create_unit_test_config());
+    let (block_1, one_tx_block, many_tx_block) = make_blocks(&chain_config);
+    let serial_pool = rayon::ThreadPoolBuilder::new().num_threads(1).build().unwrap();
+
+    for (name, block_2) in [
+        ("connect block with one transaction", one_tx_block),
+        ("connect block with many transactions", many_tx_block),
+    ] {
+        let mut group = c.benchmark_group(name);
+        group.sample_size(20);
+        group.bench_function("parallel", |b| {
+            b.iter_batched(
+                || chainstate_with_block(&chain_config, &block_1),
+                |mut chainstate| {
+                    chainstate.process_block(block_2.clone(), BlockSource::Local).unwrap();
+                },
+                BatchSize::PerIteration,
+            )
+        });
+        group.bench_function("serial", |b| {
+            b.iter_batched(
+                || chainstate_with_block(&chain_config, &block_1),
+                |mut chainstate| {
+                    serial_pool.install(|| {
+                        chainstate.process_block(block_2.clone(), BlockSource::Local).unwrap();
+                    })
+                },
+                BatchSize::PerIteration,
+            )
+        });
+        group.finish();
+    }
+}
+
+criterion_group!(benches, signature_verification);
+criterion_main!(benches);
@@ -0,0 +1,25 @@
+//! What external miners need to build a block on the current tip
+
+use common::{
+    chain::{
+        block::{timestamp::BlockTimestamp, Block},
+        Transaction,
+    },
+    primitives::{Amount, BlockHeight, Compact, Id},
+};
+
+/// The parameters of a new block on top of the best block
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlockTemplate {
+    pub prev_block_id: Id<Block>,
+    pub height: BlockHeight,
+    /// The earliest timestamp the block may have, the median time past of the previous block
+    pub min_timestamp: BlockTimestamp,
+    /// The suggested timestamp of the block, the current time unless it is too early
+    pub timestamp: BlockTimestamp,
+    /// Proof of work target of a block with the suggested timestamp
+    pub bits: Compact,
+    /// The block subsidy along with the fees of the transactions
+    pub reward: Amount,
+    pub transactions: Vec<Transaction>,
+}
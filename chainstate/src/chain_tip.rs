@@ -0,0 +1,32 @@
+//! Leaves of the block tree
+
+use common::{
+    chain::block::Block,
+    primitives::{BlockDistance, BlockHeight, Id},
+    Uint256,
+};
+
+/// How far a chain tip got in validation
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ChainTipStatus {
+    /// The tip of the main chain
+    Active,
+    /// The tip of a fork that has been connected to the chain at some point, so it is valid
+    ValidFork,
+    /// The tip of a fork that has never been connected, only its headers have been validated
+    HeadersOnly,
+    /// The tip or one of its ancestors failed validation
+    Invalid,
+}
+
+/// A block in the block index that no other block builds on
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChainTip {
+    pub block_id: Id<Block>,
+    pub height: BlockHeight,
+    pub chain_trust: Uint256,
+    /// Number of blocks between the tip and the point it forks off the main chain
+    pub branch_length: BlockDistance,
+    pub status: ChainTipStatus,
+}
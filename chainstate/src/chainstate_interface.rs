@@ -3,15 +3,23 @@ pub mod mock;
 
 use std::sync::Arc;
 
+use chainstate_types::address_index::AddressHistoryEntry;
 use common::{
-    chain::block::{Block, BlockHeader},
+    address::Address,
+    chain::{
+        block::{Block, BlockHeader},
+        Transaction,
+    },
     primitives::{BlockHeight, Id},
 };
 
-use crate::{detail::BlockSource, ChainstateError, ChainstateEvent};
+use crate::{
+    detail::BlockSource, BlockTemplate, ChainTip, ChainstateError, ChainstateEvent, EventReceiver,
+};
 
 pub trait ChainstateInterface: Send {
     fn subscribe_to_events(&mut self, handler: Arc<dyn Fn(ChainstateEvent) + Send + Sync>);
+    fn subscribe_to_event_stream(&mut self, buffer_size: usize) -> EventReceiver;
     fn process_block(&mut self, block: Block, source: BlockSource) -> Result<(), ChainstateError>;
     fn preliminary_block_check(&self, block: Block) -> Result<Block, ChainstateError>;
     fn get_best_block_id(&self) -> Result<Id<Block>, ChainstateError>;
@@ -32,4 +40,20 @@ pub trait ChainstateInterface: Send {
         &self,
         headers: Vec<BlockHeader>,
     ) -> Result<Vec<BlockHeader>, ChainstateError>;
+    fn get_transaction(
+        &self,
+        tx_id: &Id<Transaction>,
+    ) -> Result<Option<(Transaction, Id<Block>, BlockHeight)>, ChainstateError>;
+    fn get_address_history(
+        &self,
+        address: &Address,
+        skip: usize,
+        limit: usize,
+    ) -> Result<Vec<AddressHistoryEntry>, ChainstateError>;
+    fn get_chain_tips(&self) -> Result<Vec<ChainTip>, ChainstateError>;
+    fn get_storage_stats(&self) -> Result<chainstate_storage::Stats, ChainstateError>;
+    fn invalidate_block(&mut self, block_id: &Id<Block>) -> Result<(), ChainstateError>;
+    fn reconsider_block(&mut self, block_id: &Id<Block>) -> Result<(), ChainstateError>;
+    fn get_block_template(&self) -> Result<BlockTemplate, ChainstateError>;
+    fn generate_block(&mut self, address: &Address) -> Result<Block, ChainstateError>;
 }
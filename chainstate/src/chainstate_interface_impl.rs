@@ -1,12 +1,17 @@
+use chainstate_types::address_index::AddressHistoryEntry;
 use common::{
-    chain::block::{Block, BlockHeader},
+    address::Address,
+    chain::{
+        block::{Block, BlockHeader},
+        Transaction,
+    },
     primitives::{BlockHeight, Id},
 };
 use utils::eventhandler::EventHandler;
 
 use crate::{
     detail::{self, BlockSource},
-    ChainstateError, ChainstateEvent, ChainstateInterface,
+    BlockTemplate, ChainTip, ChainstateError, ChainstateEvent, ChainstateInterface, EventReceiver,
 };
 
 pub struct ChainstateInterfaceImpl {
@@ -24,6 +29,10 @@ impl ChainstateInterface for ChainstateInterfaceImpl {
         self.chainstate.subscribe_to_events(handler)
     }
 
+    fn subscribe_to_event_stream(&mut self, buffer_size: usize) -> EventReceiver {
+        self.chainstate.subscribe_to_event_stream(buffer_size)
+    }
+
     fn process_block(&mut self, block: Block, source: BlockSource) -> Result<(), ChainstateError> {
         self.chainstate
             .process_block(block, source)
@@ -104,4 +113,58 @@ impl ChainstateInterface for ChainstateInterfaceImpl {
             .expect("Best block index could not be found");
         Ok(best_block_index.block_height())
     }
+
+    fn get_transaction(
+        &self,
+        tx_id: &Id<Transaction>,
+    ) -> Result<Option<(Transaction, Id<Block>, BlockHeight)>, ChainstateError> {
+        self.chainstate
+            .get_transaction(tx_id)
+            .map_err(ChainstateError::FailedToReadProperty)
+    }
+
+    fn get_address_history(
+        &self,
+        address: &Address,
+        skip: usize,
+        limit: usize,
+    ) -> Result<Vec<AddressHistoryEntry>, ChainstateError> {
+        self.chainstate
+            .get_address_history(address, skip, limit)
+            .map_err(ChainstateError::FailedToReadProperty)
+    }
+
+    fn get_chain_tips(&self) -> Result<Vec<ChainTip>, ChainstateError> {
+        self.chainstate.get_chain_tips().map_err(ChainstateError::FailedToReadProperty)
+    }
+
+    fn get_storage_stats(&self) -> Result<chainstate_storage::Stats, ChainstateError> {
+        self.chainstate
+            .get_storage_stats()
+            .map_err(ChainstateError::FailedToReadProperty)
+    }
+
+    fn invalidate_block(&mut self, block_id: &Id<Block>) -> Result<(), ChainstateError> {
+        self.chainstate
+            .invalidate_block(block_id)
+            .map_err(ChainstateError::ProcessBlockError)
+    }
+
+    fn reconsider_block(&mut self, block_id: &Id<Block>) -> Result<(), ChainstateError> {
+        self.chainstate
+            .reconsider_block(block_id)
+            .map_err(ChainstateError::ProcessBlockError)
+    }
+
+    fn get_block_template(&self) -> Result<BlockTemplate, ChainstateError> {
+        self.chainstate
+            .get_block_template()
+            .map_err(ChainstateError::BlockGenerationFailed)
+    }
+
+    fn generate_block(&mut self, address: &Address) -> Result<Block, ChainstateError> {
+        self.chainstate
+            .generate_block(address)
+            .map_err(ChainstateError::BlockGenerationFailed)
+    }
 }
@@ -0,0 +1,12 @@
+//! Chainstate subsystem configuration
+
+/// Settings of the chainstate, as opposed to the consensus rules given by the chain config
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ChainstateConfig {
+    /// Maintain an index of the mainchain transactions, so they can be looked up by ID. The
+    /// setting is recorded in the storage when it is initialized and cannot be changed later.
+    pub tx_index_enabled: bool,
+    /// Maintain an index of the outputs paid to each address, spent or not. Like the transaction
+    /// index, the setting cannot be changed once the storage has been initialized.
+    pub address_index_enabled: bool,
+}
@@ -29,6 +29,15 @@ impl BanScore for BlockError {
             BlockError::BlockAlreadyExists(_) => 0,
             BlockError::DatabaseCommitError(_, _, _) => 0,
             BlockError::BlockProofCalculationError(_) => 100,
+            BlockError::BlockMarkedInvalid(_) => 100,
+            BlockError::InvalidAncestor(_, _) => 100,
+            BlockError::BlockNotFound(_) => 0,
+            BlockError::InvalidateGenesis => 0,
+            BlockError::BlockQueryError(_) => 0,
+            BlockError::CheckpointMismatch(_, _, _) => 100,
+            BlockError::ForkBelowCheckpoint(_, _) => 100,
+            // The peer may just follow another chain, but it sends blocks we will never accept
+            BlockError::ReorgTooDeep(_, _, _) => 20,
         }
     }
 }
@@ -54,34 +63,24 @@ impl BanScore for StateUpdateError {
             StateUpdateError::InvariantErrorTxNumWrongInBlock(_, _) => 0,
             StateUpdateError::OutputAlreadyPresentInInputsCache => 100,
             StateUpdateError::ImmatureBlockRewardSpend => 100,
-            StateUpdateError::PreviouslyCachedInputNotFound => 0,
-            StateUpdateError::PreviouslyCachedInputWasErased => 100,
             StateUpdateError::InvariantBrokenAlreadyUnspent => 0,
-            // Even though this is an invariant error, it stems from referencing a block for reward that doesn't exist
-            StateUpdateError::InvariantBrokenSourceBlockIndexNotFound => 100,
             StateUpdateError::MissingOutputOrSpent => 100,
-            StateUpdateError::MissingOutputOrSpentOutputErasedOnConnect => 100,
-            StateUpdateError::MissingOutputOrSpentOutputErasedOnDisconnect => 0,
+            StateUpdateError::MissingOutputOrSpentOnDisconnect => 0,
             StateUpdateError::AttemptToPrintMoney(_, _) => 100,
             StateUpdateError::TxFeeTotalCalcFailed(_, _) => 100,
             StateUpdateError::OutputAdditionError => 100,
-            StateUpdateError::SignatureVerificationFailed => 100,
-            StateUpdateError::InvalidOutputCount => 100,
+            StateUpdateError::SignatureVerificationFailed(_, _) => 100,
             StateUpdateError::BlockHeightArithmeticError => 100,
             StateUpdateError::InputAdditionError => 100,
-            StateUpdateError::DoubleSpendAttempt(_) => 100,
-            StateUpdateError::OutputIndexOutOfRange {
-                tx_id: _,
-                source_output_index: _,
-            } => 100,
-            // Even though this is an invariant error, it stems from a transaction that doesn't exist
-            StateUpdateError::InvariantErrorTransactionCouldNotBeLoaded(_) => 100,
-            // Even though this is an invariant error, it stems from a block reward that doesn't exist
-            StateUpdateError::InvariantErrorHeaderCouldNotBeLoaded(_) => 100,
             StateUpdateError::FailedToAddAllFeesOfBlock(_) => 100,
             StateUpdateError::RewardAdditionError(_) => 100,
+            StateUpdateError::InvalidOutputCount => 100,
             // Even though this is an invariant, we consider it a violation to be overly cautious
             StateUpdateError::SerializationInvariantError(_) => 100,
+            StateUpdateError::LockTimeNotReached(_, _) => 100,
+            StateUpdateError::MissingBlockUndo(_) => 0,
+            StateUpdateError::BlockUndoMismatch(_) => 0,
+            StateUpdateError::UtxoError(_) => 0,
         }
     }
 }
@@ -69,8 +69,14 @@ mod tests {
         common::concurrency::model(|| {
             let chain_config = Arc::new(create_unit_test_config());
             let storage = Store::new_empty().unwrap();
-            let mut chainstate =
-                Chainstate::new(chain_config.clone(), storage, None, Default::default()).unwrap();
+            let mut chainstate = Chainstate::new(
+                chain_config.clone(),
+                Default::default(),
+                storage,
+                None,
+                Default::default(),
+            )
+            .unwrap();
 
             // put three blocks in a chain after genesis
             let block1 = Block::new(
@@ -1,37 +1,60 @@
 use std::collections::BTreeSet;
 
-use super::{
-    consensus_validator::TransactionIndexHandle, median_time::calculate_median_time_past,
-    time_getter::TimeGetterFn,
+use super::{median_time::calculate_median_time_past, time_getter::TimeGetterFn};
+use chainstate_storage::{
+    BlockchainStorageRead, BlockchainStorageWrite, TransactionRw, UtxoDBImpl,
+};
+use chainstate_types::{
+    address_index::{AddressHistoryEntry, AddressIndexKey},
+    block_index::{BlockIndex, BlockStatus},
+    height_skip::get_skip_height,
 };
-use chainstate_storage::{BlockchainStorageRead, BlockchainStorageWrite, TransactionRw};
-use chainstate_types::{block_index::BlockIndex, height_skip::get_skip_height};
 use common::{
     chain::{
-        block::{calculate_tx_merkle_root, calculate_witness_merkle_root, Block, BlockHeader},
-        calculate_tx_index_from_block, ChainConfig, OutPointSourceId,
+        block::{
+            calculate_tx_merkle_root, calculate_witness_merkle_root, timestamp::BlockTimestamp,
+            Block, BlockHeader, ConsensusData,
+        },
+        calculate_tx_index_from_block,
+        config::ChainType,
+        signature::Transactable,
+        ChainConfig, Destination, OutPoint, OutPointSourceId, OutputPurpose, RequiredConsensus,
+        SpendablePosition, Transaction, TxInput, TxMainChainPosition, TxOutput,
     },
     primitives::{BlockDistance, BlockHeight, Id, Idable},
     Uint256,
 };
 use logging::log;
 use utils::ensure;
+use utxo::{utxo_storage::UtxoDB, BlockUndo, TxUndo, Utxo};
 
-use crate::{BlockError, BlockSource};
+use crate::{
+    BlockError, BlockSource, BlockTemplate, ChainTip, ChainTipStatus, ChainstateConfig,
+    ChainstateEvent,
+};
 
 use super::{
     consensus_validator::{self, BlockIndexHandle},
     orphan_blocks::{OrphanBlocks, OrphanBlocksMut},
-    spend_cache::{BlockTransactableRef, CachedInputs},
-    BlockSizeError, CheckBlockError, CheckBlockTransactionsError, OrphanCheckError,
-    PropertyQueryError,
+    pow::{
+        error::ConsensusPoWError,
+        work::{calculate_work_required, mine},
+    },
+    spend_cache::{error::StateUpdateError, BlockTransactableRef, CachedInputs},
+    BlockGenerationError, BlockSizeError, CheckBlockError, CheckBlockTransactionsError,
+    OrphanCheckError, PropertyQueryError,
 };
 
 pub(crate) struct ChainstateRef<'a, S, O> {
     chain_config: &'a ChainConfig,
+    chainstate_config: &'a ChainstateConfig,
     db_tx: S,
     orphan_blocks: O,
     time_getter: &'a TimeGetterFn,
+    // Block that failed to connect while activating the best chain, if any
+    failed_block_id: Option<Id<Block>>,
+    // Events to be emitted once the transaction is committed, in order
+    events: Vec<ChainstateEvent>,
 }
 
 impl<'a, S: BlockchainStorageRead, O: OrphanBlocks> BlockIndexHandle for ChainstateRef<'a, S, O> {
@@ -50,24 +73,6 @@ impl<'a, S: BlockchainStorageRead, O: OrphanBlocks> BlockIndexHandle for Chainst
     }
 }
 
-impl<'a, S: BlockchainStorageRead, O: OrphanBlocks> TransactionIndexHandle
-    for ChainstateRef<'a, S, O>
-{
-    fn get_mainchain_tx_index(
-        &self,
-        tx_id: &OutPointSourceId,
-    ) -> Result<Option<common::chain::TxMainChainIndex>, PropertyQueryError> {
-        self.get_mainchain_tx_index(tx_id)
-    }
-
-    fn get_mainchain_tx_by_position(
-        &self,
-        tx_index: &common::chain::TxMainChainPosition,
-    ) -> Result<Option<common::chain::Transaction>, PropertyQueryError> {
-        self.get_mainchain_tx_by_position(tx_index)
-    }
-}
-
 impl<'a, S: TransactionRw<Error = chainstate_storage::Error>, O> ChainstateRef<'a, S, O> {
     pub fn commit_db_tx(self) -> chainstate_storage::Result<()> {
         self.db_tx.commit()
@@ -77,32 +82,50 @@ impl<'a, S: TransactionRw<Error = chainstate_storage::Error>, O> ChainstateRef<'
 impl<'a, S: BlockchainStorageRead, O: OrphanBlocks> ChainstateRef<'a, S, O> {
     pub fn new_rw(
         chain_config: &'a ChainConfig,
+        chainstate_config: &'a ChainstateConfig,
         db_tx: S,
         orphan_blocks: O,
         time_getter: &'a TimeGetterFn,
     ) -> ChainstateRef<'a, S, O> {
         ChainstateRef {
             chain_config,
+            chainstate_config,
             db_tx,
             orphan_blocks,
             time_getter,
+            failed_block_id: None,
+            events: Vec::new(),
         }
     }
 
     pub fn new_ro(
         chain_config: &'a ChainConfig,
+        chainstate_config: &'a ChainstateConfig,
         db_tx: S,
         orphan_blocks: O,
         time_getter: &'a TimeGetterFn,
     ) -> ChainstateRef<'a, S, O> {
         ChainstateRef {
             chain_config,
+            chainstate_config,
             db_tx,
             orphan_blocks,
             time_getter,
+            failed_block_id: None,
+            events: Vec::new(),
         }
     }
 
+    /// The block that failed to connect, causing the last chain activation to fail
+    pub fn failed_block_id(&self) -> Option<&Id<Block>> {
+        self.failed_block_id.as_ref()
+    }
+
+    /// Take the events generated by the changes made so far
+    pub fn take_events(&mut self) -> Vec<ChainstateEvent> {
+        std::mem::take(&mut self.events)
+    }
+
     pub fn current_time(&self) -> std::time::Duration {
         (self.time_getter)()
     }
@@ -119,24 +142,6 @@ impl<'a, S: BlockchainStorageRead, O: OrphanBlocks> ChainstateRef<'a, S, O> {
         self.db_tx.get_block_index(block_id).map_err(PropertyQueryError::from)
     }
 
-    pub fn get_mainchain_tx_index(
-        &self,
-        tx_id: &OutPointSourceId,
-    ) -> Result<Option<common::chain::TxMainChainIndex>, PropertyQueryError> {
-        log::trace!("Loading transaction index of id: {:?}", tx_id);
-        self.db_tx.get_mainchain_tx_index(tx_id).map_err(PropertyQueryError::from)
-    }
-
-    fn get_mainchain_tx_by_position(
-        &self,
-        tx_index: &common::chain::TxMainChainPosition,
-    ) -> Result<Option<common::chain::Transaction>, PropertyQueryError> {
-        log::trace!("Loading transaction by pos: {:?}", tx_index);
-        self.db_tx
-            .get_mainchain_tx_by_position(tx_index)
-            .map_err(PropertyQueryError::from)
-    }
-
     pub fn get_block_id_by_height(
         &self,
         height: &BlockHeight,
@@ -226,7 +231,6 @@ impl<'a, S: BlockchainStorageRead, O: OrphanBlocks> ChainstateRef<'a, S, O> {
         Ok(block_index_walk)
     }
 
-    #[allow(unused)]
     pub fn last_common_ancestor(
         &self,
         first_block_index: &BlockIndex,
@@ -289,6 +293,34 @@ impl<'a, S: BlockchainStorageRead, O: OrphanBlocks> ChainstateRef<'a, S, O> {
         }
     }
 
+    pub fn get_transaction(
+        &self,
+        tx_id: &Id<Transaction>,
+    ) -> Result<Option<(Transaction, Id<Block>, BlockHeight)>, PropertyQueryError> {
+        let tx_pos = match self.db_tx.get_mainchain_tx_index(tx_id)? {
+            Some(tx_pos) => tx_pos,
+            None => return Ok(None),
+        };
+        let tx = self
+            .db_tx
+            .get_mainchain_tx_by_position(&tx_pos)?
+            .ok_or_else(|| PropertyQueryError::IndexedTxNotFound(tx_id.clone()))?;
+        let block_id = tx_pos.block_id().clone();
+        let block_index = self
+            .get_block_index(&block_id)?
+            .ok_or_else(|| PropertyQueryError::BlockNotFound(block_id.clone()))?;
+        Ok(Some((tx, block_id, block_index.block_height())))
+    }
+
+    pub fn get_address_history(
+        &self,
+        key: &AddressIndexKey,
+        skip: usize,
+        limit: usize,
+    ) -> Result<Vec<AddressHistoryEntry>, PropertyQueryError> {
+        Ok(self.db_tx.get_address_history(key, skip, limit)?)
+    }
+
     // Get indexes for a new longest chain
     fn get_new_chain(
         &self,
@@ -305,6 +337,175 @@ impl<'a, S: BlockchainStorageRead, O: OrphanBlocks> ChainstateRef<'a, S, O> {
         Ok(result)
     }
 
+    // Get the indices of all the known descendants of given block, not including the block itself
+    fn get_descendants(&self, block_id: &Id<Block>) -> Result<Vec<BlockIndex>, PropertyQueryError> {
+        let mut descendants = Vec::new();
+        let mut to_visit = self.db_tx.get_block_children(block_id)?;
+        while let Some(block_id) = to_visit.pop() {
+            to_visit.extend(self.db_tx.get_block_children(&block_id)?);
+            let block_index = self
+                .db_tx
+                .get_block_index(&block_id)?
+                .ok_or(PropertyQueryError::BlockNotFound(block_id))?;
+            descendants.push(block_index);
+        }
+        Ok(descendants)
+    }
+
+    /// List the blocks in the block index that no other block builds on, plus the best block,
+    /// which stays the active tip even when only invalid blocks build on it
+    pub fn get_chain_tips(&self) -> Result<Vec<ChainTip>, PropertyQueryError> {
+        let best_block_index =
+            self.get_best_block_index()?.ok_or(PropertyQueryError::BestBlockIndexNotFound)?;
+        let block_indices = self.db_tx.get_block_indices()?;
+        let parents: BTreeSet<_> =
+            block_indices.iter().filter_map(|bi| bi.prev_block_id().clone()).collect();
+
+        block_indices
+            .into_iter()
+            .filter(|bi| {
+                !parents.contains(bi.block_id()) || bi.block_id() == best_block_index.block_id()
+            })
+            .map(|tip| {
+                let fork_point = self.last_common_ancestor(&tip, &best_block_index)?;
+                let branch_length = (tip.block_height() - fork_point.block_height())
+                    .expect("the fork point to be an ancestor of the tip");
+                let status = if tip.block_id() == best_block_index.block_id() {
+                    ChainTipStatus::Active
+                } else {
+                    match tip.status() {
+                        BlockStatus::FullyValid => ChainTipStatus::ValidFork,
+                        BlockStatus::HeaderValid => ChainTipStatus::HeadersOnly,
+                        BlockStatus::Failed | BlockStatus::FailedAncestor => {
+                            ChainTipStatus::Invalid
+                        }
+                    }
+                };
+                Ok(ChainTip {
+                    block_id: tip.block_id().clone(),
+                    height: tip.block_height(),
+                    chain_trust: *tip.chain_trust(),
+                    branch_length,
+                    status,
+                })
+            })
+            .collect()
+    }
+
+    /// Parameters of a new block on top of the best block, which has to be mined with PoW
+    pub fn get_block_template(&self) -> Result<BlockTemplate, BlockGenerationError> {
+        let best_block_index =
+            self.get_best_block_index()?.ok_or(PropertyQueryError::BestBlockIndexNotFound)?;
+        let height = best_block_index.block_height().next_height();
+        let pow_status = match self.chain_config.net_upgrade().consensus_status(height) {
+            RequiredConsensus::PoW(pow_status) => pow_status,
+            RequiredConsensus::PoS
+            | RequiredConsensus::DSA
+            | RequiredConsensus::IgnoreConsensus => {
+                return Err(BlockGenerationError::NeedsPoW(height))
+            }
+        };
+
+        let min_timestamp = calculate_median_time_past(self, best_block_index.block_id());
+        let current_time = BlockTimestamp::from_duration_since_epoch(self.current_time())
+            .expect("the current time to fit a block timestamp");
+        let timestamp = std::cmp::max(current_time, min_timestamp);
+
+        // The work required only depends on the parent and the timestamp of the block
+        let transactions = vec![];
+        let header_block = Block::new(
+            transactions.clone(),
+            Some(best_block_index.block_id().clone()),
+            timestamp,
+            ConsensusData::None,
+        )
+        .expect("a block without transactions to have no merkle tree");
+        let bits =
+            calculate_work_required(self.chain_config, header_block.header(), &pow_status, self)
+                .map_err(BlockGenerationError::PoWError)?;
+
+        Ok(BlockTemplate {
+            prev_block_id: best_block_index.block_id().clone(),
+            height,
+            min_timestamp,
+            timestamp,
+            bits,
+            reward: self.chain_config.block_subsidy_at_height(&height),
+            transactions,
+        })
+    }
+
+    /// Build a block on top of the best block, paying the block subsidy to given destination, and
+    /// solve its proof of work. Only allowed on chains meant for testing.
+    pub fn generate_block(&self, destination: Destination) -> Result<Block, BlockGenerationError> {
+        let chain_type = *self.chain_config.chain_type();
+        ensure!(
+            matches!(chain_type, ChainType::Regtest | ChainType::Signet),
+            BlockGenerationError::NotAllowed(chain_type),
+        );
+
+        let template = self.get_block_template()?;
+        let mut block = Block::new(
+            template.transactions,
+            Some(template.prev_block_id),
+            template.timestamp,
+            ConsensusData::None,
+        )
+        .expect("the template transactions to form a merkle tree");
+        let reward = TxOutput::new(template.reward, OutputPurpose::Transfer(destination));
+        let solved = mine(&mut block, u128::MAX, template.bits, vec![reward])
+            .map_err(BlockGenerationError::PoWError)?;
+        ensure!(
+            solved,
+            BlockGenerationError::PoWError(ConsensusPoWError::InvalidPoW(block.get_id())),
+        );
+        Ok(block)
+    }
+
+    /// Find the tip of the chain with the most trust among the blocks not known to be invalid,
+    /// if it has more trust than the current best block.
+    pub fn find_best_chain_candidate(&self) -> Result<Option<BlockIndex>, PropertyQueryError> {
+        let best_block_index =
+            self.get_best_block_index()?.ok_or(PropertyQueryError::BestBlockIndexNotFound)?;
+        let mut candidates = self.db_tx.get_block_indices()?;
+        candidates.retain(|bi| {
+            !bi.status().is_failed() && bi.chain_trust() > best_block_index.chain_trust()
+        });
+        candidates.sort_by(|a, b| b.chain_trust().cmp(a.chain_trust()));
+
+        'candidates: for candidate in candidates {
+            let mut block_index = candidate.clone();
+            while !self.is_block_in_main_chain(&block_index)? {
+                if block_index.status().is_failed() {
+                    continue 'candidates;
+                }
+                block_index = self.get_previous_block_index(&block_index)?;
+            }
+            return Ok(Some(candidate));
+        }
+        Ok(None)
+    }
+
+    /// Check neither the block nor its parent has been found invalid before
+    pub fn check_block_status(&self, block: &Block) -> Result<(), BlockError> {
+        let block_id = block.get_id();
+        if let Some(block_index) = self.db_tx.get_block_index(&block_id)? {
+            ensure!(
+                !block_index.status().is_failed(),
+                BlockError::BlockMarkedInvalid(block_id.clone()),
+            );
+        }
+        if let Some(prev_block_id) = block.prev_block_id() {
+            if let Some(prev_block_index) = self.db_tx.get_block_index(&prev_block_id)? {
+                ensure!(
+                    !prev_block_index.status().is_failed(),
+                    BlockError::InvalidAncestor(block_id, prev_block_id),
+                );
+            }
+        }
+        Ok(())
+    }
+
     fn check_block_index(&self, block_index: &BlockIndex) -> Result<(), BlockError> {
         // BlockIndex is already known or block exists
         if self.db_tx.get_block_index(block_index.block_id())?.is_some() {
@@ -312,10 +513,69 @@ impl<'a, S: BlockchainStorageRead, O: OrphanBlocks> ChainstateRef<'a, S, O> {
                 block_index.block_id().clone(),
             ));
         }
+        self.check_block_checkpoints(block_index)?;
+        self.check_reorg_depth(block_index)?;
         // TODO: Will be expanded
         Ok(())
     }
 
+    // Check the block agrees with the checkpoints in the chain config
+    fn check_block_checkpoints(&self, block_index: &BlockIndex) -> Result<(), BlockError> {
+        let height = block_index.block_height();
+        let checkpoints = self.chain_config.height_checkpoints();
+
+        if let Some(expected_id) = checkpoints.get(&height) {
+            ensure!(
+                expected_id == block_index.block_id(),
+                BlockError::CheckpointMismatch(
+                    height,
+                    expected_id.clone(),
+                    block_index.block_id().clone()
+                ),
+            );
+        }
+
+        // Once the main chain has reached a checkpoint, there can be no new blocks below it
+        for (checkpoint_height, checkpoint_id) in checkpoints.range(height..).rev() {
+            if self.db_tx.get_block_id_by_height(checkpoint_height)?.as_ref() == Some(checkpoint_id)
+            {
+                return Err(BlockError::ForkBelowCheckpoint(height, *checkpoint_height));
+            }
+        }
+
+        Ok(())
+    }
+
+    // Check switching to a chain with the block would not disconnect too many main chain blocks
+    fn check_reorg_depth(&self, block_index: &BlockIndex) -> Result<(), BlockError> {
+        let max_reorg_depth = match self.chain_config.max_reorg_depth() {
+            Some(max_reorg_depth) => max_reorg_depth,
+            None => return Ok(()),
+        };
+        let (prev_block_id, best_block_index) =
+            match (block_index.prev_block_id(), self.get_best_block_index()?) {
+                (Some(prev_block_id), Some(best_block_index)) => (prev_block_id, best_block_index),
+                _ => return Ok(()),
+            };
+        // The limit is local policy, it only applies if the main chain would actually switch
+        if block_index.chain_trust() <= best_block_index.chain_trust() {
+            return Ok(());
+        }
+        let prev_block_index = self
+            .db_tx
+            .get_block_index(prev_block_id)?
+            .ok_or(BlockError::PrevBlockNotFound)?;
+
+        let fork_point = self.last_common_ancestor(&prev_block_index, &best_block_index)?;
+        let reorg_depth = (best_block_index.block_height() - fork_point.block_height())
+            .expect("the fork point to be an ancestor of the best block");
+        ensure!(
+            reorg_depth <= max_reorg_depth,
+            BlockError::ReorgTooDeep(block_index.block_id().clone(), reorg_depth, max_reorg_depth),
+        );
+        Ok(())
+    }
+
     fn check_block_detail(&self, block: &Block) -> Result<(), CheckBlockError> {
         // MerkleTree root
         let merkle_tree_root = block.merkle_root();
@@ -414,17 +674,20 @@ impl<'a, S: BlockchainStorageRead, O: OrphanBlocks> ChainstateRef<'a, S, O> {
             for tx in block.transactions() {
                 let mut tx_inputs = BTreeSet::new();
                 for input in tx.inputs() {
-                    if !block_inputs.insert(input.outpoint()) {
-                        return Err(CheckBlockTransactionsError::DuplicateInputInBlock(
-                            block.get_id(),
-                        ));
-                    }
+                    // Checked first as the transaction being invalid on its own is a more
+                    // specific error, and unlike a conflict between transactions it cannot be
+                    // caused by tampering with a valid block
                     if !tx_inputs.insert(input.outpoint()) {
                         return Err(CheckBlockTransactionsError::DuplicateInputInTransaction(
                             tx.get_id(),
                             block.get_id(),
                         ));
                     }
+                    if !block_inputs.insert(input.outpoint()) {
+                        return Err(CheckBlockTransactionsError::DuplicateInputInBlock(
+                            block.get_id(),
+                        ));
+                    }
                 }
             }
         }
@@ -468,46 +731,6 @@ impl<'a, S: BlockchainStorageRead, O: OrphanBlocks> ChainstateRef<'a, S, O> {
             .get_block_proof()
             .ok_or_else(|| BlockError::BlockProofCalculationError(block.get_id()))
     }
-
-    fn make_cache_with_connected_transactions(
-        &self,
-        block: &Block,
-        spend_height: &BlockHeight,
-        blockreward_maturity: &BlockDistance,
-    ) -> Result<CachedInputs<S>, BlockError> {
-        let mut cached_inputs = CachedInputs::new(&self.db_tx);
-
-        cached_inputs.spend(
-            BlockTransactableRef::BlockReward(block),
-            spend_height,
-            blockreward_maturity,
-        )?;
-
-        for (tx_num, _tx) in block.transactions().iter().enumerate() {
-            cached_inputs.spend(
-                BlockTransactableRef::Transaction(block, tx_num),
-                spend_height,
-                blockreward_maturity,
-            )?;
-        }
-
-        let block_subsidy = self.chain_config.block_subsidy_at_height(spend_height);
-        cached_inputs.check_block_reward(block, block_subsidy)?;
-
-        Ok(cached_inputs)
-    }
-
-    fn make_cache_with_disconnected_transactions(
-        &self,
-        block: &Block,
-    ) -> Result<CachedInputs<S>, BlockError> {
-        let mut cached_inputs = CachedInputs::new(&self.db_tx);
-        block.transactions().iter().enumerate().try_for_each(|(tx_num, _tx)| {
-            cached_inputs.unspend(BlockTransactableRef::Transaction(block, tx_num))
-        })?;
-        cached_inputs.unspend(BlockTransactableRef::BlockReward(block))?;
-        Ok(cached_inputs)
-    }
 }
 
 impl<'a, S: BlockchainStorageWrite, O: OrphanBlocksMut> ChainstateRef<'a, S, O> {
@@ -546,12 +769,14 @@ impl<'a, S: BlockchainStorageWrite, O: OrphanBlocksMut> ChainstateRef<'a, S, O>
         &mut self,
         to_disconnect: &BlockIndex,
         last_to_remain_connected: &Id<Block>,
-    ) -> Result<(), BlockError> {
+    ) -> Result<Vec<Id<Block>>, BlockError> {
+        let mut disconnected = Vec::new();
         let mut to_disconnect_next = to_disconnect.clone();
         while to_disconnect_next.block_id() != last_to_remain_connected {
+            disconnected.push(to_disconnect_next.block_id().clone());
             to_disconnect_next = self.disconnect_tip(Some(to_disconnect_next.block_id()))?;
         }
-        Ok(())
+        Ok(disconnected)
     }
 
     fn reorganize(
@@ -567,6 +792,14 @@ impl<'a, S: BlockchainStorageWrite, O: OrphanBlocksMut> ChainstateRef<'a, S, O>
             )
         })?;
 
+        // Never switch to a chain containing a block already found invalid
+        if let Some(failed) = new_chain.iter().find(|bi| bi.status().is_failed()) {
+            return Err(BlockError::InvalidAncestor(
+                new_block_index.block_id().clone(),
+                failed.block_id().clone(),
+            ));
+        }
+
         let common_ancestor_id = {
             let err = "This vector cannot be empty since there is at least one block to connect";
             let first_block = &new_chain.first().expect(err);
@@ -574,21 +807,31 @@ impl<'a, S: BlockchainStorageWrite, O: OrphanBlocksMut> ChainstateRef<'a, S, O>
         };
 
         // Disconnect the current chain if it is not a genesis
-        {
+        let disconnected = {
             let mainchain_tip = self
                 .db_tx
                 .get_block_index(best_block_id)?
                 .expect("Can't get block index. Inconsistent DB");
 
             // Disconnect blocks
-            self.disconnect_until(&mainchain_tip, common_ancestor_id)?;
-        }
+            self.disconnect_until(&mainchain_tip, common_ancestor_id)?
+        };
 
         // Connect the new chain
+        let connected = new_chain.iter().map(|bi| bi.block_id().clone()).collect();
         for block_index in new_chain {
-            self.connect_tip(&block_index)?;
+            if let Err(err) = self.connect_tip(&block_index) {
+                self.failed_block_id = Some(block_index.block_id().clone());
+                return Err(err);
+            }
         }
 
+        if !disconnected.is_empty() {
+            self.events.push(ChainstateEvent::Reorg {
+                disconnected,
+                connected,
+            });
+        }
         Ok(())
     }
 
@@ -598,29 +841,171 @@ impl<'a, S: BlockchainStorageWrite, O: OrphanBlocksMut> ChainstateRef<'a, S, O>
         spend_height: &BlockHeight,
         blockreward_maturity: &BlockDistance,
     ) -> Result<(), BlockError> {
-        let cached_inputs =
-            self.make_cache_with_connected_transactions(block, spend_height, blockreward_maturity)?;
-        let cached_inputs = cached_inputs.consume()?;
+        let prev_block_id =
+            block.prev_block_id().ok_or(BlockError::InvariantErrorPrevBlockNotFound)?;
+        let median_time_past = calculate_median_time_past(self, &prev_block_id);
+        let block_subsidy = self.chain_config.block_subsidy_at_height(spend_height);
+
+        let mut utxo_store = UtxoDBImpl::new(&mut self.db_tx);
+        let utxo_db = UtxoDB::new(&mut utxo_store);
+        let mut cached_inputs = CachedInputs::new(&utxo_db);
+
+        // All the signatures of the block are checked at once, which keeps the worker pool busy
+        // even when the block is made of many small transactions
+        cached_inputs.verify_transaction_signatures(block)?;
+
+        cached_inputs.spend(
+            BlockTransactableRef::BlockReward(block),
+            spend_height,
+            &median_time_past,
+            blockreward_maturity,
+        )?;
+
+        for (tx_num, _tx) in block.transactions().iter().enumerate() {
+            cached_inputs.spend(
+                BlockTransactableRef::Transaction(block, tx_num),
+                spend_height,
+                &median_time_past,
+                blockreward_maturity,
+            )?;
+        }
+
+        cached_inputs.check_block_reward(block, block_subsidy)?;
+
+        let block_undo = cached_inputs.take_block_undo(*spend_height);
+        let cached_inputs = cached_inputs.consume(block.get_id())?;
 
         CachedInputs::flush_to_storage(&mut self.db_tx, cached_inputs)?;
-        Ok(())
+        self.db_tx.add_undo_data(block.get_id(), &block_undo)?;
+        self.connect_tx_index(block)?;
+        self.connect_address_index(block, &block_undo)
     }
 
     fn disconnect_transactions(&mut self, block: &Block) -> Result<(), BlockError> {
-        let cached_inputs = self.make_cache_with_disconnected_transactions(block)?;
-        let cached_inputs = cached_inputs.consume()?;
+        let prev_block_id =
+            block.prev_block_id().ok_or(BlockError::InvariantErrorPrevBlockNotFound)?;
+        let block_undo = self
+            .db_tx
+            .get_undo_data(block.get_id())?
+            .ok_or_else(|| StateUpdateError::MissingBlockUndo(block.get_id()))?;
+        ensure!(
+            block_undo.tx_undos().len() == block.transactions().len(),
+            StateUpdateError::BlockUndoMismatch(block.get_id()),
+        );
+
+        let mut utxo_store = UtxoDBImpl::new(&mut self.db_tx);
+        let utxo_db = UtxoDB::new(&mut utxo_store);
+        let mut cached_inputs = CachedInputs::new(&utxo_db);
+
+        for (tx_num, tx_undo) in block_undo.tx_undos().iter().enumerate().rev() {
+            cached_inputs.unspend(BlockTransactableRef::Transaction(block, tx_num), tx_undo)?;
+        }
+        let empty_undo = TxUndo::new_empty();
+        cached_inputs.unspend(
+            BlockTransactableRef::BlockReward(block),
+            block_undo.reward_undo().unwrap_or(&empty_undo),
+        )?;
+
+        let cached_inputs = cached_inputs.consume(prev_block_id)?;
 
         CachedInputs::flush_to_storage(&mut self.db_tx, cached_inputs)?;
-        Ok(())
+        self.db_tx.del_undo_data(block.get_id())?;
+        self.disconnect_tx_index(block)?;
+        self.disconnect_address_index(block, &block_undo)
     }
 
     fn connect_genesis_transactions(&mut self, block: &Block) -> Result<(), BlockError> {
-        for (num, tx) in block.transactions().iter().enumerate() {
-            self.db_tx.set_mainchain_tx_index(
-                &OutPointSourceId::from(tx.get_id()),
-                &calculate_tx_index_from_block(block, num)
-                    .expect("Index calculation for genesis failed"),
-            )?;
+        let mut utxo_store = UtxoDBImpl::new(&mut self.db_tx);
+        let utxo_db = UtxoDB::new(&mut utxo_store);
+        let mut cached_inputs = CachedInputs::new(&utxo_db);
+
+        cached_inputs.add_genesis_outputs(block)?;
+        let cached_inputs = cached_inputs.consume(block.get_id())?;
+
+        CachedInputs::flush_to_storage(&mut self.db_tx, cached_inputs)?;
+        self.connect_tx_index(block)?;
+
+        // The genesis spends nothing
+        let tx_undos = vec![TxUndo::new_empty(); block.transactions().len()];
+        let genesis_undo = BlockUndo::new(None, tx_undos, BlockHeight::new(0));
+        self.connect_address_index(block, &genesis_undo)
+    }
+
+    fn connect_tx_index(&mut self, block: &Block) -> Result<(), BlockError> {
+        if !self.chainstate_config.tx_index_enabled {
+            return Ok(());
+        }
+        for (tx_num, tx) in block.transactions().iter().enumerate() {
+            let tx_pos = tx_position_in_block(block, tx_num)?;
+            self.db_tx.set_mainchain_tx_index(&tx.get_id(), &tx_pos)?;
+        }
+        Ok(())
+    }
+
+    fn disconnect_tx_index(&mut self, block: &Block) -> Result<(), BlockError> {
+        if !self.chainstate_config.tx_index_enabled {
+            return Ok(());
+        }
+        for tx in block.transactions() {
+            self.db_tx.del_mainchain_tx_index(&tx.get_id())?;
+        }
+        Ok(())
+    }
+
+    fn connect_address_index(
+        &mut self,
+        block: &Block,
+        block_undo: &BlockUndo,
+    ) -> Result<(), BlockError> {
+        if !self.chainstate_config.address_index_enabled {
+            return Ok(());
+        }
+        for (source_id, inputs, outputs, undo) in block_operations(block, block_undo) {
+            for (output_index, output) in outputs.into_iter().enumerate() {
+                let outpoint = OutPoint::new(source_id.clone(), output_index as u32);
+                let entry = AddressHistoryEntry::new(outpoint, output, block_undo.height(), None);
+                self.set_address_history_entry(&entry)?;
+            }
+            for (input, utxo) in inputs.iter().zip(undo.inner()) {
+                let entry = address_history_of_spent(input, utxo, Some(source_id.clone()))?;
+                self.set_address_history_entry(&entry)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn disconnect_address_index(
+        &mut self,
+        block: &Block,
+        block_undo: &BlockUndo,
+    ) -> Result<(), BlockError> {
+        if !self.chainstate_config.address_index_enabled {
+            return Ok(());
+        }
+        // In reverse, so the outputs both created and spent in the block end up removed
+        for (source_id, inputs, outputs, undo) in
+            block_operations(block, block_undo).into_iter().rev()
+        {
+            for (input, utxo) in inputs.iter().zip(undo.inner()) {
+                let entry = address_history_of_spent(input, utxo, None)?;
+                self.set_address_history_entry(&entry)?;
+            }
+            for (output_index, output) in outputs.iter().enumerate() {
+                let key = match AddressIndexKey::from_destination(output.purpose().destination()) {
+                    Some(key) => key,
+                    None => continue,
+                };
+                let outpoint = OutPoint::new(source_id.clone(), output_index as u32);
+                self.db_tx.del_address_history_entry(&key, block_undo.height(), &outpoint)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn set_address_history_entry(&mut self, entry: &AddressHistoryEntry) -> Result<(), BlockError> {
+        if let Some(key) = AddressIndexKey::from_destination(entry.output().purpose().destination())
+        {
+            self.db_tx.set_address_history_entry(&key, entry)?;
         }
         Ok(())
     }
@@ -642,12 +1027,18 @@ impl<'a, S: BlockchainStorageWrite, O: OrphanBlocksMut> ChainstateRef<'a, S, O>
             )?;
         }
 
+        let mut new_tip_block_index = new_tip_block_index.clone();
+        new_tip_block_index.set_status(BlockStatus::FullyValid);
         self.db_tx.set_block_id_at_height(
             &new_tip_block_index.block_height(),
             new_tip_block_index.block_id(),
         )?;
-        self.db_tx.set_block_index(new_tip_block_index)?;
+        self.db_tx.set_block_index(&new_tip_block_index)?;
         self.db_tx.set_best_block_id(new_tip_block_index.block_id())?;
+        self.events.push(ChainstateEvent::BlockConnected(
+            new_tip_block_index.block_id().clone(),
+            new_tip_block_index.block_height(),
+        ));
         Ok(())
     }
 
@@ -683,6 +1074,10 @@ impl<'a, S: BlockchainStorageWrite, O: OrphanBlocksMut> ChainstateRef<'a, S, O>
         )?;
         // Disconnect block
         self.db_tx.del_block_id_at_height(&block_index.block_height())?;
+        self.events.push(ChainstateEvent::BlockDisconnected(
+            block_index.block_id().clone(),
+            block_index.block_height(),
+        ));
 
         let prev_block_index = self
             .get_previous_block_index(&block_index)
@@ -697,14 +1092,16 @@ impl<'a, S: BlockchainStorageWrite, O: OrphanBlocksMut> ChainstateRef<'a, S, O>
     ) -> Result<Option<BlockIndex>, BlockError> {
         if best_block_id.is_none() && genesis_block_index.is_genesis(self.chain_config) {
             self.connect_tip(genesis_block_index)?;
-            return Ok(Some(genesis_block_index.clone()));
+            let mut genesis_block_index = genesis_block_index.clone();
+            genesis_block_index.set_status(BlockStatus::FullyValid);
+            return Ok(Some(genesis_block_index));
         }
         Ok(None)
     }
 
     pub fn activate_best_chain(
         &mut self,
-        new_block_index: BlockIndex,
+        mut new_block_index: BlockIndex,
         best_block_id: Option<Id<Block>>,
     ) -> Result<Option<BlockIndex>, BlockError> {
         let connected_genesis = self.try_connect_genesis_block(&new_block_index, &best_block_id)?;
@@ -722,6 +1119,7 @@ impl<'a, S: BlockchainStorageWrite, O: OrphanBlocksMut> ChainstateRef<'a, S, O>
 
         if new_block_index.chain_trust() > current_best_block_index.chain_trust() {
             self.reorganize(&best_block_id, &new_block_index)?;
+            new_block_index.set_status(BlockStatus::FullyValid);
             return Ok(Some(new_block_index));
         }
 
@@ -783,6 +1181,109 @@ impl<'a, S: BlockchainStorageWrite, O: OrphanBlocksMut> ChainstateRef<'a, S, O>
         Ok(block_index)
     }
 
+    /// Record that a block failed validation or descends from a block that did.
+    ///
+    /// The failed block is either the block itself or one of its ancestors. In the latter case,
+    /// the blocks in between are marked as descending from a failed block too.
+    pub fn mark_block_failed(
+        &mut self,
+        block: &Block,
+        failed_block_id: &Id<Block>,
+    ) -> Result<(), BlockError> {
+        let mut block_index = self.add_to_block_index(block)?;
+        loop {
+            let is_failed_block = block_index.block_id() == failed_block_id;
+            if !block_index.status().is_failed() {
+                block_index.set_status(match is_failed_block {
+                    true => BlockStatus::Failed,
+                    false => BlockStatus::FailedAncestor,
+                });
+                self.db_tx.set_block_index(&block_index)?;
+            }
+            if is_failed_block {
+                return Ok(());
+            }
+            let prev_block_id = block_index
+                .prev_block_id()
+                .as_ref()
+                .ok_or(BlockError::InvariantErrorPrevBlockNotFound)?;
+            block_index = self
+                .db_tx
+                .get_block_index(prev_block_id)?
+                .ok_or(BlockError::InvariantErrorPrevBlockNotFound)?;
+        }
+    }
+
+    /// Mark a block as invalid on request, along with all its known descendants.
+    ///
+    /// If the block is in the main chain, the chain is disconnected down to its parent. Returns
+    /// the new best block index in that case.
+    pub fn invalidate_block(
+        &mut self,
+        block_id: &Id<Block>,
+    ) -> Result<Option<BlockIndex>, BlockError> {
+        let mut block_index = self
+            .db_tx
+            .get_block_index(block_id)?
+            .ok_or_else(|| BlockError::BlockNotFound(block_id.clone()))?;
+        ensure!(
+            !block_index.is_genesis(self.chain_config),
+            BlockError::InvalidateGenesis
+        );
+
+        let new_best_block_index = if self.is_block_in_main_chain(&block_index)? {
+            let best_block_index =
+                self.get_best_block_index()?.ok_or(PropertyQueryError::BestBlockIndexNotFound)?;
+            let prev_block_id =
+                block_index.prev_block_id().as_ref().expect("Only genesis has no parent");
+            self.disconnect_until(&best_block_index, prev_block_id)?;
+            self.get_best_block_index()?
+        } else {
+            None
+        };
+
+        block_index.set_status(BlockStatus::Failed);
+        self.db_tx.set_block_index(&block_index)?;
+
+        for mut descendant in self.get_descendants(block_id)? {
+            if !descendant.status().is_failed() {
+                descendant.set_status(BlockStatus::FailedAncestor);
+                self.db_tx.set_block_index(&descendant)?;
+            }
+        }
+
+        Ok(new_best_block_index)
+    }
+
+    /// Clear the invalid status of a block, its ancestors and descendants.
+    ///
+    /// Blocks rejected before being stored are not affected, as there is nothing to validate
+    /// them again with.
+    pub fn reconsider_block(&mut self, block_id: &Id<Block>) -> Result<(), BlockError> {
+        let block_index = self
+            .db_tx
+            .get_block_index(block_id)?
+            .ok_or_else(|| BlockError::BlockNotFound(block_id.clone()))?;
+
+        let mut to_reconsider = Vec::new();
+        let mut ancestor = block_index;
+        while !self.is_block_in_main_chain(&ancestor)? {
+            to_reconsider.push(ancestor.clone());
+            ancestor = self.get_previous_block_index(&ancestor)?;
+        }
+        to_reconsider.extend(self.get_descendants(block_id)?);
+
+        for mut block_index in to_reconsider {
+            if block_index.status().is_failed()
+                && self.db_tx.get_block(block_index.block_id().clone())?.is_some()
+            {
+                block_index.set_status(BlockStatus::HeaderValid);
+                self.db_tx.set_block_index(&block_index)?;
+            }
+        }
+        Ok(())
+    }
+
     /// Mark new block as an orphan
     fn new_orphan_block(&mut self, block: Block) -> Result<(), OrphanCheckError> {
         // It can't be a genesis block
@@ -793,3 +1294,59 @@ impl<'a, S: BlockchainStorageWrite, O: OrphanBlocksMut> ChainstateRef<'a, S, O>
         }
     }
 }
+
+fn tx_position_in_block(
+    block: &Block,
+    tx_num: usize,
+) -> Result<TxMainChainPosition, StateUpdateError> {
+    match calculate_tx_index_from_block(block, tx_num)?.position() {
+        SpendablePosition::Transaction(tx_pos) => Ok(tx_pos.clone()),
+        SpendablePosition::BlockReward(_) => Err(StateUpdateError::SerializationInvariantError(
+            block.get_id(),
+        )),
+    }
+}
+
+// The block reward and the transactions of a block, each with the source ID of its outputs, the
+// inputs it spends, the outputs it creates and its undo data
+fn block_operations(
+    block: &Block,
+    block_undo: &BlockUndo,
+) -> Vec<(OutPointSourceId, Vec<TxInput>, Vec<TxOutput>, TxUndo)> {
+    let reward_transactable = block.header().block_reward_transactable();
+    let reward = (
+        OutPointSourceId::from(block.get_id()),
+        reward_transactable.inputs().unwrap_or(&[]).to_vec(),
+        reward_transactable.outputs().unwrap_or(&[]).to_vec(),
+    );
+    let txs = block.transactions().iter().map(|tx| {
+        (
+            tx.get_id().into(),
+            tx.inputs().clone(),
+            tx.outputs().clone(),
+        )
+    });
+    let reward_undo = block_undo.reward_undo().cloned().unwrap_or_else(TxUndo::new_empty);
+    let undos = std::iter::once(reward_undo).chain(block_undo.tx_undos().iter().cloned());
+
+    std::iter::once(reward)
+        .chain(txs)
+        .zip(undos)
+        .map(|((source_id, inputs, outputs), undo)| (source_id, inputs, outputs, undo))
+        .collect()
+}
+
+// History entry of an output spent by given input, restored from the undo data
+fn address_history_of_spent(
+    input: &TxInput,
+    utxo: &Utxo,
+    spent_by: Option<OutPointSourceId>,
+) -> Result<AddressHistoryEntry, StateUpdateError> {
+    let height = utxo.source_height().blockchain_height()?;
+    Ok(AddressHistoryEntry::new(
+        input.outpoint().clone(),
+        utxo.output().clone(),
+        height,
+        spent_by,
+    ))
+}
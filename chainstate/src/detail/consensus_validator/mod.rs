@@ -9,12 +9,10 @@ use common::primitives::Idable;
 use crate::detail::pow::work::check_pow_consensus;
 
 pub use self::block_index_handle::BlockIndexHandle;
-pub use self::transaction_index_handle::TransactionIndexHandle;
 
 use super::ConsensusVerificationError;
 
 mod block_index_handle;
-mod transaction_index_handle;
 
 pub(crate) fn validate_consensus<H: BlockIndexHandle>(
     chain_config: &ChainConfig,
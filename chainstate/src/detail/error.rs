@@ -16,8 +16,8 @@
 // Author(s): S. Afach, A. Sinitsyn
 
 use common::{
-    chain::{block::Block, Transaction},
-    primitives::{BlockHeight, Id},
+    chain::{block::Block, config::ChainType, Transaction},
+    primitives::{BlockDistance, BlockHeight, Id},
 };
 use thiserror::Error;
 
@@ -54,6 +54,36 @@ pub enum BlockError {
     DatabaseCommitError(Id<Block>, usize, chainstate_storage::Error),
     #[error("Block proof calculation error for block: {0}")]
     BlockProofCalculationError(Id<Block>),
+    #[error("Block {0} has already been found invalid")]
+    BlockMarkedInvalid(Id<Block>),
+    #[error("Block {0} descends from block {1} which has been found invalid")]
+    InvalidAncestor(Id<Block>, Id<Block>),
+    #[error("Block {0} not found")]
+    BlockNotFound(Id<Block>),
+    #[error("Genesis block cannot be invalidated")]
+    InvalidateGenesis,
+    #[error("Failed to query block data: {0}")]
+    BlockQueryError(#[from] PropertyQueryError),
+    #[error("Block {2} at height {0} does not match the checkpoint {1}")]
+    CheckpointMismatch(BlockHeight, Id<Block>, Id<Block>),
+    #[error("Block at height {0} forks off below the checkpoint at height {1}")]
+    ForkBelowCheckpoint(BlockHeight, BlockHeight),
+    #[error("Block {0} forks off {1} blocks below the tip, more than the maximum reorg depth {2}")]
+    ReorgTooDeep(Id<Block>, BlockDistance, BlockDistance),
+}
+
+#[derive(Error, Debug, PartialEq, Eq, Clone)]
+pub enum BlockGenerationError {
+    #[error("Block generation is not allowed on {0}")]
+    NotAllowed(ChainType),
+    #[error("Block generation needs PoW consensus, which is not in use at height {0}")]
+    NeedsPoW(BlockHeight),
+    #[error("Failed to solve the proof of work of a generated block: {0}")]
+    PoWError(ConsensusPoWError),
+    #[error("Failed to query block data: {0}")]
+    QueryError(#[from] PropertyQueryError),
+    #[error("Failed to process the generated block: {0}")]
+    ProcessBlockError(#[from] BlockError),
 }
 
 #[derive(Error, Debug, PartialEq, Eq, Clone)]
@@ -133,6 +163,14 @@ pub enum PropertyQueryError {
         block_height: BlockHeight,
         ancestor_height: BlockHeight,
     },
+    #[error("Transaction index is disabled")]
+    TxIndexDisabled,
+    #[error("Indexed transaction {0} not found")]
+    IndexedTxNotFound(Id<Transaction>),
+    #[error("Address index is disabled")]
+    AddressIndexDisabled,
+    #[error("Invalid address {0}")]
+    InvalidAddress(String),
 }
 
 #[derive(Error, Debug, PartialEq, Eq, Clone)]
@@ -72,8 +72,14 @@ mod test {
         common::concurrency::model(|| {
             let chain_config = Arc::new(create_unit_test_config());
             let storage = Store::new_empty().unwrap();
-            let mut chainstate =
-                Chainstate::new(chain_config, storage, None, Default::default()).unwrap();
+            let mut chainstate = Chainstate::new(
+                chain_config,
+                Default::default(),
+                storage,
+                None,
+                Default::default(),
+            )
+            .unwrap();
 
             let block_count = 500;
 
@@ -136,7 +142,9 @@ mod test {
             }));
 
             let storage = Store::new_empty().unwrap();
-            let mut chainstate = Chainstate::new(chain_config, storage, None, time_getter).unwrap();
+            let mut chainstate =
+                Chainstate::new(chain_config, Default::default(), storage, None, time_getter)
+                    .unwrap();
 
             // we use unordered block times, and ensure that the median will be in the right spot
             let block1_time = current_time.load(Ordering::SeqCst) as u32 + 1;
@@ -16,15 +16,20 @@
 // Author(s): S. Afach, A. Sinitsyn
 
 use crate::detail::orphan_blocks::OrphanBlocksPool;
-use crate::ChainstateEvent;
-use chainstate_storage::Transactional;
+use crate::event_stream::EventStreams;
+use crate::{BlockTemplate, ChainTip, ChainstateConfig, ChainstateEvent, EventReceiver};
+use chainstate_storage::{BlockchainStorage, Transactional};
+use chainstate_types::address_index::{AddressHistoryEntry, AddressIndexKey};
 use chainstate_types::block_index::BlockIndex;
+use common::address::Address;
 use common::chain::block::{Block, BlockHeader};
 use common::chain::config::ChainConfig;
+use common::chain::Transaction;
 use common::primitives::{BlockDistance, BlockHeight, Id, Idable};
 use itertools::Itertools;
 use logging::log;
 use std::sync::Arc;
+use utils::ensure;
 use utils::eventhandler::{EventHandler, EventsController};
 mod consensus_validator;
 mod orphan_blocks;
@@ -32,6 +37,7 @@ mod orphan_blocks;
 mod error;
 pub use error::*;
 
+use self::ban_score::BanScore;
 use self::orphan_blocks::{OrphanBlocksRef, OrphanBlocksRefMut};
 
 mod pow;
@@ -42,8 +48,8 @@ mod median_time;
 
 mod chainstateref;
 
-type TxRw<'a> = <chainstate_storage::Store as Transactional<'a>>::TransactionRw;
-type TxRo<'a> = <chainstate_storage::Store as Transactional<'a>>::TransactionRo;
+type TxRw<'a, S> = <S as Transactional<'a>>::TransactionRw;
+type TxRo<'a, S> = <S as Transactional<'a>>::TransactionRo;
 type ChainstateEventHandler = EventHandler<ChainstateEvent>;
 
 const HEADER_LIMIT: BlockDistance = BlockDistance::new(2000);
@@ -56,12 +62,15 @@ pub mod time_getter;
 use time_getter::TimeGetter;
 
 #[must_use]
-pub struct Chainstate {
+pub struct Chainstate<S = chainstate_storage::Store> {
     chain_config: Arc<ChainConfig>,
-    chainstate_storage: chainstate_storage::Store,
+    chainstate_config: ChainstateConfig,
+    chainstate_storage: S,
     orphan_blocks: OrphanBlocksPool,
     custom_orphan_error_hook: Option<Arc<OrphanErrorHandler>>,
     events_controller: EventsController<ChainstateEvent>,
+    // Event stream subscriptions, registered as one event handler on first use
+    event_streams: Option<EventStreams>,
     time_getter: TimeGetter,
 }
 
@@ -71,16 +80,17 @@ pub enum BlockSource {
     Local,
 }
 
-impl Chainstate {
+impl<S: BlockchainStorage> Chainstate<S> {
     pub fn wait_for_all_events(&self) {
         self.events_controller.wait_for_all_events();
     }
 
     #[must_use]
-    fn make_db_tx(&mut self) -> chainstateref::ChainstateRef<TxRw, OrphanBlocksRefMut> {
+    fn make_db_tx(&mut self) -> chainstateref::ChainstateRef<TxRw<'_, S>, OrphanBlocksRefMut> {
         let db_tx = self.chainstate_storage.transaction_rw();
         chainstateref::ChainstateRef::new_rw(
             &self.chain_config,
+            &self.chainstate_config,
             db_tx,
             self.orphan_blocks.as_rw_ref(),
             self.time_getter.getter(),
@@ -88,10 +98,11 @@ impl Chainstate {
     }
 
     #[must_use]
-    fn make_db_tx_ro(&self) -> chainstateref::ChainstateRef<TxRo, OrphanBlocksRef> {
+    fn make_db_tx_ro(&self) -> chainstateref::ChainstateRef<TxRo<'_, S>, OrphanBlocksRef> {
         let db_tx = self.chainstate_storage.transaction_ro();
         chainstateref::ChainstateRef::new_ro(
             &self.chain_config,
+            &self.chainstate_config,
             db_tx,
             self.orphan_blocks.as_ro_ref(),
             self.time_getter.getter(),
@@ -102,9 +113,23 @@ impl Chainstate {
         self.events_controller.subscribe_to_events(handler);
     }
 
+    /// Subscribe to events through a receiver buffering up to given number of events
+    pub fn subscribe_to_event_stream(&mut self, buffer_size: usize) -> EventReceiver {
+        let streams = match &self.event_streams {
+            Some(streams) => streams.clone(),
+            None => {
+                let streams = EventStreams::default();
+                self.subscribe_to_events(streams.handler());
+                self.event_streams.insert(streams).clone()
+            }
+        };
+        streams.subscribe(buffer_size)
+    }
+
     pub fn new(
         chain_config: Arc<ChainConfig>,
-        chainstate_storage: chainstate_storage::Store,
+        chainstate_config: ChainstateConfig,
+        chainstate_storage: S,
         custom_orphan_error_hook: Option<Arc<OrphanErrorHandler>>,
         time_getter: TimeGetter,
     ) -> Result<Self, crate::ChainstateError> {
@@ -112,6 +137,7 @@ impl Chainstate {
 
         let mut cons = Self::new_no_genesis(
             chain_config,
+            chainstate_config,
             chainstate_storage,
             custom_orphan_error_hook,
             time_getter,
@@ -138,21 +164,57 @@ impl Chainstate {
 
     fn new_no_genesis(
         chain_config: Arc<ChainConfig>,
-        chainstate_storage: chainstate_storage::Store,
+        chainstate_config: ChainstateConfig,
+        chainstate_storage: S,
         custom_orphan_error_hook: Option<Arc<OrphanErrorHandler>>,
         time_getter: TimeGetter,
     ) -> Result<Self, crate::ChainstateError> {
-        let cons = Self {
+        let mut cons = Self {
             chain_config,
+            chainstate_config,
             chainstate_storage,
             orphan_blocks: OrphanBlocksPool::new_default(),
             custom_orphan_error_hook,
             events_controller: EventsController::new(),
+            event_streams: None,
             time_getter,
         };
+        cons.check_index_settings()?;
         Ok(cons)
     }
 
+    // The indices are only complete if they have been maintained since the storage was
+    // initialized, so their settings cannot change afterwards
+    fn check_index_settings(&mut self) -> Result<(), crate::ChainstateError> {
+        // Blocks stored without a setting recorded have not been indexed
+        let has_blocks = self
+            .chainstate_storage
+            .get_best_block_id()
+            .map_err(|e| {
+                crate::ChainstateError::FailedToInitializeChainstate(format!(
+                    "Database read error: {:?}",
+                    e
+                ))
+            })?
+            .is_some();
+        check_index_setting(
+            &mut self.chainstate_storage,
+            "transaction",
+            self.chainstate_config.tx_index_enabled,
+            has_blocks,
+            S::get_is_mainchain_tx_index_enabled,
+            S::set_is_mainchain_tx_index_enabled,
+        )?;
+        check_index_setting(
+            &mut self.chainstate_storage,
+            "address",
+            self.chainstate_config.address_index_enabled,
+            has_blocks,
+            S::get_is_address_index_enabled,
+            S::set_is_address_index_enabled,
+        )
+    }
+
     fn broadcast_new_tip_event(&self, new_block_index: &Option<BlockIndex>) {
         match new_block_index {
             Some(ref new_block_index) => {
@@ -164,6 +226,10 @@ impl Chainstate {
         }
     }
 
+    fn broadcast_events(&self, events: Vec<ChainstateEvent>) {
+        events.into_iter().for_each(|event| self.events_controller.broadcast(event));
+    }
+
     /// returns the new block index, which is the new tip, if any
     fn process_orphans(&mut self, last_processed_block: &Id<Block>) -> Option<BlockIndex> {
         let orphans = self.orphan_blocks.take_all_children_of(last_processed_block);
@@ -198,11 +264,99 @@ impl Chainstate {
                 db_error,
             ))
         } else {
-            // TODO: test reattempts using mocks of the database that emulate failure
             self.attempt_to_process_block(block, block_source, attempt_number + 1)
         }
     }
 
+    // Remember the block is invalid so it is not validated again
+    fn mark_block_failed(
+        &mut self,
+        block: &Block,
+        failed_block_id: &Id<Block>,
+    ) -> Result<(), BlockError> {
+        let mut chainstate_ref = self.make_db_tx();
+        chainstate_ref.mark_block_failed(block, failed_block_id)?;
+        Ok(chainstate_ref.commit_db_tx()?)
+    }
+
+    /// Update the block statuses using given function, then switch to the best chain among the
+    /// blocks not known to be invalid if it has more trust than the current one. Both are done
+    /// in a single transaction.
+    ///
+    /// Chains failing to connect are marked invalid and skipped, and the update is made again
+    /// on top of that. Returns the result of the update and the new tip if the chain was switched.
+    fn update_and_activate_best_chain<T>(
+        &mut self,
+        update: impl Fn(
+            &mut chainstateref::ChainstateRef<TxRw<'_, S>, OrphanBlocksRefMut>,
+        ) -> Result<T, BlockError>,
+    ) -> Result<(T, Option<BlockIndex>), BlockError> {
+        loop {
+            let mut chainstate_ref = self.make_db_tx();
+            let result = update(&mut chainstate_ref)?;
+            let candidate = match chainstate_ref.find_best_chain_candidate()? {
+                Some(candidate) => candidate,
+                None => {
+                    let events = chainstate_ref.take_events();
+                    chainstate_ref.commit_db_tx()?;
+                    self.broadcast_events(events);
+                    return Ok((result, None));
+                }
+            };
+            let best_block_id =
+                chainstate_ref.get_best_block_id().map_err(BlockError::BestBlockLoadError)?;
+            let err = match chainstate_ref.activate_best_chain(candidate.clone(), best_block_id) {
+                Ok(new_tip) => {
+                    let events = chainstate_ref.take_events();
+                    chainstate_ref.commit_db_tx()?;
+                    self.broadcast_events(events);
+                    return Ok((result, new_tip));
+                }
+                Err(err) => err,
+            };
+            let failed_block_id = match &err {
+                BlockError::InvalidAncestor(_, failed_block_id) => failed_block_id.clone(),
+                _ => chainstate_ref.failed_block_id().cloned().ok_or(err)?,
+            };
+            drop(chainstate_ref);
+
+            log::warn!(
+                "Failed to switch to chain with tip {}, block {} is invalid",
+                candidate.block_id(),
+                failed_block_id
+            );
+            let block = self
+                .get_block(candidate.block_id().clone())?
+                .ok_or_else(|| BlockError::BlockNotFound(candidate.block_id().clone()))?;
+            self.mark_block_failed(&block, &failed_block_id)?;
+        }
+    }
+
+    /// Mark a block and all its descendants as invalid, switching to the best remaining chain
+    /// if the block is in the main chain.
+    pub fn invalidate_block(&mut self, block_id: &Id<Block>) -> Result<(), BlockError> {
+        log::info!("Invalidating block: {}", block_id);
+
+        let (disconnected_tip, new_tip) =
+            self.update_and_activate_best_chain(|chainstate_ref| {
+                chainstate_ref.invalidate_block(block_id)
+            })?;
+        self.broadcast_new_tip_event(&new_tip.or(disconnected_tip));
+        Ok(())
+    }
+
+    /// Undo the effect of invalidating a block, switching to the chain it is in if that is
+    /// the best chain.
+    pub fn reconsider_block(&mut self, block_id: &Id<Block>) -> Result<(), BlockError> {
+        log::info!("Reconsidering block: {}", block_id);
+
+        let ((), new_tip) = self.update_and_activate_best_chain(|chainstate_ref| {
+            chainstate_ref.reconsider_block(block_id)
+        })?;
+        self.broadcast_new_tip_event(&new_tip);
+        Ok(())
+    }
+
     pub fn attempt_to_process_block(
         &mut self,
         block: Block,
@@ -222,10 +376,59 @@ impl Chainstate {
         let best_block_id =
             chainstate_ref.get_best_block_id().map_err(BlockError::BestBlockLoadError)?;
 
-        chainstate_ref.check_block(&block).map_err(BlockError::CheckBlockFailed)?;
-
-        let block_index = chainstate_ref.accept_block(&block)?;
-        let result = chainstate_ref.activate_best_chain(block_index, best_block_id)?;
+        let result = chainstate_ref
+            .check_block_status(&block)
+            .and_then(|()| chainstate_ref.check_block(&block).map_err(BlockError::CheckBlockFailed))
+            .and_then(|()| chainstate_ref.accept_block(&block))
+            .and_then(|block_index| chainstate_ref.activate_best_chain(block_index, best_block_id));
+        let result = match result {
+            Ok(result) => result,
+            Err(err) => {
+                let failed_block_id = match &err {
+                    // Already recorded
+                    BlockError::BlockMarkedInvalid(_) => None,
+                    BlockError::InvalidAncestor(_, failed_block_id) => {
+                        Some(failed_block_id.clone())
+                    }
+                    // The block may become valid later
+                    BlockError::CheckBlockFailed(CheckBlockError::BlockFromTheFuture) => None,
+                    // The block id only commits to the header. Without valid proof of work, the
+                    // header may have been made up, and the transactions may have been tampered
+                    // with in transit. Recording such blocks would let anyone fill the block
+                    // index or get a valid block with the same id rejected.
+                    BlockError::CheckBlockFailed(
+                        CheckBlockError::ConsensusVerificationFailed(_)
+                        | CheckBlockError::MerkleRootMismatch
+                        | CheckBlockError::WitnessMerkleRootMismatch
+                        | CheckBlockError::BlockSizeError(_)
+                        | CheckBlockError::CheckTransactionFailed(
+                            CheckBlockTransactionsError::DuplicateInputInBlock(_)
+                            | CheckBlockTransactionsError::DuplicatedTransactionInBlock(_, _),
+                        ),
+                    ) => None,
+                    // Not caused by the block being invalid
+                    BlockError::ReorgTooDeep(_, _, _) => None,
+                    err if err.ban_score() == 0 => None,
+                    BlockError::CheckBlockFailed(_)
+                    | BlockError::CheckpointMismatch(_, _, _)
+                    | BlockError::ForkBelowCheckpoint(_, _) => Some(block.get_id()),
+                    _ => chainstate_ref.failed_block_id().cloned(),
+                };
+                drop(chainstate_ref);
+                if let Some(failed_block_id) = failed_block_id {
+                    // Failing to record the block is not fatal, it is validated again next time
+                    if let Err(mark_err) = self.mark_block_failed(&block, &failed_block_id) {
+                        log::error!(
+                            "Failed to record block {} as invalid: {}",
+                            block.get_id(),
+                            mark_err
+                        );
+                    }
+                }
+                return Err(err);
+            }
+        };
+        let events = chainstate_ref.take_events();
         let db_commit_result = chainstate_ref.commit_db_tx();
         match db_commit_result {
             Ok(_) => {}
@@ -233,6 +436,7 @@ impl Chainstate {
                 return self.process_db_commit_error(err, block, block_source, attempt_number)
             }
         }
+        self.broadcast_events(events);
 
         let new_block_index_after_orphans = self.process_orphans(&block.get_id());
         let result = match new_block_index_after_orphans {
@@ -326,6 +530,64 @@ impl Chainstate {
         self.make_db_tx_ro().get_block_height_in_main_chain(id)
     }
 
+    pub fn get_storage_stats(&self) -> Result<chainstate_storage::Stats, PropertyQueryError> {
+        Ok(self.chainstate_storage.stats()?)
+    }
+
+    /// Get a mainchain transaction along with the ID and height of the block containing it
+    pub fn get_transaction(
+        &self,
+        tx_id: &Id<Transaction>,
+    ) -> Result<Option<(Transaction, Id<Block>, BlockHeight)>, PropertyQueryError> {
+        ensure!(
+            self.chainstate_config.tx_index_enabled,
+            PropertyQueryError::TxIndexDisabled
+        );
+        self.make_db_tx_ro().get_transaction(tx_id)
+    }
+
+    /// Parameters for external miners of a new block on the best block
+    pub fn get_block_template(&self) -> Result<BlockTemplate, BlockGenerationError> {
+        self.make_db_tx_ro().get_block_template()
+    }
+
+    /// Build a block on the best block paying the block subsidy to given address, mine it and
+    /// process it. Only allowed on chains meant for testing.
+    pub fn generate_block(&mut self, address: &Address) -> Result<Block, BlockGenerationError> {
+        let destination = address
+            .destination(&self.chain_config)
+            .map_err(|_| PropertyQueryError::InvalidAddress(address.get().to_owned()))?;
+        let block = self.make_db_tx_ro().generate_block(destination)?;
+        self.process_block(block.clone(), BlockSource::Local)?;
+        Ok(block)
+    }
+
+    /// List the blocks in the block index that no other block builds on
+    pub fn get_chain_tips(&self) -> Result<Vec<ChainTip>, PropertyQueryError> {
+        self.make_db_tx_ro().get_chain_tips()
+    }
+
+    /// Get the mainchain outputs paid to given address, oldest first, skipping `skip` outputs and
+    /// returning at most `limit` of them
+    pub fn get_address_history(
+        &self,
+        address: &Address,
+        skip: usize,
+        limit: usize,
+    ) -> Result<Vec<AddressHistoryEntry>, PropertyQueryError> {
+        ensure!(
+            self.chainstate_config.address_index_enabled,
+            PropertyQueryError::AddressIndexDisabled
+        );
+        let key = address
+            .destination(&self.chain_config)
+            .ok()
+            .as_ref()
+            .and_then(AddressIndexKey::from_destination)
+            .ok_or_else(|| PropertyQueryError::InvalidAddress(address.get().to_owned()))?;
+        self.make_db_tx_ro().get_address_history(&key, skip, limit)
+    }
+
     pub fn get_headers(
         &self,
         locator: Vec<BlockHeader>,
@@ -388,3 +650,45 @@ impl Chainstate {
 
 #[cfg(test)]
 mod tests;
+
+// Record the setting of an index if there is none, or check it matches the recorded one. An index
+// missing the blocks already in the storage is recorded as disabled.
+fn check_index_setting<S>(
+    storage: &mut S,
+    index_name: &str,
+    enabled: bool,
+    has_blocks: bool,
+    get: impl FnOnce(&S) -> chainstate_storage::Result<Option<bool>>,
+    set: impl FnOnce(&mut S, bool) -> chainstate_storage::Result<()>,
+) -> Result<(), crate::ChainstateError> {
+    use crate::ChainstateError;
+
+    let db_error =
+        |e| ChainstateError::FailedToInitializeChainstate(format!("Database error: {:?}", e));
+    let stored = match get(storage).map_err(db_error)? {
+        Some(stored) => stored,
+        None if has_blocks => {
+            set(storage, false).map_err(db_error)?;
+            false
+        }
+        None => return set(storage, enabled).map_err(db_error),
+    };
+    // The index can only be built by processing the whole chain again
+    ensure!(
+        stored == enabled,
+        ChainstateError::FailedToInitializeChainstate(if enabled {
+            format!(
+                "The {} index is disabled in the storage, cannot enable it. To build the index, \
+                resync the chain from scratch into an empty data directory.",
+                index_name
+            )
+        } else {
+            format!(
+                "The {} index is enabled in the storage, cannot disable it. Keep the index \
+                enabled or resync the chain from scratch into an empty data directory.",
+                index_name
+            )
+        })
+    );
+    Ok(())
+}
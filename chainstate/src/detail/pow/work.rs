@@ -63,7 +63,7 @@ pub(crate) fn check_pow_consensus<H: BlockIndexHandle>(
     }
 }
 
-fn calculate_work_required<H: BlockIndexHandle>(
+pub(crate) fn calculate_work_required<H: BlockIndexHandle>(
     chain_config: &ChainConfig,
     header: &BlockHeader,
     pow_status: &PoWStatus,
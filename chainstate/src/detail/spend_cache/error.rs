@@ -1,5 +1,5 @@
 use common::{
-    chain::{block::Block, SpendError, Spender, TxMainChainIndexError, TxMainChainPosition},
+    chain::{block::Block, OutPointSourceId, Transaction, TxMainChainIndexError},
     primitives::{Amount, Id},
 };
 use thiserror::Error;
@@ -18,51 +18,40 @@ pub enum StateUpdateError {
     OutputAlreadyPresentInInputsCache,
     #[error("Block reward spent immaturely")]
     ImmatureBlockRewardSpend,
-    #[error("Input was cached, but could not be found")]
-    PreviouslyCachedInputNotFound,
-    #[error("Input was cached, but it is erased")]
-    PreviouslyCachedInputWasErased,
     #[error("Block disconnect already-unspent (invaraint broken)")]
     InvariantBrokenAlreadyUnspent,
-    #[error("Source block index for block reward output not found")]
-    InvariantBrokenSourceBlockIndexNotFound,
     #[error("Output is not found in the cache or database")]
     MissingOutputOrSpent,
-    #[error("While connecting a block, output was erased in a previous step (possible in reorgs with no cache flushing)")]
-    MissingOutputOrSpentOutputErasedOnConnect,
-    #[error("While disconnecting a block, output was erased in a previous step (possible in reorgs with no cache flushing)")]
-    MissingOutputOrSpentOutputErasedOnDisconnect,
+    #[error("While disconnecting a block, an output it created is not found or already spent")]
+    MissingOutputOrSpentOnDisconnect,
     #[error("Attempt to print money (total inputs: `{0:?}` vs total outputs `{1:?}`")]
     AttemptToPrintMoney(Amount, Amount),
     #[error("Fee calculation failed (total inputs: `{0:?}` vs total outputs `{1:?}`")]
     TxFeeTotalCalcFailed(Amount, Amount),
     #[error("Output addition error")]
     OutputAdditionError,
-    #[error("Signature verification failed in transaction")]
-    SignatureVerificationFailed,
-    #[error("Invalid output count")]
-    InvalidOutputCount,
+    #[error("Signature verification failed for input {1} of `{0:?}`")]
+    SignatureVerificationFailed(OutPointSourceId, usize),
     #[error("Block distance calculation for maturity failed")]
     BlockHeightArithmeticError,
     #[error("Input addition error")]
     InputAdditionError,
-    #[error("Double-spend attempt")]
-    DoubleSpendAttempt(Spender),
-    #[error("Input of tx {tx_id:?} has an out-of-range output index {source_output_index}")]
-    OutputIndexOutOfRange {
-        tx_id: Option<Spender>,
-        source_output_index: usize,
-    },
-    #[error("Transaction index found but transaction not found")]
-    InvariantErrorTransactionCouldNotBeLoaded(TxMainChainPosition),
-    #[error("Transaction index for header found but header not found")]
-    InvariantErrorHeaderCouldNotBeLoaded(Id<Block>),
     #[error("Addition of all fees in block `{0}` failed")]
     FailedToAddAllFeesOfBlock(Id<Block>),
     #[error("Block reward addition error for block {0}")]
     RewardAdditionError(Id<Block>),
+    #[error("Invalid output count")]
+    InvalidOutputCount,
     #[error("Serialization invariant failed for block `{0}`")]
     SerializationInvariantError(Id<Block>),
+    #[error("Transaction `{0}` is locked until `{1}`")]
+    LockTimeNotReached(Id<Transaction>, u32),
+    #[error("Undo data for block `{0}` not found")]
+    MissingBlockUndo(Id<Block>),
+    #[error("Undo data does not match the inputs of block `{0}`")]
+    BlockUndoMismatch(Id<Block>),
+    #[error("UTXO set error: {0}")]
+    UtxoError(#[from] utxo::Error),
 }
 
 impl From<chainstate_storage::Error> for StateUpdateError {
@@ -73,22 +62,6 @@ impl From<chainstate_storage::Error> for StateUpdateError {
     }
 }
 
-impl From<SpendError> for StateUpdateError {
-    fn from(err: SpendError) -> Self {
-        match err {
-            SpendError::AlreadySpent(spender) => StateUpdateError::DoubleSpendAttempt(spender),
-            SpendError::AlreadyUnspent => StateUpdateError::InvariantBrokenAlreadyUnspent,
-            SpendError::OutOfRange {
-                tx_id,
-                source_output_index,
-            } => StateUpdateError::OutputIndexOutOfRange {
-                tx_id,
-                source_output_index,
-            },
-        }
-    }
-}
-
 impl From<TxMainChainIndexError> for StateUpdateError {
     fn from(err: TxMainChainIndexError) -> Self {
         match err {
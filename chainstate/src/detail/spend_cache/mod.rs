@@ -15,26 +15,33 @@
 //
 // Author(s): S. Afach
 
-use chainstate_storage::{BlockchainStorageRead, BlockchainStorageWrite};
+use std::collections::BTreeMap;
+
+use chainstate_storage::{BlockchainStorageWrite, UtxoDBImpl};
 use common::amount_sum;
 use common::chain::signature::{verify_signature, Transactable};
 use common::chain::Transaction;
 use common::{
     chain::{
-        block::Block, calculate_tx_index_from_block, OutPoint, OutPointSourceId, SpendablePosition,
-        Spender, TxInput, TxMainChainIndex, TxOutput,
+        block::{timestamp::BlockTimestamp, Block},
+        Destination, OutPoint, OutPointSourceId, TxInput, TxOutput,
     },
     primitives::{Amount, BlockDistance, BlockHeight, Id, Idable},
 };
-use std::collections::{btree_map::Entry, BTreeMap};
-
-mod cached_operation;
-use cached_operation::CachedInputsOperation;
+use rayon::prelude::*;
+use utxo::{
+    utxo_storage::UtxoDB, BlockUndo, ConsumedUtxoCache, FlushableUtxoView, TxUndo, Utxo,
+    UtxosCache, UtxosView,
+};
 
 use self::error::StateUpdateError;
 
 pub mod error;
 
+/// Lock time values below this threshold are interpreted as block heights, the values at or above
+/// it as timestamps in seconds since the epoch
+pub const LOCK_TIME_THRESHOLD: u32 = 500_000_000;
+
 /// A BlockTransactableRef is a reference to an operation in a block that causes inputs to be spent, outputs to be created, or both
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
 pub enum BlockTransactableRef<'a> {
@@ -42,87 +49,97 @@ pub enum BlockTransactableRef<'a> {
     BlockReward(&'a Block),
 }
 
+// An input to be checked: the transaction, its id, the input index and the destination of the
+// spent output
+type SignedInput<'a, T> = (
+    &'a T,
+    OutPointSourceId,
+    usize,
+    Result<Destination, StateUpdateError>,
+);
+
 pub struct ConsumedCachedInputs {
-    data: BTreeMap<OutPointSourceId, CachedInputsOperation>,
+    utxos: ConsumedUtxoCache,
 }
 
-pub struct CachedInputs<'a, S> {
-    db_tx: &'a S,
-    inputs: BTreeMap<OutPointSourceId, CachedInputsOperation>,
+/// Changes to the UTXO set made by connecting or disconnecting a block, on top of a view of the
+/// UTXO set as it was before the block
+pub struct CachedInputs<'a> {
+    utxo_cache: UtxosCache<'a>,
+    // the outputs spent by the block reward and by the transactions connected so far
+    reward_undo: Option<TxUndo>,
+    tx_undos: Vec<TxUndo>,
+    // the fees have to be collected while spending since the spent outputs leave the cache
+    total_fees: Option<Amount>,
 }
 
-impl<'a, S> CachedInputs<'a, S> {
-    pub fn new(db_tx: &'a S) -> Self {
+impl<'a> CachedInputs<'a> {
+    pub fn new(utxo_view: &'a dyn UtxosView) -> Self {
         Self {
-            db_tx,
-            inputs: BTreeMap::new(),
+            utxo_cache: UtxosCache::new(utxo_view),
+            reward_undo: None,
+            tx_undos: Vec::new(),
+            total_fees: Some(Amount::from_atoms(0)),
         }
     }
-}
 
-impl<'a, S: BlockchainStorageRead> CachedInputs<'a, S> {
-    fn outpoint_source_id_from_spend_ref(
+    fn outputs_from_spend_ref(
         spend_ref: BlockTransactableRef,
-    ) -> Result<OutPointSourceId, StateUpdateError> {
-        let outpoint_source_id = match spend_ref {
+    ) -> Result<(OutPointSourceId, Vec<TxOutput>), StateUpdateError> {
+        match spend_ref {
             BlockTransactableRef::Transaction(block, tx_num) => {
                 let tx = block.transactions().get(tx_num).ok_or_else(|| {
                     StateUpdateError::InvariantErrorTxNumWrongInBlock(tx_num, block.get_id())
                 })?;
-                let tx_id = tx.get_id();
-                OutPointSourceId::from(tx_id)
-            }
-            BlockTransactableRef::BlockReward(block) => OutPointSourceId::from(block.get_id()),
-        };
-        Ok(outpoint_source_id)
-    }
-
-    fn add_outputs(&mut self, spend_ref: BlockTransactableRef) -> Result<(), StateUpdateError> {
-        let tx_index = match spend_ref {
-            BlockTransactableRef::Transaction(block, tx_num) => {
-                CachedInputsOperation::Write(calculate_tx_index_from_block(block, tx_num)?)
+                Ok((tx.get_id().into(), tx.outputs().clone()))
             }
             BlockTransactableRef::BlockReward(block) => {
-                match block.header().block_reward_transactable().outputs() {
-                    Some(outputs) => CachedInputsOperation::Write(TxMainChainIndex::new(
-                        block.get_id().into(),
-                        outputs
-                            .len()
-                            .try_into()
-                            .map_err(|_| StateUpdateError::InvalidOutputCount)?,
-                    )?),
-                    None => return Ok(()), // no outputs to add
-                }
+                let reward_transactable = block.header().block_reward_transactable();
+                let outputs = reward_transactable.outputs().unwrap_or(&[]).to_vec();
+                Ok((block.get_id().into(), outputs))
             }
-        };
-
-        let outpoint_source_id = Self::outpoint_source_id_from_spend_ref(spend_ref)?;
+        }
+    }
 
-        match self.inputs.entry(outpoint_source_id) {
-            Entry::Occupied(_) => return Err(StateUpdateError::OutputAlreadyPresentInInputsCache),
-            Entry::Vacant(entry) => entry.insert(tx_index),
-        };
+    fn add_outputs(
+        &mut self,
+        spend_ref: BlockTransactableRef,
+        height: BlockHeight,
+    ) -> Result<(), StateUpdateError> {
+        let is_block_reward = matches!(spend_ref, BlockTransactableRef::BlockReward(_));
+        let (outpoint_source_id, outputs) = Self::outputs_from_spend_ref(spend_ref)?;
+
+        for (output_index, output) in outputs.into_iter().enumerate() {
+            let outpoint = OutPoint::new(outpoint_source_id.clone(), output_index as u32);
+            let utxo = Utxo::new(output, is_block_reward, height);
+            self.utxo_cache
+                .add_utxo(utxo, &outpoint, false)
+                .map_err(|_| StateUpdateError::OutputAlreadyPresentInInputsCache)?;
+        }
         Ok(())
     }
 
     fn remove_outputs(&mut self, spend_ref: BlockTransactableRef) -> Result<(), StateUpdateError> {
-        let tx_index = CachedInputsOperation::Erase;
-        let outpoint_source_id = Self::outpoint_source_id_from_spend_ref(spend_ref)?;
+        let (outpoint_source_id, outputs) = Self::outputs_from_spend_ref(spend_ref)?;
 
-        self.inputs.insert(outpoint_source_id, tx_index);
+        for output_index in 0..outputs.len() {
+            let outpoint = OutPoint::new(outpoint_source_id.clone(), output_index as u32);
+            self.utxo_cache
+                .spend_utxo(&outpoint)
+                .map_err(|_| StateUpdateError::MissingOutputOrSpentOnDisconnect)?;
+        }
         Ok(())
     }
 
     fn check_blockreward_maturity(
-        &self,
-        spending_block_id: &Id<Block>,
+        utxo: &Utxo,
         spend_height: &BlockHeight,
         blockreward_maturity: &BlockDistance,
     ) -> Result<(), StateUpdateError> {
-        let source_block_index = self.db_tx.get_block_index(spending_block_id)?;
-        let source_block_index =
-            source_block_index.ok_or(StateUpdateError::InvariantBrokenSourceBlockIndexNotFound)?;
-        let source_height = source_block_index.block_height();
+        if !utxo.is_block_reward() {
+            return Ok(());
+        }
+        let source_height = utxo.source_height().blockchain_height()?;
         let actual_distance =
             (*spend_height - source_height).ok_or(StateUpdateError::BlockHeightArithmeticError)?;
         if actual_distance < *blockreward_maturity {
@@ -131,107 +148,34 @@ impl<'a, S: BlockchainStorageRead> CachedInputs<'a, S> {
         Ok(())
     }
 
-    fn get_from_cached_mut(
-        &mut self,
-        outpoint: &OutPoint,
-    ) -> Result<&mut CachedInputsOperation, StateUpdateError> {
-        let result = match self.inputs.get_mut(&outpoint.tx_id()) {
-            Some(tx_index) => tx_index,
-            None => return Err(StateUpdateError::PreviouslyCachedInputNotFound),
-        };
-        Ok(result)
-    }
-
-    fn get_from_cached(
-        &self,
-        outpoint: &OutPoint,
-    ) -> Result<&CachedInputsOperation, StateUpdateError> {
-        let result = match self.inputs.get(&outpoint.tx_id()) {
-            Some(tx_index) => tx_index,
-            None => return Err(StateUpdateError::PreviouslyCachedInputNotFound),
-        };
-        Ok(result)
-    }
-
-    fn fetch_and_cache(&mut self, outpoint: &OutPoint) -> Result<(), StateUpdateError> {
-        let _tx_index_op = match self.inputs.entry(outpoint.tx_id()) {
-            Entry::Occupied(entry) => {
-                // If tx index was loaded
-                entry.into_mut()
-            }
-            Entry::Vacant(entry) => {
-                // Maybe the utxo is in a previous block?
-                let tx_index = self
-                    .db_tx
-                    .get_mainchain_tx_index(&outpoint.tx_id())?
-                    .ok_or(StateUpdateError::MissingOutputOrSpent)?;
-                entry.insert(CachedInputsOperation::Read(tx_index))
-            }
+    fn check_lock_time(
+        tx: &Transaction,
+        spend_height: &BlockHeight,
+        median_time_past: &BlockTimestamp,
+    ) -> Result<(), StateUpdateError> {
+        // A transaction is final once the lock time is strictly below the height or time of the
+        // block spending it. Zero lock time is therefore always final.
+        let lock_time = tx.lock_time();
+        let is_final = if lock_time < LOCK_TIME_THRESHOLD {
+            BlockHeight::new(lock_time.into()) < *spend_height
+        } else {
+            lock_time < median_time_past.as_int_seconds()
         };
+        if !is_final {
+            return Err(StateUpdateError::LockTimeNotReached(tx.get_id(), lock_time));
+        }
         Ok(())
     }
 
-    fn get_output_amount(
-        outputs: &[TxOutput],
-        output_index: usize,
-        spender_id: Spender,
-    ) -> Result<Amount, StateUpdateError> {
-        let output = outputs.get(output_index).ok_or(StateUpdateError::OutputIndexOutOfRange {
-            tx_id: Some(spender_id),
-            source_output_index: output_index,
-        })?;
-        Ok(output.value())
+    fn get_utxo(&self, outpoint: &OutPoint) -> Result<Utxo, StateUpdateError> {
+        self.utxo_cache.get_utxo(outpoint).ok_or(StateUpdateError::MissingOutputOrSpent)
     }
 
     fn calculate_total_inputs(&self, inputs: &[TxInput]) -> Result<Amount, StateUpdateError> {
-        let mut total = Amount::from_atoms(0);
-        for (_input_idx, input) in inputs.iter().enumerate() {
-            let outpoint = input.outpoint();
-            let tx_index = match self.inputs.get(&outpoint.tx_id()) {
-                Some(tx_index_op) => match tx_index_op {
-                    CachedInputsOperation::Write(tx_index) => tx_index,
-                    CachedInputsOperation::Read(tx_index) => tx_index,
-                    CachedInputsOperation::Erase => {
-                        return Err(StateUpdateError::PreviouslyCachedInputWasErased)
-                    }
-                },
-                None => return Err(StateUpdateError::PreviouslyCachedInputNotFound),
-            };
-            let output_index = outpoint.output_index() as usize;
-
-            let output_amount = match tx_index.position() {
-                common::chain::SpendablePosition::Transaction(tx_pos) => {
-                    let tx = self
-                        .db_tx
-                        .get_mainchain_tx_by_position(tx_pos)
-                        .map_err(StateUpdateError::from)?
-                        .ok_or_else(|| {
-                            StateUpdateError::InvariantErrorTransactionCouldNotBeLoaded(
-                                tx_pos.clone(),
-                            )
-                        })?;
-
-                    Self::get_output_amount(tx.outputs(), output_index, tx.get_id().into())?
-                }
-                common::chain::SpendablePosition::BlockReward(block_id) => {
-                    let block_index = self
-                        .db_tx
-                        .get_block_index(block_id)
-                        .map_err(StateUpdateError::from)?
-                        .ok_or_else(|| {
-                            StateUpdateError::InvariantErrorHeaderCouldNotBeLoaded(block_id.clone())
-                        })?;
-
-                    let rewards_tx = block_index.block_header().block_reward_transactable();
-
-                    let outputs = rewards_tx.outputs().unwrap_or(&[]);
-
-                    Self::get_output_amount(outputs, output_index, block_id.clone().into())?
-                }
-            };
-            total = (total + output_amount).ok_or(StateUpdateError::InputAdditionError)?;
-        }
-        Ok(total)
+        inputs.iter().try_fold(Amount::from_atoms(0), |total, input| {
+            let utxo = self.get_utxo(input.outpoint())?;
+            (total + utxo.output().value()).ok_or(StateUpdateError::InputAdditionError)
+        })
     }
 
     fn check_transferred_amounts_and_get_fee(
@@ -265,35 +209,31 @@ impl<'a, S: BlockchainStorageRead> CachedInputs<'a, S> {
             .ok_or(StateUpdateError::OutputAdditionError)
     }
 
-    fn calculate_block_total_fees(&self, block: &Block) -> Result<Amount, StateUpdateError> {
-        let total_fees = block
-            .transactions()
-            .iter()
-            .try_fold(Amount::from_atoms(0), |init, tx| {
-                init + self.check_transferred_amounts_and_get_fee(tx).ok()?
-            })
-            .ok_or_else(|| StateUpdateError::FailedToAddAllFeesOfBlock(block.get_id()))?;
-        Ok(total_fees)
-    }
-
+    /// Checks the block reward against the subsidy and the fees of the transactions spent so far;
+    /// to be called after the reward and all the transactions of the block have been spent
     pub fn check_block_reward(
         &self,
         block: &Block,
         block_subsidy_at_height: Amount,
     ) -> Result<(), StateUpdateError> {
-        let total_fees = self.calculate_block_total_fees(block)?;
+        let total_fees = self
+            .total_fees
+            .ok_or_else(|| StateUpdateError::FailedToAddAllFeesOfBlock(block.get_id()))?;
 
         let block_reward_transactable = block.header().block_reward_transactable();
 
-        let inputs = block_reward_transactable.inputs();
-        let outputs = block_reward_transactable.outputs();
-
-        let inputs_total = inputs.map_or_else(
-            || Ok(Amount::from_atoms(0)),
-            |ins| self.calculate_total_inputs(ins),
-        )?;
-        let outputs_total =
-            outputs.map_or_else(|| Ok(Amount::from_atoms(0)), Self::calculate_total_outputs)?;
+        // the reward inputs are already spent, their values are taken from the undo data
+        let inputs_total = self
+            .reward_undo
+            .iter()
+            .flat_map(|undo| undo.inner())
+            .try_fold(Amount::from_atoms(0), |total, utxo| {
+                total + utxo.output().value()
+            })
+            .ok_or(StateUpdateError::InputAdditionError)?;
+        let outputs_total = block_reward_transactable
+            .outputs()
+            .map_or_else(|| Ok(Amount::from_atoms(0)), Self::calculate_total_outputs)?;
 
         let max_allowed_outputs_total =
             amount_sum!(inputs_total, block_subsidy_at_height, total_fees)
@@ -308,74 +248,70 @@ impl<'a, S: BlockchainStorageRead> CachedInputs<'a, S> {
         Ok(())
     }
 
-    fn verify_signatures<T: Transactable>(&self, tx: &T) -> Result<(), StateUpdateError> {
-        let inputs = match tx.inputs() {
-            Some(ins) => ins,
-            None => return Ok(()),
-        };
+    fn get_destination(&self, outpoint: &OutPoint) -> Result<Destination, StateUpdateError> {
+        self.get_utxo(outpoint)
+            .map(|utxo| utxo.output().purpose().destination().clone())
+    }
 
-        for (input_idx, input) in inputs.iter().enumerate() {
-            let outpoint = input.outpoint();
-            let prev_tx_index_op = self.get_from_cached(outpoint)?;
-
-            let tx_index = prev_tx_index_op
-                .get_tx_index()
-                .ok_or(StateUpdateError::PreviouslyCachedInputNotFound)?;
-
-            match tx_index.position() {
-                SpendablePosition::Transaction(tx_pos) => {
-                    let prev_tx = self
-                        .db_tx
-                        .get_mainchain_tx_by_position(tx_pos)
-                        .map_err(StateUpdateError::from)?
-                        .ok_or_else(|| {
-                            StateUpdateError::InvariantErrorTransactionCouldNotBeLoaded(
-                                tx_pos.clone(),
-                            )
-                        })?;
-
-                    let output = prev_tx
-                        .outputs()
-                        .get(input.outpoint().output_index() as usize)
-                        .ok_or(StateUpdateError::OutputIndexOutOfRange {
-                            tx_id: None,
-                            source_output_index: outpoint.output_index() as usize,
-                        })?;
-
-                    // TODO: see if a different treatment should be done for different output purposes
-
-                    verify_signature(output.purpose().destination(), tx, input_idx)
-                        .map_err(|_| StateUpdateError::SignatureVerificationFailed)?;
-                }
-                SpendablePosition::BlockReward(block_id) => {
-                    let block_index = self
-                        .db_tx
-                        .get_block_index(block_id)
-                        .map_err(StateUpdateError::from)?
-                        .ok_or_else(|| {
-                            StateUpdateError::InvariantErrorHeaderCouldNotBeLoaded(block_id.clone())
-                        })?;
-
-                    let reward_tx = block_index.block_header().block_reward_transactable();
-
-                    let output = reward_tx
-                        .outputs()
-                        .unwrap_or(&[])
-                        .get(input.outpoint().output_index() as usize)
-                        .ok_or(StateUpdateError::OutputIndexOutOfRange {
-                            tx_id: None,
-                            source_output_index: outpoint.output_index() as usize,
-                        })?;
-
-                    // TODO: see if a different treatment should be done for different output purposes
-
-                    verify_signature(output.purpose().destination(), tx, input_idx)
-                        .map_err(|_| StateUpdateError::SignatureVerificationFailed)?;
-                }
-            };
-        }
+    // The signatures are checked on the worker pool. The spent outputs are looked up beforehand,
+    // since the cache is not shared with the workers. The error for the first failing input is
+    // returned, the same as if the inputs were checked one after another.
+    fn verify_signatures<T: Transactable + Sync>(
+        signed_inputs: Vec<SignedInput<T>>,
+    ) -> Result<(), StateUpdateError> {
+        signed_inputs
+            .into_par_iter()
+            .map(|(tx, source_id, input_idx, destination)| {
+                // TODO: see if a different treatment should be done for different output purposes
+
+                verify_signature(&destination?, tx, input_idx).map_err(|_| {
+                    StateUpdateError::SignatureVerificationFailed(source_id, input_idx)
+                })
+            })
+            .find_first(|result| result.is_err())
+            .unwrap_or(Ok(()))
+    }
 
-        Ok(())
+    // Checks the signatures of the inputs of a single transactable, spending outputs in the cache
+    fn verify_input_signatures<T: Transactable + Sync>(
+        &self,
+        tx: &T,
+        source_id: OutPointSourceId,
+    ) -> Result<(), StateUpdateError> {
+        let signed_inputs = tx
+            .inputs()
+            .unwrap_or(&[])
+            .iter()
+            .enumerate()
+            .map(|(input_idx, input)| {
+                let destination = self.get_destination(input.outpoint());
+                (tx, source_id.clone(), input_idx, destination)
+            })
+            .collect();
+        Self::verify_signatures(signed_inputs)
+    }
+
+    /// Checks the input signatures of all the transactions of the block in one pass, before any
+    /// of them is spent. Inputs spending the outputs of earlier transactions in the same block
+    /// are resolved from those transactions.
+    pub fn verify_transaction_signatures(&self, block: &Block) -> Result<(), StateUpdateError> {
+        let mut block_outputs: BTreeMap<OutPoint, &Destination> = BTreeMap::new();
+        let mut signed_inputs = Vec::new();
+        for tx in block.transactions() {
+            let source_id = OutPointSourceId::from(tx.get_id());
+            for (input_idx, input) in tx.inputs().iter().enumerate() {
+                let destination = match block_outputs.get(input.outpoint()) {
+                    Some(&destination) => Ok(destination.clone()),
+                    None => self.get_destination(input.outpoint()),
+                };
+                signed_inputs.push((tx, source_id.clone(), input_idx, destination));
+            }
+            for (output_idx, output) in tx.outputs().iter().enumerate() {
+                let outpoint = OutPoint::new(source_id.clone(), output_idx as u32);
+                block_outputs.insert(outpoint, output.purpose().destination());
+            }
+        }
+        Self::verify_signatures(signed_inputs)
     }
 
     fn apply_spend(
@@ -383,32 +319,27 @@ impl<'a, S: BlockchainStorageRead> CachedInputs<'a, S> {
         inputs: &[TxInput],
         spend_height: &BlockHeight,
         blockreward_maturity: &BlockDistance,
-        spender: Spender,
-    ) -> Result<(), StateUpdateError> {
+    ) -> Result<TxUndo, StateUpdateError> {
+        let mut tx_undo = TxUndo::new_empty();
         for input in inputs {
-            let outpoint = input.outpoint();
-
-            match outpoint.tx_id() {
-                OutPointSourceId::Transaction(_) => {}
-                OutPointSourceId::BlockReward(block_id) => {
-                    self.check_blockreward_maturity(&block_id, spend_height, blockreward_maturity)?;
-                }
-            }
+            let utxo = self
+                .utxo_cache
+                .spend_utxo(input.outpoint())
+                .map_err(|_| StateUpdateError::MissingOutputOrSpent)?;
 
-            let prev_tx_index_op = self.get_from_cached_mut(outpoint)?;
+            Self::check_blockreward_maturity(&utxo, spend_height, blockreward_maturity)?;
 
-            prev_tx_index_op
-                .spend(outpoint.output_index(), spender.clone())
-                .map_err(StateUpdateError::from)?;
+            tx_undo.push(utxo);
         }
 
-        Ok(())
+        Ok(tx_undo)
     }
 
     pub fn spend(
         &mut self,
         spend_ref: BlockTransactableRef,
         spend_height: &BlockHeight,
+        median_time_past: &BlockTimestamp,
         blockreward_maturity: &BlockDistance,
     ) -> Result<(), StateUpdateError> {
         match spend_ref {
@@ -417,119 +348,113 @@ impl<'a, S: BlockchainStorageRead> CachedInputs<'a, S> {
                     StateUpdateError::TxNumWrongInBlockOnConnect(tx_num, block.get_id())
                 })?;
 
-                // pre-cache all inputs
-                self.precache_inputs(tx.inputs())?;
+                // check that the transaction is not time-locked
+                Self::check_lock_time(tx, spend_height, median_time_past)?;
 
                 // check for attempted money printing
-                self.check_transferred_amounts_and_get_fee(tx)?;
+                let fee = self.check_transferred_amounts_and_get_fee(tx)?;
+                self.total_fees = self.total_fees.and_then(|total| total + fee);
 
-                // verify input signatures
-                self.verify_signatures(tx)?;
+                // the input signatures are checked for the whole block beforehand
 
                 // spend inputs of this transaction
-                let spender = tx.get_id().into();
-                self.apply_spend(tx.inputs(), spend_height, blockreward_maturity, spender)?;
+                let tx_undo = self.apply_spend(tx.inputs(), spend_height, blockreward_maturity)?;
+                self.tx_undos.push(tx_undo);
             }
             BlockTransactableRef::BlockReward(block) => {
                 let reward_transactable = block.header().block_reward_transactable();
                 let inputs = reward_transactable.inputs();
                 // TODO: test spending block rewards from chains outside the mainchain
-                match inputs {
-                    Some(ins) => {
-                        // pre-cache all inputs
-                        self.precache_inputs(ins)?;
-
-                        // verify input signatures
-                        self.verify_signatures(&reward_transactable)?;
-
-                        let spender = block.get_id().into();
-                        self.apply_spend(ins, spend_height, blockreward_maturity, spender)?;
-                    }
-                    None => (),
+                if let Some(ins) = inputs {
+                    // verify input signatures
+                    self.verify_input_signatures(&reward_transactable, block.get_id().into())?;
+
+                    let reward_undo = self.apply_spend(ins, spend_height, blockreward_maturity)?;
+                    self.reward_undo = Some(reward_undo);
                 }
             }
         }
         // add the outputs to the cache
-        self.add_outputs(spend_ref)?;
+        self.add_outputs(spend_ref, *spend_height)?;
 
         Ok(())
     }
 
-    pub fn unspend(&mut self, spend_ref: BlockTransactableRef) -> Result<(), StateUpdateError> {
-        // Delete TxMainChainIndex for the current tx
+    /// Reverts `spend`, restoring the spent outputs from the undo data saved when the block was
+    /// connected
+    pub fn unspend(
+        &mut self,
+        spend_ref: BlockTransactableRef,
+        undo: &TxUndo,
+    ) -> Result<(), StateUpdateError> {
+        // remove the outputs created by the current tx
         self.remove_outputs(spend_ref)?;
 
-        match spend_ref {
-            BlockTransactableRef::Transaction(block, tx_num) => {
-                let tx = block.transactions().get(tx_num).ok_or_else(|| {
+        let block = match spend_ref {
+            BlockTransactableRef::Transaction(block, _)
+            | BlockTransactableRef::BlockReward(block) => block,
+        };
+        let reward_transactable = block.header().block_reward_transactable();
+        let inputs: &[TxInput] = match spend_ref {
+            BlockTransactableRef::Transaction(block, tx_num) => block
+                .transactions()
+                .get(tx_num)
+                .ok_or_else(|| {
                     StateUpdateError::TxNumWrongInBlockOnDisconnect(tx_num, block.get_id())
-                })?;
+                })?
+                .inputs(),
+            BlockTransactableRef::BlockReward(_) => reward_transactable.inputs().unwrap_or(&[]),
+        };
 
-                // pre-cache all inputs
-                self.precache_inputs(tx.inputs())?;
+        if inputs.len() != undo.inner().len() {
+            return Err(StateUpdateError::BlockUndoMismatch(block.get_id()));
+        }
 
-                // unspend inputs
-                for input in tx.inputs() {
-                    let outpoint = input.outpoint();
+        // put the spent outputs back
+        for (input, utxo) in inputs.iter().zip(undo.inner()) {
+            self.utxo_cache
+                .add_utxo(utxo.clone(), input.outpoint(), false)
+                .map_err(|_| StateUpdateError::InvariantBrokenAlreadyUnspent)?;
+        }
 
-                    let input_tx_id_op = self.get_from_cached_mut(outpoint)?;
+        Ok(())
+    }
 
-                    // Mark input as unspend
-                    input_tx_id_op
-                        .unspend(outpoint.output_index())
-                        .map_err(StateUpdateError::from)?;
-                }
-            }
-            BlockTransactableRef::BlockReward(block) => {
-                let reward_transactable = block.header().block_reward_transactable();
-                match reward_transactable.inputs() {
-                    Some(inputs) => {
-                        // pre-cache all inputs
-                        self.precache_inputs(inputs)?;
-
-                        // unspend inputs
-                        for input in inputs {
-                            let outpoint = input.outpoint();
-
-                            let input_tx_id_op = self.get_from_cached_mut(outpoint)?;
-
-                            // Mark input as unspend
-                            input_tx_id_op
-                                .unspend(outpoint.output_index())
-                                .map_err(StateUpdateError::from)?;
-                        }
-                    }
-                    None => (),
-                }
-            }
+    /// Adds the outputs of the genesis block, which has nothing to spend
+    pub fn add_genesis_outputs(&mut self, genesis: &Block) -> Result<(), StateUpdateError> {
+        let height = BlockHeight::new(0);
+        self.add_outputs(BlockTransactableRef::BlockReward(genesis), height)?;
+        for tx_num in 0..genesis.transactions().len() {
+            self.add_outputs(BlockTransactableRef::Transaction(genesis, tx_num), height)?;
         }
-
         Ok(())
     }
 
-    fn precache_inputs(&mut self, inputs: &[TxInput]) -> Result<(), StateUpdateError> {
-        inputs.iter().try_for_each(|input| self.fetch_and_cache(input.outpoint()))
+    /// Takes the undo data collected while spending the block at the given height
+    pub fn take_block_undo(&mut self, height: BlockHeight) -> BlockUndo {
+        BlockUndo::new(
+            self.reward_undo.take(),
+            std::mem::take(&mut self.tx_undos),
+            height,
+        )
     }
 
-    pub fn consume(self) -> Result<ConsumedCachedInputs, StateUpdateError> {
-        Ok(ConsumedCachedInputs { data: self.inputs })
+    pub fn consume(
+        mut self,
+        best_block: Id<Block>,
+    ) -> Result<ConsumedCachedInputs, StateUpdateError> {
+        self.utxo_cache.set_best_block(best_block);
+        Ok(ConsumedCachedInputs {
+            utxos: self.utxo_cache.consume()?,
+        })
     }
-}
 
-impl<'a, S: BlockchainStorageWrite> CachedInputs<'a, S> {
-    pub fn flush_to_storage(
+    pub fn flush_to_storage<S: BlockchainStorageWrite>(
         db_tx: &mut S,
         input_data: ConsumedCachedInputs,
     ) -> Result<(), StateUpdateError> {
-        for (tx_id, tx_index_op) in input_data.data {
-            match tx_index_op {
-                CachedInputsOperation::Write(ref tx_index) => {
-                    db_tx.set_mainchain_tx_index(&tx_id, tx_index)?
-                }
-                CachedInputsOperation::Read(_) => (),
-                CachedInputsOperation::Erase => db_tx.del_mainchain_tx_index(&tx_id)?,
-            }
-        }
+        let mut store = UtxoDBImpl::new(db_tx);
+        UtxoDB::new(&mut store).batch_write(input_data.utxos)?;
         Ok(())
     }
 }
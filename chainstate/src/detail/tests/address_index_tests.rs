@@ -0,0 +1,156 @@
+// Copyright (c) 2022 RBB S.r.l
+// opensource@mintlayer.org
+// SPDX-License-Identifier: MIT
+// Licensed under the MIT License;
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// 	http://spdx.org/licenses/MIT
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::detail::tests::*;
+use chainstate_types::address_index::AddressHistoryEntry;
+use common::address::pubkeyhash::PublicKeyHash;
+use common::address::Address;
+use common::chain::signature::inputsig::StandardInputSignature;
+use common::chain::signature::sighashtype::SigHashType;
+use common::chain::OutPoint;
+use crypto::key::{KeyKind, PrivateKey};
+
+fn transfer(value: Amount, destination: Destination) -> TxOutput {
+    TxOutput::new(value, OutputPurpose::Transfer(destination))
+}
+
+// Spend an anyone-can-spend output of the first transaction of given block
+fn spend_anyonecanspend(prev_block: &Block, index: u32, outputs: Vec<TxOutput>) -> Block {
+    let prev_tx_id = prev_block.transactions()[0].get_id();
+    let input = TxInput::new(prev_tx_id.into(), index, empty_witness());
+    let tx = Transaction::new(0, vec![input], outputs, 0).expect(ERR_CREATE_TX_FAIL);
+    block_with_tx(prev_block, tx)
+}
+
+#[test]
+fn address_history_follows_mainchain() {
+    common::concurrency::model(|| {
+        let mut chainstate = ChainstateBuilder::new().with_address_index().build();
+        let genesis = chainstate.chain_config.genesis_block().clone();
+        let genesis_value = genesis.transactions()[0].outputs()[0].value();
+
+        let (private_key, public_key) = PrivateKey::new(KeyKind::RistrettoSchnorr);
+        let pkh = PublicKeyHash::from(&public_key);
+        let address = Address::from_public_key_hash(&chainstate.chain_config, &pkh).unwrap();
+        let history = |chainstate: &Chainstate, skip, limit| {
+            chainstate.get_address_history(&address, skip, limit).unwrap()
+        };
+
+        // Pay to the address, both by its hash and by its public key
+        let value = Amount::from_atoms(1);
+        let change = (genesis_value - Amount::from_atoms(2)).unwrap();
+        let block_1 = spend_anyonecanspend(
+            &genesis,
+            0,
+            vec![
+                transfer(value, Destination::Address(pkh)),
+                transfer(value, Destination::PublicKey(public_key)),
+                transfer(change, anyonecanspend_address()),
+            ],
+        );
+        chainstate.process_block(block_1.clone(), BlockSource::Local).unwrap();
+        let tx_1 = &block_1.transactions()[0];
+        let paid = |index: u32, spent_by: Option<OutPointSourceId>| {
+            AddressHistoryEntry::new(
+                OutPoint::new(tx_1.get_id().into(), index),
+                tx_1.outputs()[index as usize].clone(),
+                BlockHeight::new(1),
+                spent_by,
+            )
+        };
+        assert_eq!(
+            history(&chainstate, 0, 10),
+            vec![paid(0, None), paid(1, None)]
+        );
+        assert_eq!(history(&chainstate, 1, 10), vec![paid(1, None)]);
+        assert_eq!(history(&chainstate, 0, 1), vec![paid(0, None)]);
+
+        // Spend the output paid to the address hash
+        let destination = Destination::Address(pkh);
+        let unsigned_input = TxInput::new(tx_1.get_id().into(), 0, empty_witness());
+        let outputs = vec![transfer(value, anyonecanspend_address())];
+        let unsigned_tx = Transaction::new(0, vec![unsigned_input], outputs.clone(), 0).unwrap();
+        let signature = StandardInputSignature::produce_signature_for_input(
+            &private_key,
+            SigHashType::default(),
+            destination,
+            &unsigned_tx,
+            0,
+        )
+        .unwrap();
+        let input = TxInput::new(tx_1.get_id().into(), 0, InputWitness::Standard(signature));
+        let tx_2 = Transaction::new(0, vec![input], outputs, 0).unwrap();
+        let block_2 = block_with_tx(&block_1, tx_2.clone());
+        chainstate.process_block(block_2, BlockSource::Local).unwrap();
+        assert_eq!(
+            history(&chainstate, 0, 10),
+            vec![paid(0, Some(tx_2.get_id().into())), paid(1, None)]
+        );
+
+        // Reorg away from the spending block
+        let block_2a =
+            spend_anyonecanspend(&block_1, 2, vec![transfer(value, anyonecanspend_address())]);
+        chainstate.process_block(block_2a.clone(), BlockSource::Local).unwrap();
+        let block_3a = spend_anyonecanspend(
+            &block_2a,
+            0,
+            vec![transfer(value, anyonecanspend_address())],
+        );
+        chainstate.process_block(block_3a.clone(), BlockSource::Local).unwrap();
+        assert_eq!(
+            chainstate.get_best_block_id().unwrap(),
+            Some(block_3a.get_id())
+        );
+        assert_eq!(
+            history(&chainstate, 0, 10),
+            vec![paid(0, None), paid(1, None)]
+        );
+
+        // Reorg away from the paying block
+        let mut prev_block = genesis;
+        for _ in 0..4 {
+            let block = produce_test_block(&prev_block, false);
+            chainstate.process_block(block.clone(), BlockSource::Local).unwrap();
+            prev_block = block;
+        }
+        assert_eq!(
+            chainstate.get_best_block_id().unwrap(),
+            Some(prev_block.get_id())
+        );
+        assert_eq!(history(&chainstate, 0, 10), vec![]);
+    });
+}
+
+#[test]
+fn address_history_errors() {
+    common::concurrency::model(|| {
+        let (_, public_key) = PrivateKey::new(KeyKind::RistrettoSchnorr);
+
+        let chainstate = setup_chainstate();
+        let address = Address::from_public_key(&chainstate.chain_config, &public_key).unwrap();
+        assert_eq!(
+            chainstate.get_address_history(&address, 0, 10),
+            Err(PropertyQueryError::AddressIndexDisabled)
+        );
+
+        let chainstate = ChainstateBuilder::new().with_address_index().build();
+        let regtest_config = create_regtest();
+        let address = Address::from_public_key(&regtest_config, &public_key).unwrap();
+        assert_eq!(
+            chainstate.get_address_history(&address, 0, 10),
+            Err(PropertyQueryError::InvalidAddress(address.get().to_owned()))
+        );
+    });
+}
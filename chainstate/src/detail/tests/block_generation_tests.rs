@@ -0,0 +1,153 @@
+// Copyright (c) 2022 RBB S.r.l
+// opensource@mintlayer.org
+// SPDX-License-Identifier: MIT
+// Licensed under the MIT License;
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// 	http://spdx.org/licenses/MIT
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::detail::pow::work::mine;
+use crate::detail::tests::*;
+use common::address::pubkeyhash::PublicKeyHash;
+use common::address::Address;
+use common::chain::config::{create_testnet, Builder, ChainType};
+use common::chain::signature::Transactable;
+use common::primitives::Compact;
+use crypto::key::{KeyKind, PrivateKey};
+
+#[test]
+fn generate_blocks_on_regtest() {
+    common::concurrency::model(|| {
+        let mut chainstate = ChainstateBuilder::new().with_config(create_regtest()).build();
+        let (_, public_key) = PrivateKey::new(KeyKind::RistrettoSchnorr);
+        let pkh = PublicKeyHash::from(&public_key);
+        let address = Address::from_public_key_hash(&chainstate.chain_config, &pkh).unwrap();
+
+        for height in 1..=3 {
+            let height = BlockHeight::new(height);
+            let block = chainstate.generate_block(&address).unwrap();
+            assert_eq!(
+                chainstate.get_best_block_id().unwrap(),
+                Some(block.get_id())
+            );
+            assert_eq!(
+                chainstate.get_block_height_in_main_chain(&block.get_id()),
+                Ok(Some(height))
+            );
+
+            let expected_reward = TxOutput::new(
+                chainstate.chain_config.block_subsidy_at_height(&height),
+                OutputPurpose::Transfer(Destination::Address(pkh)),
+            );
+            let reward = block.header().block_reward_transactable();
+            assert_eq!(reward.outputs(), Some(&[expected_reward][..]));
+        }
+    });
+}
+
+#[test]
+fn mine_block_from_template() {
+    common::concurrency::model(|| {
+        let mut chainstate = ChainstateBuilder::new().with_config(create_regtest()).build();
+        let genesis_id = chainstate.chain_config.genesis_block_id();
+
+        let template = chainstate.get_block_template().unwrap();
+        let height = BlockHeight::new(1);
+        assert_eq!(template.prev_block_id, genesis_id);
+        assert_eq!(template.height, height);
+        assert!(template.min_timestamp <= template.timestamp);
+        assert_eq!(
+            template.bits,
+            Compact::from(chainstate.chain_config.get_proof_of_work_config().limit())
+        );
+        assert_eq!(
+            template.reward,
+            chainstate.chain_config.block_subsidy_at_height(&height)
+        );
+        assert_eq!(template.transactions, vec![]);
+
+        let mut block = Block::new(
+            template.transactions,
+            Some(template.prev_block_id),
+            template.timestamp,
+            ConsensusData::None,
+        )
+        .unwrap();
+        let reward = TxOutput::new(
+            template.reward,
+            OutputPurpose::Transfer(anyonecanspend_address()),
+        );
+        assert!(mine(&mut block, u128::MAX, template.bits, vec![reward]).unwrap());
+        chainstate.process_block(block.clone(), BlockSource::Local).unwrap();
+        assert_eq!(
+            chainstate.get_best_block_id().unwrap(),
+            Some(block.get_id())
+        );
+
+        // Paying more than the expected reward is not allowed
+        let template = chainstate.get_block_template().unwrap();
+        let mut block = Block::new(
+            template.transactions,
+            Some(template.prev_block_id),
+            template.timestamp,
+            ConsensusData::None,
+        )
+        .unwrap();
+        let reward = TxOutput::new(
+            (template.reward + Amount::from_atoms(1)).unwrap(),
+            OutputPurpose::Transfer(anyonecanspend_address()),
+        );
+        assert!(mine(&mut block, u128::MAX, template.bits, vec![reward]).unwrap());
+        assert!(matches!(
+            chainstate.process_block(block, BlockSource::Local),
+            Err(BlockError::StateUpdateFailed(_))
+        ));
+    });
+}
+
+#[test]
+fn generate_block_errors() {
+    common::concurrency::model(|| {
+        let (_, public_key) = PrivateKey::new(KeyKind::RistrettoSchnorr);
+
+        let mut chainstate = setup_chainstate();
+        let address = Address::from_public_key(&chainstate.chain_config, &public_key).unwrap();
+        assert_eq!(
+            chainstate.generate_block(&address),
+            Err(BlockGenerationError::NotAllowed(ChainType::Mainnet))
+        );
+
+        let config = create_testnet();
+        let address = Address::from_public_key(&config, &public_key).unwrap();
+        let mut chainstate = ChainstateBuilder::new().with_config(config).build();
+        assert_eq!(
+            chainstate.generate_block(&address),
+            Err(BlockGenerationError::NotAllowed(ChainType::Testnet))
+        );
+
+        // Signet does not use PoW unless configured to
+        let config = Builder::new(ChainType::Signet).build();
+        let address = Address::from_public_key(&config, &public_key).unwrap();
+        let mut chainstate = ChainstateBuilder::new().with_config(config).build();
+        assert_eq!(
+            chainstate.generate_block(&address),
+            Err(BlockGenerationError::NeedsPoW(BlockHeight::new(1)))
+        );
+
+        let mut chainstate = ChainstateBuilder::new().with_config(create_regtest()).build();
+        let address = Address::from_public_key(&create_unit_test_config(), &public_key).unwrap();
+        assert_eq!(
+            chainstate.generate_block(&address),
+            Err(BlockGenerationError::QueryError(
+                PropertyQueryError::InvalidAddress(address.get().to_owned())
+            ))
+        );
+    });
+}
@@ -0,0 +1,318 @@
+// Copyright (c) 2022 RBB S.r.l
+// opensource@mintlayer.org
+// SPDX-License-Identifier: MIT
+// Licensed under the MIT License;
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// 	http://spdx.org/licenses/MIT
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::detail::tests::test_framework::BlockTestFramework;
+use crate::detail::tests::*;
+use chainstate_storage::BlockchainStorageRead;
+use chainstate_types::block_index::BlockStatus;
+use serialization::Decode;
+
+// A block spending the same output twice, which fails the block checks
+fn block_with_duplicate_input(prev_block: &Block) -> Block {
+    let input = TxInput::new(
+        OutPointSourceId::Transaction(prev_block.transactions()[0].get_id()),
+        0,
+        empty_witness(),
+    );
+    let tx = Transaction::new(0, vec![input.clone(), input], vec![], 0).expect(ERR_CREATE_TX_FAIL);
+    Block::new(
+        vec![tx],
+        Some(prev_block.get_id()),
+        BlockTimestamp::from_duration_since_epoch(time::get()).unwrap(),
+        ConsensusData::None,
+    )
+    .expect(ERR_CREATE_BLOCK_FAIL)
+}
+
+// A copy of the block with the transactions replaced. The header and so the block id stay the same.
+fn with_transactions(block: &Block, transactions: Vec<Transaction>) -> Block {
+    let encoded = block.encode();
+    let header_end = encoded.len() - block.transactions().encode().len();
+    let mutated = [&encoded[..header_end], &transactions.encode()].concat();
+    Block::decode(&mut &mutated[..]).expect("a valid block encoding")
+}
+
+fn status(btf: &BlockTestFramework, block_id: &Id<Block>) -> BlockStatus {
+    btf.get_block_index(block_id).status()
+}
+
+#[test]
+fn valid_blocks() {
+    common::concurrency::model(|| {
+        let mut btf = BlockTestFramework::new();
+        let genesis_id = btf.genesis().get_id();
+        assert_eq!(status(&btf, &genesis_id), BlockStatus::FullyValid);
+
+        btf.create_chain(&genesis_id, 2).unwrap();
+        for block_index in &btf.block_indexes {
+            assert_eq!(
+                status(&btf, block_index.block_id()),
+                BlockStatus::FullyValid
+            );
+        }
+
+        // A block that does not extend the best chain is not connected
+        btf.create_chain(&genesis_id, 1).unwrap();
+        let side_block_id = btf.block_indexes.last().unwrap().block_id().clone();
+        assert_eq!(status(&btf, &side_block_id), BlockStatus::HeaderValid);
+        assert_eq!(
+            status(&btf, btf.block_indexes[1].block_id()),
+            BlockStatus::FullyValid
+        );
+    });
+}
+
+#[test]
+fn block_failing_checks_remembered() {
+    common::concurrency::model(|| {
+        let mut btf = BlockTestFramework::new();
+        let best_block_id = btf.chainstate.get_best_block_id().unwrap().unwrap();
+
+        let bad_block = block_with_duplicate_input(btf.genesis());
+        let bad_block_id = bad_block.get_id();
+        assert!(matches!(
+            btf.chainstate.process_block(bad_block.clone(), BlockSource::Peer),
+            Err(BlockError::CheckBlockFailed(_))
+        ));
+        assert_eq!(status(&btf, &bad_block_id), BlockStatus::Failed);
+        assert_eq!(btf.chainstate.get_block(bad_block_id.clone()), Ok(None));
+
+        // The block is rejected without being checked again
+        assert_eq!(
+            btf.chainstate.process_block(bad_block.clone(), BlockSource::Peer).unwrap_err(),
+            BlockError::BlockMarkedInvalid(bad_block_id.clone())
+        );
+
+        // So are its descendants
+        let child = produce_test_block(&bad_block, false);
+        let child_id = child.get_id();
+        assert_eq!(
+            btf.chainstate.process_block(child.clone(), BlockSource::Peer).unwrap_err(),
+            BlockError::InvalidAncestor(child_id.clone(), bad_block_id.clone())
+        );
+        assert_eq!(status(&btf, &child_id), BlockStatus::FailedAncestor);
+        assert_eq!(status(&btf, &bad_block_id), BlockStatus::Failed);
+        assert_eq!(
+            btf.chainstate.process_block(child, BlockSource::Peer).unwrap_err(),
+            BlockError::BlockMarkedInvalid(child_id)
+        );
+
+        assert_eq!(btf.chainstate.get_best_block_id(), Ok(Some(best_block_id)));
+    });
+}
+
+#[test]
+fn block_with_mutated_body_not_remembered() {
+    common::concurrency::model(|| {
+        let mut btf = BlockTestFramework::new();
+        let block = produce_test_block(btf.genesis(), false);
+        let block_id = block.get_id();
+
+        // A copy of the block altered in transit has the same id but does not match its header
+        let other_transactions = block_with_duplicate_input(btf.genesis()).transactions().clone();
+        let mutated_block = with_transactions(&block, other_transactions);
+        assert_eq!(mutated_block.get_id(), block_id);
+        assert_eq!(
+            btf.chainstate.process_block(mutated_block, BlockSource::Peer).unwrap_err(),
+            BlockError::CheckBlockFailed(CheckBlockError::MerkleRootMismatch)
+        );
+        assert!(btf.chainstate.chainstate_storage.get_block_index(&block_id).unwrap().is_none());
+
+        // The genuine block is still accepted
+        btf.chainstate.process_block(block, BlockSource::Peer).unwrap();
+        assert_eq!(
+            btf.chainstate.get_best_block_id(),
+            Ok(Some(block_id.clone()))
+        );
+        assert_eq!(status(&btf, &block_id), BlockStatus::FullyValid);
+    });
+}
+
+#[test]
+fn block_failing_to_connect_remembered() {
+    common::concurrency::model(|| {
+        let mut btf = BlockTestFramework::new();
+        let genesis_id = btf.genesis().get_id();
+
+        // Main chain of two blocks
+        btf.create_chain(&genesis_id, 2).unwrap();
+        let main_block_id = btf.block_indexes[1].block_id().clone();
+        let best_block_id = btf.block_indexes[2].block_id().clone();
+
+        // Side chain with a block spending an output that only exists in the main chain. It is
+        // not connected until the side chain gets longer.
+        btf.create_chain(&genesis_id, 1).unwrap();
+        let side_block_id = btf.block_indexes[3].block_id().clone();
+        let side_block = btf.get_block(side_block_id.clone()).unwrap().unwrap();
+        let bad_block = btf.random_block(
+            &side_block,
+            Some(&[TestBlockParams::SpendFrom(main_block_id)]),
+        );
+        let bad_block_id = bad_block.get_id();
+        btf.add_special_block(bad_block.clone()).unwrap();
+        assert_eq!(status(&btf, &bad_block_id), BlockStatus::HeaderValid);
+
+        // Extending the side chain triggers a reorg which fails
+        let child = produce_test_block(&bad_block, false);
+        let child_id = child.get_id();
+        assert!(matches!(
+            btf.chainstate.process_block(child.clone(), BlockSource::Peer),
+            Err(BlockError::StateUpdateFailed(_))
+        ));
+        assert_eq!(status(&btf, &side_block_id), BlockStatus::HeaderValid);
+        assert_eq!(status(&btf, &bad_block_id), BlockStatus::Failed);
+        assert_eq!(status(&btf, &child_id), BlockStatus::FailedAncestor);
+        assert_eq!(
+            btf.chainstate.get_best_block_id(),
+            Ok(Some(best_block_id.clone()))
+        );
+
+        // Further extensions are rejected without attempting a reorg
+        let grandchild = produce_test_block(&child, false);
+        assert_eq!(
+            btf.chainstate.process_block(grandchild.clone(), BlockSource::Peer).unwrap_err(),
+            BlockError::InvalidAncestor(grandchild.get_id(), child_id)
+        );
+        assert_eq!(
+            btf.chainstate.chainstate_storage.get_best_block_id(),
+            Ok(Some(best_block_id))
+        );
+
+        // The valid part of the side chain can still be extended
+        btf.create_chain(&side_block_id, 3).unwrap();
+        assert!(btf.is_block_in_main_chain(&side_block_id));
+    });
+}
+
+#[test]
+fn invalidate_and_reconsider_mainchain_block() {
+    common::concurrency::model(|| {
+        let mut btf = BlockTestFramework::new();
+        let genesis_id = btf.genesis().get_id();
+        btf.create_chain(&genesis_id, 3).unwrap();
+        let ids: Vec<_> = btf.block_indexes.iter().map(|bi| bi.block_id().clone()).collect();
+
+        let events: EventList = Arc::new(Mutex::new(Vec::new()));
+        let events_copy = Arc::clone(&events);
+        btf.chainstate.subscribe_to_events(Arc::new(move |event| {
+            if let ChainstateEvent::NewTip(block_id, block_height) = event {
+                events_copy.lock().unwrap().push((block_id, block_height));
+            }
+        }));
+
+        btf.chainstate.invalidate_block(&ids[2]).unwrap();
+        btf.chainstate.wait_for_all_events();
+        assert_eq!(btf.chainstate.get_best_block_id(), Ok(Some(ids[1].clone())));
+        assert!(!btf.is_block_in_main_chain(&ids[2]));
+        assert_eq!(status(&btf, &ids[2]), BlockStatus::Failed);
+        assert_eq!(status(&btf, &ids[3]), BlockStatus::FailedAncestor);
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec![(ids[1].clone(), BlockHeight::new(1))]
+        );
+
+        // The invalidated chain cannot be extended
+        let block3 = btf.get_block(ids[3].clone()).unwrap().unwrap();
+        let block4 = produce_test_block(&block3, false);
+        assert_eq!(
+            btf.chainstate.process_block(block4.clone(), BlockSource::Local).unwrap_err(),
+            BlockError::InvalidAncestor(block4.get_id(), ids[3].clone())
+        );
+
+        btf.chainstate.reconsider_block(&ids[2]).unwrap();
+        btf.chainstate.wait_for_all_events();
+        assert_eq!(btf.chainstate.get_best_block_id(), Ok(Some(ids[3].clone())));
+        for id in &ids {
+            assert_eq!(status(&btf, id), BlockStatus::FullyValid);
+        }
+        assert_eq!(
+            events.lock().unwrap().last(),
+            Some(&(ids[3].clone(), BlockHeight::new(3)))
+        );
+    });
+}
+
+#[test]
+fn invalidate_switches_to_side_chain() {
+    common::concurrency::model(|| {
+        let mut btf = BlockTestFramework::new();
+        let genesis_id = btf.genesis().get_id();
+        btf.create_chain(&genesis_id, 2).unwrap();
+        btf.create_chain(&genesis_id, 2).unwrap();
+        let ids: Vec<_> = btf.block_indexes.iter().map(|bi| bi.block_id().clone()).collect();
+        assert_eq!(btf.chainstate.get_best_block_id(), Ok(Some(ids[2].clone())));
+
+        btf.chainstate.invalidate_block(&ids[1]).unwrap();
+        assert_eq!(btf.chainstate.get_best_block_id(), Ok(Some(ids[4].clone())));
+        assert!(btf.is_block_in_main_chain(&ids[3]));
+        assert_eq!(status(&btf, &ids[4]), BlockStatus::FullyValid);
+
+        // The original chain has no more trust, so the best chain stays the same
+        btf.chainstate.reconsider_block(&ids[1]).unwrap();
+        assert_eq!(btf.chainstate.get_best_block_id(), Ok(Some(ids[4].clone())));
+        assert_eq!(status(&btf, &ids[1]), BlockStatus::HeaderValid);
+        assert_eq!(status(&btf, &ids[2]), BlockStatus::HeaderValid);
+    });
+}
+
+#[test]
+fn invalidate_skips_side_chain_failing_to_connect() {
+    common::concurrency::model(|| {
+        let mut btf = BlockTestFramework::new();
+        let genesis_id = btf.genesis().get_id();
+        btf.create_chain(&genesis_id, 2).unwrap();
+        let main_block_id = btf.block_indexes[1].block_id().clone();
+        let best_block_id = btf.block_indexes[2].block_id().clone();
+
+        // Side chain with a block spending an output that only exists in the main chain
+        btf.create_chain(&genesis_id, 1).unwrap();
+        let side_block_id = btf.block_indexes[3].block_id().clone();
+        let side_block = btf.get_block(side_block_id.clone()).unwrap().unwrap();
+        let bad_block = btf.random_block(
+            &side_block,
+            Some(&[TestBlockParams::SpendFrom(main_block_id.clone())]),
+        );
+        let bad_block_id = bad_block.get_id();
+        btf.add_special_block(bad_block).unwrap();
+
+        btf.chainstate.invalidate_block(&best_block_id).unwrap();
+        assert_eq!(btf.chainstate.get_best_block_id(), Ok(Some(main_block_id)));
+        assert_eq!(status(&btf, &bad_block_id), BlockStatus::Failed);
+        assert_eq!(status(&btf, &side_block_id), BlockStatus::HeaderValid);
+    });
+}
+
+#[test]
+fn invalidate_errors() {
+    common::concurrency::model(|| {
+        let mut btf = BlockTestFramework::new();
+        let genesis_id = btf.genesis().get_id();
+        assert_eq!(
+            btf.chainstate.invalidate_block(&genesis_id),
+            Err(BlockError::InvalidateGenesis)
+        );
+
+        let unknown_id = produce_test_block(btf.genesis(), false).get_id();
+        assert_eq!(
+            btf.chainstate.invalidate_block(&unknown_id),
+            Err(BlockError::BlockNotFound(unknown_id.clone()))
+        );
+        assert_eq!(
+            btf.chainstate.reconsider_block(&unknown_id),
+            Err(BlockError::BlockNotFound(unknown_id))
+        );
+        assert_eq!(btf.chainstate.get_best_block_id(), Ok(Some(genesis_id)));
+    });
+}
@@ -0,0 +1,140 @@
+// Copyright (c) 2022 RBB S.r.l
+// opensource@mintlayer.org
+// SPDX-License-Identifier: MIT
+// Licensed under the MIT License;
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// 	http://spdx.org/licenses/MIT
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::detail::tests::test_framework::BlockTestFramework;
+use crate::detail::tests::*;
+use crate::{ChainTip, ChainTipStatus};
+use common::primitives::BlockDistance;
+
+fn last_block_id(btf: &BlockTestFramework) -> Id<Block> {
+    btf.block_indexes.last().unwrap().block_id().clone()
+}
+
+// Chain tips sorted by block id, so they can be compared regardless of the storage order
+fn chain_tips(btf: &BlockTestFramework) -> Vec<ChainTip> {
+    sorted(btf.chainstate.get_chain_tips().unwrap())
+}
+
+fn sorted(mut tips: Vec<ChainTip>) -> Vec<ChainTip> {
+    tips.sort_by_key(|tip| tip.block_id.get());
+    tips
+}
+
+fn expected_tip(
+    btf: &BlockTestFramework,
+    block_id: &Id<Block>,
+    branch_length: i64,
+    status: ChainTipStatus,
+) -> ChainTip {
+    let block_index = btf.get_block_index(block_id);
+    ChainTip {
+        block_id: block_id.clone(),
+        height: block_index.block_height(),
+        chain_trust: *block_index.chain_trust(),
+        branch_length: BlockDistance::new(branch_length),
+        status,
+    }
+}
+
+#[test]
+fn genesis_only() {
+    common::concurrency::model(|| {
+        let btf = BlockTestFramework::new();
+        let genesis_id = btf.genesis().get_id();
+        assert_eq!(
+            chain_tips(&btf),
+            vec![expected_tip(&btf, &genesis_id, 0, ChainTipStatus::Active)]
+        );
+    });
+}
+
+#[test]
+fn forks_and_reorg() {
+    common::concurrency::model(|| {
+        let mut btf = BlockTestFramework::new();
+        let genesis_id = btf.genesis().get_id();
+
+        btf.create_chain(&genesis_id, 2).unwrap();
+        let main_tip_id = last_block_id(&btf);
+        assert_eq!(
+            chain_tips(&btf),
+            vec![expected_tip(&btf, &main_tip_id, 0, ChainTipStatus::Active)]
+        );
+
+        // A shorter fork is never connected
+        btf.create_chain(&genesis_id, 1).unwrap();
+        let fork_block_id = last_block_id(&btf);
+        assert_eq!(
+            chain_tips(&btf),
+            sorted(vec![
+                expected_tip(&btf, &main_tip_id, 0, ChainTipStatus::Active),
+                expected_tip(&btf, &fork_block_id, 1, ChainTipStatus::HeadersOnly),
+            ])
+        );
+
+        // Extending the fork past the main chain reorgs to it
+        btf.create_chain(&fork_block_id, 2).unwrap();
+        let fork_tip_id = last_block_id(&btf);
+        assert_eq!(
+            btf.chainstate.get_best_block_id().unwrap(),
+            Some(fork_tip_id.clone())
+        );
+        assert_eq!(
+            chain_tips(&btf),
+            sorted(vec![
+                expected_tip(&btf, &fork_tip_id, 0, ChainTipStatus::Active),
+                expected_tip(&btf, &main_tip_id, 2, ChainTipStatus::ValidFork),
+            ])
+        );
+    });
+}
+
+#[test]
+fn invalidated_tip() {
+    common::concurrency::model(|| {
+        let mut btf = BlockTestFramework::new();
+        let genesis_id = btf.genesis().get_id();
+
+        btf.create_chain(&genesis_id, 2).unwrap();
+        let main_tip_id = last_block_id(&btf);
+        btf.create_chain(&genesis_id, 1).unwrap();
+        let fork_tip_id = last_block_id(&btf);
+
+        btf.chainstate.invalidate_block(&fork_tip_id).unwrap();
+        assert_eq!(
+            chain_tips(&btf),
+            sorted(vec![
+                expected_tip(&btf, &main_tip_id, 0, ChainTipStatus::Active),
+                expected_tip(&btf, &fork_tip_id, 1, ChainTipStatus::Invalid),
+            ])
+        );
+
+        // The new best block is still reported as active, even though an invalid block builds on it
+        let new_best_id = btf.get_block_index(&main_tip_id).prev_block_id().clone().unwrap();
+        btf.chainstate.invalidate_block(&main_tip_id).unwrap();
+        assert_eq!(
+            btf.chainstate.get_best_block_id().unwrap(),
+            Some(new_best_id.clone())
+        );
+        assert_eq!(
+            chain_tips(&btf),
+            sorted(vec![
+                expected_tip(&btf, &new_best_id, 0, ChainTipStatus::Active),
+                expected_tip(&btf, &main_tip_id, 1, ChainTipStatus::Invalid),
+                expected_tip(&btf, &fork_tip_id, 1, ChainTipStatus::Invalid),
+            ])
+        );
+    });
+}
@@ -37,14 +37,11 @@ fn test_events_simple_subscribe() {
         // Event handler
         let events: EventList = Arc::new(Mutex::new(Vec::new()));
         let events_copy = Arc::clone(&events);
-        let subscribe_func =
-            Arc::new(
-                move |chainstate_event: ChainstateEvent| match chainstate_event {
-                    ChainstateEvent::NewTip(block_id, block_height) => {
-                        events_copy.lock().unwrap().push((block_id, block_height));
-                    }
-                },
-            );
+        let subscribe_func = Arc::new(move |chainstate_event: ChainstateEvent| {
+            if let ChainstateEvent::NewTip(block_id, block_height) = chainstate_event {
+                events_copy.lock().unwrap().push((block_id, block_height));
+            }
+        });
 
         // Subscribe and then process a new block
         chainstate.subscribe_to_events(subscribe_func);
@@ -78,14 +75,11 @@ fn test_events_with_a_bunch_of_subscribers() {
         // Event handler
         let events: EventList = Arc::new(Mutex::new(Vec::new()));
         let events_copy = Arc::clone(&events);
-        let subscribe_func =
-            Arc::new(
-                move |chainstate_event: ChainstateEvent| match chainstate_event {
-                    ChainstateEvent::NewTip(block_id, block_height) => {
-                        events_copy.lock().unwrap().push((block_id, block_height));
-                    }
-                },
-            );
+        let subscribe_func = Arc::new(move |chainstate_event: ChainstateEvent| {
+            if let ChainstateEvent::NewTip(block_id, block_height) = chainstate_event {
+                events_copy.lock().unwrap().push((block_id, block_height));
+            }
+        });
 
         // Subscribe and then process a new block
         for _ in 0..COUNT_SUBSCRIBERS {
@@ -113,7 +107,14 @@ fn test_events_a_bunch_of_events() {
     common::concurrency::model(|| {
         let config = Arc::new(create_unit_test_config());
         let storage = Store::new_empty().unwrap();
-        let mut chainstate = Chainstate::new(config, storage, None, Default::default()).unwrap();
+        let mut chainstate = Chainstate::new(
+            config,
+            Default::default(),
+            storage,
+            None,
+            Default::default(),
+        )
+        .unwrap();
 
         let mut map_heights: BTreeMap<Id<Block>, BlockHeight> = BTreeMap::new();
         let mut blocks = Vec::new();
@@ -130,14 +131,11 @@ fn test_events_a_bunch_of_events() {
         // Event handler
         let events: EventList = Arc::new(Mutex::new(Vec::new()));
         let events_copy = Arc::clone(&events);
-        let subscribe_func =
-            Arc::new(
-                move |chainstate_event: ChainstateEvent| match chainstate_event {
-                    ChainstateEvent::NewTip(block_id, block_height) => {
-                        events_copy.lock().unwrap().push((block_id, block_height));
-                    }
-                },
-            );
+        let subscribe_func = Arc::new(move |chainstate_event: ChainstateEvent| {
+            if let ChainstateEvent::NewTip(block_id, block_height) = chainstate_event {
+                events_copy.lock().unwrap().push((block_id, block_height));
+            }
+        });
 
         // Subscribe and then process a new block
         for _ in 0..COUNT_SUBSCRIBERS {
@@ -173,7 +171,14 @@ fn test_events_orphan_block() {
     common::concurrency::model(|| {
         let config = Arc::new(create_unit_test_config());
         let storage = Store::new_empty().unwrap();
-        let mut chainstate = Chainstate::new(config, storage, None, Default::default()).unwrap();
+        let mut chainstate = Chainstate::new(
+            config,
+            Default::default(),
+            storage,
+            None,
+            Default::default(),
+        )
+        .unwrap();
 
         // Let's create an orphan block
         let block = produce_test_block(chainstate.chain_config.genesis_block(), true);
@@ -181,14 +186,11 @@ fn test_events_orphan_block() {
         // Event handler
         let events: EventList = Arc::new(Mutex::new(Vec::new()));
         let events_copy = Arc::clone(&events);
-        let subscribe_func =
-            Arc::new(
-                move |chainstate_event: ChainstateEvent| match chainstate_event {
-                    ChainstateEvent::NewTip(block_id, block_height) => {
-                        events_copy.lock().unwrap().push((block_id, block_height));
-                    }
-                },
-            );
+        let subscribe_func = Arc::new(move |chainstate_event: ChainstateEvent| {
+            if let ChainstateEvent::NewTip(block_id, block_height) = chainstate_event {
+                events_copy.lock().unwrap().push((block_id, block_height));
+            }
+        });
         // Subscribe and then process a new block
         chainstate.subscribe_to_events(subscribe_func);
         assert!(!chainstate.events_controller.subscribers().is_empty());
@@ -197,3 +199,82 @@ fn test_events_orphan_block() {
         assert!(events.lock().unwrap().is_empty());
     });
 }
+
+#[test]
+fn test_events_block_connected() {
+    use std::sync::Arc;
+
+    common::concurrency::model(|| {
+        let mut chainstate = setup_chainstate();
+
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let events_copy = Arc::clone(&events);
+        chainstate.subscribe_to_events(Arc::new(move |chainstate_event: ChainstateEvent| {
+            events_copy.lock().unwrap().push(chainstate_event)
+        }));
+
+        let block = produce_test_block(chainstate.chain_config.genesis_block(), false);
+        let block_id = block.get_id();
+        chainstate.process_block(block, BlockSource::Local).unwrap();
+        chainstate.wait_for_all_events();
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec![
+                ChainstateEvent::BlockConnected(block_id.clone(), BlockHeight::new(1)),
+                ChainstateEvent::NewTip(block_id, BlockHeight::new(1)),
+            ]
+        );
+
+        // A block not extending the main chain generates no events
+        events.lock().unwrap().clear();
+        let side_block = produce_test_block(chainstate.chain_config.genesis_block(), false);
+        chainstate.process_block(side_block, BlockSource::Local).unwrap();
+        chainstate.wait_for_all_events();
+        assert!(events.lock().unwrap().is_empty());
+    });
+}
+
+#[tokio::test]
+async fn test_event_stream_through_handle() {
+    use crate::make_chainstate;
+    use std::sync::Arc;
+
+    let config = Arc::new(create_unit_test_config());
+    let storage = Store::new_empty().unwrap();
+    let block = produce_test_block(config.genesis_block(), false);
+    let block_id = block.get_id();
+    let chainstate = make_chainstate(
+        config,
+        Default::default(),
+        storage,
+        None,
+        Default::default(),
+    )
+    .unwrap();
+    let mut man = subsystem::Manager::new("event_stream_test");
+    let handle = man.add_subsystem("chainstate", chainstate);
+    let _ = man.add_raw_subsystem(
+        "test",
+        move |_: subsystem::subsystem::CallRequest<()>, _| async move {
+            let mut events =
+                handle.call_mut(|this| this.subscribe_to_event_stream(10)).await.unwrap();
+            handle
+                .call_mut(move |this| this.process_block(block, BlockSource::Local))
+                .await
+                .unwrap()
+                .unwrap();
+
+            let height = BlockHeight::new(1);
+            assert_eq!(
+                events.recv().await,
+                Ok(ChainstateEvent::BlockConnected(block_id.clone(), height))
+            );
+            assert_eq!(
+                events.recv().await,
+                Ok(ChainstateEvent::NewTip(block_id, height))
+            );
+            assert_eq!(events.try_recv(), Ok(None));
+        },
+    );
+    man.main().await;
+}
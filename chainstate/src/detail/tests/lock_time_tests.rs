@@ -0,0 +1,103 @@
+// Copyright (c) 2022 RBB S.r.l
+// opensource@mintlayer.org
+// SPDX-License-Identifier: MIT
+// Licensed under the MIT License;
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// 	http://spdx.org/licenses/MIT
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::detail::spend_cache::{error::StateUpdateError, LOCK_TIME_THRESHOLD};
+use crate::detail::tests::*;
+use common::chain::block::Block;
+use common::chain::Transaction;
+
+// A block spending all outputs of the previous block in a transaction with given lock time
+fn block_with_lock_time(prev_block: &Block, lock_time: u32) -> Block {
+    let (inputs, outputs) = prev_block.transactions().iter().flat_map(create_new_outputs).unzip();
+    let tx = Transaction::new(0, inputs, outputs, lock_time).expect(ERR_CREATE_TX_FAIL);
+    block_with_tx(prev_block, tx)
+}
+
+fn lock_time_error(block: &Block, lock_time: u32) -> BlockError {
+    let tx_id = block.transactions()[0].get_id();
+    BlockError::StateUpdateFailed(StateUpdateError::LockTimeNotReached(tx_id, lock_time))
+}
+
+#[test]
+fn height_lock_time() {
+    common::concurrency::model(|| {
+        let mut chainstate = setup_chainstate();
+        let genesis = chainstate.chain_config.genesis_block().clone();
+
+        // A transaction locked until height 1 cannot be included at height 1
+        let block = block_with_lock_time(&genesis, 1);
+        assert_eq!(
+            chainstate.process_block(block.clone(), BlockSource::Local).unwrap_err(),
+            lock_time_error(&block, 1),
+        );
+
+        let block = block_with_lock_time(&genesis, 0);
+        chainstate.process_block(block.clone(), BlockSource::Local).unwrap();
+
+        // The height lock is satisfied once the chain is past it
+        let block = block_with_lock_time(&block, 1);
+        let block_id = block.get_id();
+        chainstate.process_block(block, BlockSource::Local).unwrap();
+        assert_eq!(chainstate.get_best_block_id().unwrap(), Some(block_id));
+    });
+}
+
+#[test]
+fn time_lock_time() {
+    common::concurrency::model(|| {
+        let mut chainstate = setup_chainstate();
+        let genesis = chainstate.chain_config.genesis_block().clone();
+
+        // The median time past of the first block is the genesis timestamp
+        let median_time_past = genesis.timestamp().as_int_seconds();
+        assert!(median_time_past >= LOCK_TIME_THRESHOLD);
+
+        // Transactions locked until the median time past or later are rejected
+        for lock_time in [median_time_past, median_time_past + 1, u32::MAX] {
+            let block = block_with_lock_time(&genesis, lock_time);
+            assert_eq!(
+                chainstate.process_block(block.clone(), BlockSource::Local).unwrap_err(),
+                lock_time_error(&block, lock_time),
+            );
+        }
+
+        let block = block_with_lock_time(&genesis, median_time_past - 1);
+        let block_id = block.get_id();
+        chainstate.process_block(block, BlockSource::Local).unwrap();
+        assert_eq!(chainstate.get_best_block_id().unwrap(), Some(block_id));
+    });
+}
+
+#[test]
+fn lock_time_threshold() {
+    common::concurrency::model(|| {
+        let mut chainstate = setup_chainstate();
+        let genesis = chainstate.chain_config.genesis_block().clone();
+
+        // The largest height lock is far ahead of the chain
+        let lock_time = LOCK_TIME_THRESHOLD - 1;
+        let block = block_with_lock_time(&genesis, lock_time);
+        assert_eq!(
+            chainstate.process_block(block.clone(), BlockSource::Local).unwrap_err(),
+            lock_time_error(&block, lock_time),
+        );
+
+        // The smallest time lock is long past the median time past
+        let block = block_with_lock_time(&genesis, LOCK_TIME_THRESHOLD);
+        let block_id = block.get_id();
+        chainstate.process_block(block, BlockSource::Local).unwrap();
+        assert_eq!(chainstate.get_best_block_id().unwrap(), Some(block_id));
+    });
+}
@@ -34,18 +34,32 @@ pub(in crate::detail::tests) type EventList = Arc<Mutex<Vec<(Id<Block>, BlockHei
 
 mod test_framework;
 
+#[cfg(test)]
+mod address_index_tests;
+#[cfg(test)]
+mod block_generation_tests;
+#[cfg(test)]
+mod block_status_tests;
+#[cfg(test)]
+mod chain_tips_tests;
 #[cfg(test)]
 mod double_spend_tests;
 #[cfg(test)]
 mod events_tests;
 #[cfg(test)]
+mod lock_time_tests;
+#[cfg(test)]
 mod processing_tests;
 #[cfg(test)]
 mod reorgs_tests;
 #[cfg(test)]
 mod signature_tests;
 #[cfg(test)]
+mod storage_failure_tests;
+#[cfg(test)]
 mod syncing_tests;
+#[cfg(test)]
+mod tx_index_tests;
 
 pub(crate) const ERR_BEST_BLOCK_NOT_FOUND: &str = "Best block not found";
 pub(crate) const ERR_STORAGE_FAIL: &str = "Storage failure";
@@ -106,6 +120,7 @@ fn create_utxo_data(
 
 struct ChainstateBuilder {
     config: ChainConfig,
+    chainstate_config: ChainstateConfig,
     storage: Store,
 }
 
@@ -113,12 +128,14 @@ impl ChainstateBuilder {
     fn new() -> Self {
         Self {
             config: create_unit_test_config(),
+            chainstate_config: ChainstateConfig::default(),
             storage: Store::new_empty().unwrap(),
         }
     }
     fn build(self) -> Chainstate {
         Chainstate::new(
             Arc::new(self.config),
+            self.chainstate_config,
             self.storage,
             None,
             Default::default(),
@@ -131,6 +148,16 @@ impl ChainstateBuilder {
         self.config = chain_config;
         self
     }
+
+    fn with_tx_index(mut self) -> Self {
+        self.chainstate_config.tx_index_enabled = true;
+        self
+    }
+
+    fn with_address_index(mut self) -> Self {
+        self.chainstate_config.address_index_enabled = true;
+        self
+    }
 }
 
 fn setup_chainstate() -> Chainstate {
@@ -141,6 +168,17 @@ fn produce_test_block(prev_block: &Block, orphan: bool) -> Block {
     produce_test_block_with_consensus_data(prev_block, orphan, ConsensusData::None)
 }
 
+// A block on top of given block with a single transaction
+fn block_with_tx(prev_block: &Block, tx: Transaction) -> Block {
+    Block::new(
+        vec![tx],
+        Some(prev_block.get_id()),
+        BlockTimestamp::from_duration_since_epoch(time::get()).unwrap(),
+        ConsensusData::None,
+    )
+    .expect(ERR_CREATE_BLOCK_FAIL)
+}
+
 fn produce_test_block_with_consensus_data(
     prev_block: &Block,
     orphan: bool,
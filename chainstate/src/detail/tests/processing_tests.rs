@@ -20,15 +20,15 @@ use crate::detail::pow::error::ConsensusPoWError;
 use crate::detail::tests::test_framework::BlockTestFramework;
 use crate::detail::tests::*;
 use crate::make_chainstate;
-use chainstate_storage::BlockchainStorageRead;
 use chainstate_storage::Store;
+use chainstate_storage::{BlockchainStorageRead, UtxoRead};
 use common::chain::block::consensus_data::PoWData;
 use common::chain::config::create_unit_test_config;
 use common::chain::config::Builder as ConfigBuilder;
 use common::chain::ConsensusUpgrade;
 use common::chain::NetUpgrades;
+use common::chain::OutPoint;
 use common::chain::OutputPurpose;
-use common::chain::OutputSpentState;
 use common::chain::UpgradeVersion;
 use common::primitives::Compact;
 use common::Uint256;
@@ -42,8 +42,14 @@ fn test_process_genesis_block_wrong_block_source() {
         // Genesis can't be from Peer, test it
         let config = Arc::new(create_unit_test_config());
         let storage = Store::new_empty().unwrap();
-        let mut chainstate =
-            Chainstate::new_no_genesis(config.clone(), storage, None, Default::default()).unwrap();
+        let mut chainstate = Chainstate::new_no_genesis(
+            config.clone(),
+            Default::default(),
+            storage,
+            None,
+            Default::default(),
+        )
+        .unwrap();
 
         // process the genesis block
         let block_source = BlockSource::Peer;
@@ -58,8 +64,14 @@ fn test_process_genesis_block() {
         // This test process only Genesis block
         let config = Arc::new(create_unit_test_config());
         let storage = Store::new_empty().unwrap();
-        let mut chainstate =
-            Chainstate::new_no_genesis(config, storage, None, Default::default()).unwrap();
+        let mut chainstate = Chainstate::new_no_genesis(
+            config,
+            Default::default(),
+            storage,
+            None,
+            Default::default(),
+        )
+        .unwrap();
 
         // process the genesis block
         let block_source = BlockSource::Local;
@@ -91,7 +103,14 @@ fn test_orphans_chains() {
     common::concurrency::model(|| {
         let config = Arc::new(create_unit_test_config());
         let storage = Store::new_empty().unwrap();
-        let mut chainstate = Chainstate::new(config, storage, None, Default::default()).unwrap();
+        let mut chainstate = Chainstate::new(
+            config,
+            Default::default(),
+            storage,
+            None,
+            Default::default(),
+        )
+        .unwrap();
 
         assert_eq!(
             chainstate.get_best_block_id().unwrap().unwrap(),
@@ -145,8 +164,14 @@ fn test_empty_chainstate() {
         // No genesis
         let config = Arc::new(create_unit_test_config());
         let storage = Store::new_empty().unwrap();
-        let chainstate =
-            Chainstate::new_no_genesis(config, storage, None, Default::default()).unwrap();
+        let chainstate = Chainstate::new_no_genesis(
+            config,
+            Default::default(),
+            storage,
+            None,
+            Default::default(),
+        )
+        .unwrap();
         assert!(chainstate.get_best_block_id().unwrap().is_none());
         assert!(chainstate
             .chainstate_storage
@@ -156,7 +181,14 @@ fn test_empty_chainstate() {
         // Let's add genesis
         let config = Arc::new(create_unit_test_config());
         let storage = Store::new_empty().unwrap();
-        let chainstate = Chainstate::new(config, storage, None, Default::default()).unwrap();
+        let chainstate = Chainstate::new(
+            config,
+            Default::default(),
+            storage,
+            None,
+            Default::default(),
+        )
+        .unwrap();
         chainstate.get_best_block_id().unwrap().unwrap();
         assert!(
             chainstate.get_best_block_id().ok().flatten().unwrap()
@@ -187,15 +219,15 @@ fn test_spend_inputs_simple() {
         // Create a new block
         let block = produce_test_block(chainstate.chain_config.genesis_block(), false);
 
-        // Check that all tx not in the main chain
+        // Check that the outputs of all tx are not in the UTXO set
         for tx in block.transactions() {
-            assert!(
-                chainstate
-                    .chainstate_storage
-                    .get_mainchain_tx_index(&OutPointSourceId::from(tx.get_id()))
-                    .expect(ERR_STORAGE_FAIL)
-                    == None
-            );
+            for output_index in 0..tx.outputs().len() {
+                let outpoint = OutPoint::new(tx.get_id().into(), output_index as u32);
+                assert_eq!(
+                    chainstate.chainstate_storage.get_utxo(&outpoint).expect(ERR_STORAGE_FAIL),
+                    None
+                );
+            }
         }
 
         // Process the second block
@@ -209,22 +241,15 @@ fn test_spend_inputs_simple() {
             new_id
         );
 
-        // Check that tx inputs in the main chain and not spend
+        // Check that the outputs of all tx are in the UTXO set and not spent
         for tx in block.transactions() {
-            let tx_index = chainstate
-                .chainstate_storage
-                .get_mainchain_tx_index(&OutPointSourceId::from(tx.get_id()))
-                .expect("Not found mainchain tx index")
-                .expect(ERR_STORAGE_FAIL);
-
-            for input in tx.inputs() {
-                if tx_index
-                    .get_spent_state(input.outpoint().output_index())
-                    .expect("Unable to get spent state")
-                    != OutputSpentState::Unspent
-                {
-                    panic!("Tx input can't be spent");
-                }
+            for output_index in 0..tx.outputs().len() {
+                let outpoint = OutPoint::new(tx.get_id().into(), output_index as u32);
+                assert!(chainstate
+                    .chainstate_storage
+                    .get_utxo(&outpoint)
+                    .expect(ERR_STORAGE_FAIL)
+                    .is_some());
             }
         }
     });
@@ -237,8 +262,14 @@ fn test_straight_chain() {
         // In this test, processing a few correct blocks in a single chain
         let config = Arc::new(create_unit_test_config());
         let storage = Store::new_empty().unwrap();
-        let mut chainstate =
-            Chainstate::new_no_genesis(config, storage, None, Default::default()).unwrap();
+        let mut chainstate = Chainstate::new_no_genesis(
+            config,
+            Default::default(),
+            storage,
+            None,
+            Default::default(),
+        )
+        .unwrap();
 
         // process the genesis block
         let block_source = BlockSource::Local;
@@ -694,6 +725,13 @@ fn test_pow() {
             ))
         ))
     ));
+    // Anyone can make up such a block, so nothing is recorded about it
+    assert!(btf
+        .chainstate
+        .chainstate_storage
+        .get_block_index(&random_invalid_block.get_id())
+        .unwrap()
+        .is_none());
 
     // Now let's actually mine the block, i.e. find valid PoW and see that consensus checks pass
     let mut valid_block = random_invalid_block;
@@ -728,7 +766,8 @@ fn test_blocks_from_the_future() {
         }));
 
         let storage = Store::new_empty().unwrap();
-        let mut chainstate = Chainstate::new(config, storage, None, time_getter).unwrap();
+        let mut chainstate =
+            Chainstate::new(config, Default::default(), storage, None, time_getter).unwrap();
 
         {
             // ensure no blocks are in chain, so that median time can be the genesis time
@@ -810,5 +849,12 @@ fn test_blocks_from_the_future() {
 fn test_mainnet_initialization() {
     let config = Arc::new(common::chain::config::create_mainnet());
     let storage = Store::new_empty().unwrap();
-    let _chainstate = make_chainstate(config, storage, None, Default::default()).unwrap();
+    let _chainstate = make_chainstate(
+        config,
+        Default::default(),
+        storage,
+        None,
+        Default::default(),
+    )
+    .unwrap();
 }
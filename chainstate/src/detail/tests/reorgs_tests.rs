@@ -17,18 +17,28 @@
 
 use std::sync::Mutex;
 
+use crate::detail::ban_score::BanScore;
 use crate::detail::tests::test_framework::BlockTestFramework;
 use crate::detail::tests::*;
-use chainstate_storage::{BlockchainStorageRead, Store};
-use common::chain::config::create_unit_test_config;
+use chainstate_storage::{BlockchainStorageRead, Store, UndoRead, UtxoRead};
+use common::chain::config::{create_unit_test_config, Builder};
+use common::chain::OutPoint;
+use common::primitives::BlockDistance;
+use std::collections::BTreeMap;
 
 #[test]
 fn test_reorg_simple() {
     common::concurrency::model(|| {
         let config = Arc::new(create_unit_test_config());
         let storage = Store::new_empty().unwrap();
-        let mut chainstate =
-            Chainstate::new_no_genesis(config, storage, None, Default::default()).unwrap();
+        let mut chainstate = Chainstate::new_no_genesis(
+            config,
+            Default::default(),
+            storage,
+            None,
+            Default::default(),
+        )
+        .unwrap();
 
         // process the genesis block
         let result = chainstate.process_block(
@@ -90,6 +100,63 @@ fn test_reorg_simple() {
     });
 }
 
+#[test]
+fn test_reorg_undo_data() {
+    common::concurrency::model(|| {
+        let mut chainstate = setup_chainstate();
+
+        let block_first = produce_test_block(chainstate.chain_config.genesis_block(), false);
+        chainstate.process_block(block_first.clone(), BlockSource::Local).unwrap();
+        let undo_first = chainstate
+            .chainstate_storage
+            .get_undo_data(block_first.get_id())
+            .expect(ERR_STORAGE_FAIL)
+            .expect("Undo data of the connected block not found");
+        assert_eq!(
+            undo_first.tx_undos().len(),
+            block_first.transactions().len()
+        );
+        assert_eq!(
+            chainstate
+                .chainstate_storage
+                .get_best_block_for_utxos()
+                .expect(ERR_STORAGE_FAIL),
+            Some(block_first.get_id())
+        );
+
+        // Reorg to a longer chain, disconnecting the first block
+        let block_second = produce_test_block(chainstate.chain_config.genesis_block(), false);
+        chainstate.process_block(block_second.clone(), BlockSource::Local).unwrap();
+        let block_third = produce_test_block(&block_second, false);
+        chainstate.process_block(block_third.clone(), BlockSource::Local).unwrap();
+        assert_eq!(
+            chainstate
+                .chainstate_storage
+                .get_best_block_id()
+                .expect(ERR_BEST_BLOCK_NOT_FOUND),
+            Some(block_third.get_id())
+        );
+
+        let storage = &chainstate.chainstate_storage;
+        assert_eq!(
+            storage.get_undo_data(block_first.get_id()).expect(ERR_STORAGE_FAIL),
+            None
+        );
+        assert!(storage.get_undo_data(block_second.get_id()).expect(ERR_STORAGE_FAIL).is_some());
+        assert!(storage.get_undo_data(block_third.get_id()).expect(ERR_STORAGE_FAIL).is_some());
+        assert_eq!(
+            storage.get_best_block_for_utxos().expect(ERR_STORAGE_FAIL),
+            Some(block_third.get_id())
+        );
+
+        // The outputs of the disconnected block are removed from the UTXO set
+        for tx in block_first.transactions() {
+            let outpoint = OutPoint::new(tx.get_id().into(), 0);
+            assert_eq!(storage.get_utxo(&outpoint).expect(ERR_STORAGE_FAIL), None);
+        }
+    });
+}
+
 #[test]
 fn test_very_long_reorgs() {
     common::concurrency::model(|| {
@@ -121,6 +188,175 @@ fn test_very_long_reorgs() {
     });
 }
 
+#[test]
+fn test_reorgs_with_checkpoints() {
+    common::concurrency::model(|| {
+        // Produce the blocks upfront so the checkpoint can refer to one of them
+        let base_config = create_unit_test_config();
+        let block1 = produce_test_block(base_config.genesis_block(), false);
+        let block2 = produce_test_block(&block1, false);
+        let block3 = produce_test_block(&block2, false);
+        let config = Builder::test_chain()
+            .height_checkpoint_data(BTreeMap::from([(BlockHeight::new(2), block2.get_id())]))
+            .build();
+        assert_eq!(config.genesis_block_id(), base_config.genesis_block_id());
+        let chainstate = ChainstateBuilder::new().with_config(config).build();
+        let mut btf = BlockTestFramework::with_chainstate(chainstate);
+
+        btf.add_special_block(block1.clone()).unwrap();
+
+        // A block at the checkpoint height must match the checkpoint
+        let bad_block2 = produce_test_block(&block1, false);
+        assert_eq!(
+            btf.chainstate.process_block(bad_block2.clone(), BlockSource::Peer).unwrap_err(),
+            BlockError::CheckpointMismatch(
+                BlockHeight::new(2),
+                block2.get_id(),
+                bad_block2.get_id()
+            )
+        );
+        assert_eq!(
+            btf.chainstate.process_block(bad_block2.clone(), BlockSource::Peer).unwrap_err(),
+            BlockError::BlockMarkedInvalid(bad_block2.get_id())
+        );
+
+        btf.add_special_block(block2.clone()).unwrap();
+        btf.add_special_block(block3.clone()).unwrap();
+
+        // Once the checkpoint is in the main chain, forking below it is not allowed
+        let fork_block1 = produce_test_block(btf.genesis(), false);
+        assert_eq!(
+            btf.chainstate.process_block(fork_block1, BlockSource::Peer).unwrap_err(),
+            BlockError::ForkBelowCheckpoint(BlockHeight::new(1), BlockHeight::new(2))
+        );
+        let fork_block2 = produce_test_block(&block1, false);
+        assert!(matches!(
+            btf.chainstate.process_block(fork_block2, BlockSource::Peer),
+            Err(BlockError::CheckpointMismatch(_, _, _))
+        ));
+
+        // Forking above the checkpoint is fine
+        let fork_block3 = produce_test_block(&block2, false);
+        let fork_block4 = produce_test_block(&fork_block3, false);
+        btf.add_special_block(fork_block3).unwrap();
+        btf.add_special_block(fork_block4.clone()).unwrap();
+        assert_eq!(
+            btf.chainstate.get_best_block_id(),
+            Ok(Some(fork_block4.get_id()))
+        );
+        assert!(btf.is_block_in_main_chain(&block2.get_id()));
+    });
+}
+
+#[test]
+fn test_max_reorg_depth() {
+    common::concurrency::model(|| {
+        let config = Builder::test_chain().max_reorg_depth(Some(BlockDistance::new(2))).build();
+        let chainstate = ChainstateBuilder::new().with_config(config).build();
+        let mut btf = BlockTestFramework::with_chainstate(chainstate);
+        let genesis_id = btf.genesis().get_id();
+        btf.create_chain(&genesis_id, 3).unwrap();
+        let block1_id = btf.block_indexes[1].block_id().clone();
+
+        // A fork off 3 blocks below the tip is kept as long as it does not become the main chain
+        btf.create_chain(&genesis_id, 3).unwrap();
+        let fork_tip_id = btf.block_indexes.last().unwrap().block_id().clone();
+        let fork_tip = btf.get_block(fork_tip_id).unwrap().unwrap();
+
+        // Switching to it would be too deep a reorg. It is local policy, so the block is not
+        // recorded as invalid.
+        let fork_block = produce_test_block(&fork_tip, false);
+        for _ in 0..2 {
+            let err =
+                btf.chainstate.process_block(fork_block.clone(), BlockSource::Peer).unwrap_err();
+            assert_eq!(
+                err,
+                BlockError::ReorgTooDeep(
+                    fork_block.get_id(),
+                    BlockDistance::new(3),
+                    BlockDistance::new(2)
+                )
+            );
+            // The peer is penalized, but not banned right away
+            assert!((1..100).contains(&err.ban_score()));
+        }
+        assert!(btf.chainstate.get_block_index(&fork_block.get_id()).unwrap().is_none());
+
+        // Forking off 2 blocks below the tip is fine, and so is the reorg to such a fork
+        btf.create_chain(&block1_id, 3).unwrap();
+        let fork_tip_id = btf.block_indexes.last().unwrap().block_id().clone();
+        assert_eq!(btf.chainstate.get_best_block_id(), Ok(Some(fork_tip_id)));
+    });
+}
+
+#[test]
+fn test_reorg_events() {
+    common::concurrency::model(|| {
+        let mut btf = BlockTestFramework::new();
+        let genesis_id = btf.genesis().get_id();
+        btf.create_chain(&genesis_id, 2).unwrap();
+        btf.create_chain(&genesis_id, 2).unwrap();
+        let ids: Vec<_> = btf.block_indexes.iter().map(|bi| bi.block_id().clone()).collect();
+
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let events_copy = Arc::clone(&events);
+        btf.chainstate.subscribe_to_events(Arc::new(move |event: ChainstateEvent| {
+            events_copy.lock().unwrap().push(event)
+        }));
+
+        // Extending the side chain causes a reorg
+        btf.create_chain(&ids[4], 1).unwrap();
+        let new_tip_id = btf.block_indexes[5].block_id().clone();
+        btf.chainstate.wait_for_all_events();
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec![
+                ChainstateEvent::BlockDisconnected(ids[2].clone(), BlockHeight::new(2)),
+                ChainstateEvent::BlockDisconnected(ids[1].clone(), BlockHeight::new(1)),
+                ChainstateEvent::BlockConnected(ids[3].clone(), BlockHeight::new(1)),
+                ChainstateEvent::BlockConnected(ids[4].clone(), BlockHeight::new(2)),
+                ChainstateEvent::BlockConnected(new_tip_id.clone(), BlockHeight::new(3)),
+                ChainstateEvent::Reorg {
+                    disconnected: vec![ids[2].clone(), ids[1].clone()],
+                    connected: vec![ids[3].clone(), ids[4].clone(), new_tip_id.clone()],
+                },
+                ChainstateEvent::NewTip(new_tip_id, BlockHeight::new(3)),
+            ]
+        );
+    });
+}
+
+#[test]
+fn test_failed_reorg_events() {
+    common::concurrency::model(|| {
+        let mut btf = BlockTestFramework::new();
+        let genesis_id = btf.genesis().get_id();
+        btf.create_chain(&genesis_id, 2).unwrap();
+        let main_block_id = btf.block_indexes[1].block_id().clone();
+
+        // Side chain with a block spending an output that only exists in the main chain
+        btf.create_chain(&genesis_id, 1).unwrap();
+        let side_block = btf.get_block(btf.block_indexes[3].block_id().clone()).unwrap().unwrap();
+        let bad_block = btf.random_block(
+            &side_block,
+            Some(&[TestBlockParams::SpendFrom(main_block_id)]),
+        );
+        btf.add_special_block(bad_block.clone()).unwrap();
+
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let events_copy = Arc::clone(&events);
+        btf.chainstate.subscribe_to_events(Arc::new(move |event: ChainstateEvent| {
+            events_copy.lock().unwrap().push(event)
+        }));
+
+        // The reorg is rolled back, so none of its events are emitted
+        let block = produce_test_block(&bad_block, false);
+        assert!(btf.chainstate.process_block(block, BlockSource::Local).is_err());
+        btf.chainstate.wait_for_all_events();
+        assert!(events.lock().unwrap().is_empty());
+    });
+}
+
 fn check_spend_tx_in_failed_block(btf: &mut BlockTestFramework, events: &EventList) {
     // Check spending of a transaction in a block which failed to connect
     //
@@ -386,13 +622,11 @@ fn subscribe_to_events(btf: &mut BlockTestFramework, events: &EventList) {
     events.lock().unwrap().push((btf.genesis().get_id(), BlockHeight::from(0)));
     assert!(!events.lock().unwrap().is_empty());
     // Event handler
-    let subscribe_func = Arc::new(
-        move |chainstate_event: ChainstateEvent| match chainstate_event {
-            ChainstateEvent::NewTip(block_id, block_height) => {
-                events.lock().unwrap().push((block_id, block_height));
-                assert!(!events.lock().unwrap().is_empty());
-            }
-        },
-    );
+    let subscribe_func = Arc::new(move |chainstate_event: ChainstateEvent| {
+        if let ChainstateEvent::NewTip(block_id, block_height) = chainstate_event {
+            events.lock().unwrap().push((block_id, block_height));
+            assert!(!events.lock().unwrap().is_empty());
+        }
+    });
     btf.chainstate.subscribe_to_events(subscribe_func);
 }
@@ -1 +1,159 @@
-// TODO: add tests that cover signing transactions properly in blocks to spend outputs
+// Copyright (c) 2022 RBB S.r.l
+// opensource@mintlayer.org
+// SPDX-License-Identifier: MIT
+// Licensed under the MIT License;
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// 	http://spdx.org/licenses/MIT
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::detail::spend_cache::error::StateUpdateError;
+use crate::detail::tests::*;
+use common::chain::signature::inputsig::StandardInputSignature;
+use common::chain::signature::sighashtype::SigHashType;
+use crypto::key::{KeyKind, PrivateKey, PublicKey};
+
+const INPUT_COUNT: usize = 20;
+
+// Pay the genesis output to given keys, one output per key
+fn pay_to_keys(genesis: &Block, public_keys: &[PublicKey]) -> Block {
+    let genesis_tx = &genesis.transactions()[0];
+    let input = TxInput::new(genesis_tx.get_id().into(), 0, empty_witness());
+    let outputs = public_keys
+        .iter()
+        .map(|public_key| {
+            TxOutput::new(
+                Amount::from_atoms(1),
+                OutputPurpose::Transfer(Destination::PublicKey(public_key.clone())),
+            )
+        })
+        .collect();
+    let tx = Transaction::new(0, vec![input], outputs, 0).expect(ERR_CREATE_TX_FAIL);
+    block_with_tx(genesis, tx)
+}
+
+// Spend all outputs of the first transaction of given block, signing each input with given key
+fn spend_signed(prev_block: &Block, private_keys: &[PrivateKey]) -> Transaction {
+    let prev_tx = &prev_block.transactions()[0];
+    let outpoint_source = OutPointSourceId::from(prev_tx.get_id());
+    let unsigned_inputs: Vec<_> = (0..private_keys.len())
+        .map(|index| TxInput::new(outpoint_source.clone(), index as u32, empty_witness()))
+        .collect();
+    let outputs = vec![TxOutput::new(
+        Amount::from_atoms(1),
+        OutputPurpose::Transfer(anyonecanspend_address()),
+    )];
+    let unsigned_tx =
+        Transaction::new(0, unsigned_inputs, outputs.clone(), 0).expect(ERR_CREATE_TX_FAIL);
+
+    let inputs = private_keys
+        .iter()
+        .zip(prev_tx.outputs())
+        .enumerate()
+        .map(|(index, (private_key, output))| {
+            let signature = StandardInputSignature::produce_signature_for_input(
+                private_key,
+                SigHashType::default(),
+                output.purpose().destination().clone(),
+                &unsigned_tx,
+                index,
+            )
+            .unwrap();
+            TxInput::new(
+                outpoint_source.clone(),
+                index as u32,
+                InputWitness::Standard(signature),
+            )
+        })
+        .collect();
+    Transaction::new(0, inputs, outputs, 0).expect(ERR_CREATE_TX_FAIL)
+}
+
+#[test]
+fn spend_signed_inputs() {
+    common::concurrency::model(|| {
+        let mut chainstate = setup_chainstate();
+        let genesis = chainstate.chain_config.genesis_block().clone();
+        let (private_keys, public_keys): (Vec<_>, Vec<_>) =
+            (0..INPUT_COUNT).map(|_| PrivateKey::new(KeyKind::RistrettoSchnorr)).unzip();
+
+        let block_1 = pay_to_keys(&genesis, &public_keys);
+        chainstate.process_block(block_1.clone(), BlockSource::Local).unwrap();
+        let block_2 = block_with_tx(&block_1, spend_signed(&block_1, &private_keys));
+        chainstate.process_block(block_2.clone(), BlockSource::Local).unwrap();
+        assert_eq!(
+            chainstate.get_best_block_id().unwrap(),
+            Some(block_2.get_id())
+        );
+    });
+}
+
+#[test]
+fn spend_with_misplaced_signature() {
+    common::concurrency::model(|| {
+        let mut chainstate = setup_chainstate();
+        let genesis = chainstate.chain_config.genesis_block().clone();
+        let (private_keys, public_keys): (Vec<_>, Vec<_>) =
+            (0..INPUT_COUNT).map(|_| PrivateKey::new(KeyKind::RistrettoSchnorr)).unzip();
+
+        let block_1 = pay_to_keys(&genesis, &public_keys);
+        chainstate.process_block(block_1.clone(), BlockSource::Local).unwrap();
+
+        // Two inputs in the middle carry the signatures of each other, the first of them is reported
+        let failing_input = INPUT_COUNT / 2;
+        let tx = spend_signed(&block_1, &private_keys);
+        let mut inputs = tx.inputs().clone();
+        let witness = inputs[failing_input].witness().clone();
+        let next_witness = inputs[failing_input + 1].witness().clone();
+        inputs[failing_input].update_witness(next_witness);
+        inputs[failing_input + 1].update_witness(witness);
+        let tx = Transaction::new(0, inputs, tx.outputs().clone(), 0).expect(ERR_CREATE_TX_FAIL);
+        let tx_id = tx.get_id();
+        let block_2 = block_with_tx(&block_1, tx);
+        assert_eq!(
+            chainstate.process_block(block_2, BlockSource::Local).unwrap_err(),
+            BlockError::StateUpdateFailed(StateUpdateError::SignatureVerificationFailed(
+                tx_id.into(),
+                failing_input
+            ))
+        );
+        assert_eq!(
+            chainstate.get_best_block_id().unwrap(),
+            Some(block_1.get_id())
+        );
+    });
+}
+
+#[test]
+fn spend_signed_inputs_in_same_block() {
+    common::concurrency::model(|| {
+        let mut chainstate = setup_chainstate();
+        let genesis = chainstate.chain_config.genesis_block().clone();
+        let (private_keys, public_keys): (Vec<_>, Vec<_>) =
+            (0..INPUT_COUNT).map(|_| PrivateKey::new(KeyKind::RistrettoSchnorr)).unzip();
+
+        // The outputs are paid and spent by two transactions of the same block
+        let paying_block = pay_to_keys(&genesis, &public_keys);
+        let block = Block::new(
+            vec![
+                paying_block.transactions()[0].clone(),
+                spend_signed(&paying_block, &private_keys),
+            ],
+            Some(genesis.get_id()),
+            BlockTimestamp::from_duration_since_epoch(time::get()).unwrap(),
+            ConsensusData::None,
+        )
+        .expect(ERR_CREATE_BLOCK_FAIL);
+        chainstate.process_block(block.clone(), BlockSource::Local).unwrap();
+        assert_eq!(
+            chainstate.get_best_block_id().unwrap(),
+            Some(block.get_id())
+        );
+    });
+}
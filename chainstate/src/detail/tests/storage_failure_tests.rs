@@ -0,0 +1,160 @@
+// Copyright (c) 2022 RBB S.r.l
+// opensource@mintlayer.org
+// SPDX-License-Identifier: MIT
+// Licensed under the MIT License;
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// 	http://spdx.org/licenses/MIT
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::detail::tests::*;
+use chainstate_storage::failing::{FailingStorage, Faults, Operation, Schedule};
+use storage::error::Recoverable;
+
+fn setup_failing_chainstate() -> (Chainstate<FailingStorage<Store>>, Arc<Faults>) {
+    let storage = FailingStorage::new(Store::new_empty().unwrap());
+    let faults = Arc::clone(storage.faults());
+    let chainstate = Chainstate::new(
+        Arc::new(create_unit_test_config()),
+        Default::default(),
+        storage,
+        None,
+        Default::default(),
+    )
+    .unwrap();
+    (chainstate, faults)
+}
+
+const ERROR_KINDS: [Recoverable; 3] = [
+    Recoverable::TransactionFailed,
+    Recoverable::TemporarilyUnavailable,
+    Recoverable::Unknown,
+];
+
+fn best_block_id(chainstate: &Chainstate<FailingStorage<Store>>) -> Id<Block> {
+    chainstate.get_best_block_id().unwrap().expect(ERR_BEST_BLOCK_NOT_FOUND)
+}
+
+#[test]
+fn commit_retried_after_failure() {
+    common::concurrency::model(|| {
+        for error in ERROR_KINDS {
+            let (mut chainstate, faults) = setup_failing_chainstate();
+            let block = produce_test_block(chainstate.chain_config.genesis_block(), false);
+
+            faults.set(Operation::Commit, Schedule::Next(3), error);
+            let block_index = chainstate.process_block(block.clone(), BlockSource::Local).unwrap();
+            assert_eq!(block_index.unwrap().block_id(), &block.get_id());
+            assert_eq!(best_block_id(&chainstate), block.get_id());
+            assert_eq!(faults.failures(Operation::Commit), 3);
+        }
+    });
+}
+
+#[test]
+fn commit_failure_exhausts_retries() {
+    common::concurrency::model(|| {
+        for error in ERROR_KINDS {
+            let (mut chainstate, faults) = setup_failing_chainstate();
+            let genesis_id = chainstate.chain_config.genesis_block_id();
+            let block = produce_test_block(chainstate.chain_config.genesis_block(), false);
+            let commits_before = faults.count(Operation::Commit);
+
+            faults.set(Operation::Commit, Schedule::Always, error);
+            assert_eq!(
+                chainstate.process_block(block.clone(), BlockSource::Local).unwrap_err(),
+                BlockError::DatabaseCommitError(
+                    block.get_id(),
+                    10,
+                    chainstate_storage::Error::Storage(error)
+                )
+            );
+            // The first attempt and 10 retries
+            assert_eq!(faults.count(Operation::Commit) - commits_before, 11);
+            assert_eq!(best_block_id(&chainstate), genesis_id);
+            assert_eq!(chainstate.get_block(block.get_id()), Ok(None));
+
+            // The block is accepted once the storage recovers
+            faults.clear();
+            assert!(chainstate.process_block(block.clone(), BlockSource::Local).is_ok());
+            assert_eq!(best_block_id(&chainstate), block.get_id());
+        }
+    });
+}
+
+#[test]
+fn read_failure_propagated() {
+    common::concurrency::model(|| {
+        for error in ERROR_KINDS {
+            let (mut chainstate, faults) = setup_failing_chainstate();
+            let genesis_id = chainstate.chain_config.genesis_block_id();
+            let block = produce_test_block(chainstate.chain_config.genesis_block(), false);
+            let commits_before = faults.count(Operation::Commit);
+
+            faults.set(Operation::Read, Schedule::Next(1), error);
+            assert_eq!(
+                chainstate.process_block(block.clone(), BlockSource::Local).unwrap_err(),
+                BlockError::OrphanCheckFailed(OrphanCheckError::PrevBlockIndexNotFound(
+                    PropertyQueryError::StorageError(chainstate_storage::Error::Storage(error))
+                ))
+            );
+            // Failed reads are not retried and nothing gets committed
+            assert_eq!(faults.count(Operation::Commit), commits_before);
+            assert_eq!(best_block_id(&chainstate), genesis_id);
+
+            assert!(chainstate.process_block(block.clone(), BlockSource::Local).is_ok());
+            assert_eq!(best_block_id(&chainstate), block.get_id());
+        }
+    });
+}
+
+#[test]
+fn write_failure_propagated() {
+    common::concurrency::model(|| {
+        for error in ERROR_KINDS {
+            let (mut chainstate, faults) = setup_failing_chainstate();
+            let genesis_id = chainstate.chain_config.genesis_block_id();
+            let block = produce_test_block(chainstate.chain_config.genesis_block(), false);
+            let commits_before = faults.count(Operation::Commit);
+
+            faults.set(Operation::Write, Schedule::Next(1), error);
+            assert_eq!(
+                chainstate.process_block(block.clone(), BlockSource::Local).unwrap_err(),
+                BlockError::StorageError(chainstate_storage::Error::Storage(error))
+            );
+            assert_eq!(faults.count(Operation::Commit), commits_before);
+            assert_eq!(best_block_id(&chainstate), genesis_id);
+            assert_eq!(chainstate.get_block(block.get_id()), Ok(None));
+
+            assert!(chainstate.process_block(block.clone(), BlockSource::Local).is_ok());
+            assert_eq!(best_block_id(&chainstate), block.get_id());
+        }
+    });
+}
+
+#[test]
+fn random_commit_failures() {
+    common::concurrency::model(|| {
+        let (mut chainstate, faults) = setup_failing_chainstate();
+        let schedule = Schedule::Random {
+            probability: 0.3,
+            seed: 0x5eed,
+        };
+        faults.set(Operation::Commit, schedule, Recoverable::TransactionFailed);
+
+        let mut prev_block = chainstate.chain_config.genesis_block().clone();
+        for _ in 0..20 {
+            let block = produce_test_block(&prev_block, false);
+            assert!(chainstate.process_block(block.clone(), BlockSource::Local).is_ok());
+            assert_eq!(best_block_id(&chainstate), block.get_id());
+            prev_block = block;
+        }
+        assert!(faults.failures(Operation::Commit) > 0);
+    });
+}
@@ -25,8 +25,14 @@ fn test_get_locator() {
     common::concurrency::model(|| {
         let config = Arc::new(create_unit_test_config());
         let storage = Store::new_empty().unwrap();
-        let mut consensus =
-            Chainstate::new(Arc::clone(&config), storage, None, Default::default()).unwrap();
+        let mut consensus = Chainstate::new(
+            Arc::clone(&config),
+            Default::default(),
+            storage,
+            None,
+            Default::default(),
+        )
+        .unwrap();
 
         let mut prev_block = consensus.chain_config.genesis_block().clone();
         let limit = crypto::random::make_pseudo_rng().gen::<u16>();
@@ -68,7 +74,14 @@ fn test_get_headers_same_chain() {
     common::concurrency::model(|| {
         let config = Arc::new(create_unit_test_config());
         let storage = Store::new_empty().unwrap();
-        let mut consensus = Chainstate::new(config, storage, None, Default::default()).unwrap();
+        let mut consensus = Chainstate::new(
+            config,
+            Default::default(),
+            storage,
+            None,
+            Default::default(),
+        )
+        .unwrap();
 
         let mut prev_block = consensus.chain_config.genesis_block().clone();
         let limit = crypto::random::make_pseudo_rng().gen::<u16>();
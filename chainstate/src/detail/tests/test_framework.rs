@@ -16,9 +16,9 @@
 // Author(s): A. Sinitsyn
 
 use crate::detail::tests::*;
-use chainstate_storage::BlockchainStorageRead;
+use chainstate_storage::{BlockchainStorageRead, UtxoRead};
 use common::chain::block::{Block, ConsensusData};
-use common::chain::{OutputSpentState, Transaction, TxInput, TxOutput};
+use common::chain::{OutPoint, Transaction, TxInput, TxOutput};
 use common::primitives::Id;
 use common::primitives::H256;
 use std::panic;
@@ -181,15 +181,13 @@ impl<'a> BlockTestFramework {
                 println!("\t\t+From: {:?}", input.outpoint());
             }
             for (output_index, output) in tx.outputs().iter().enumerate() {
-                let spent_status = self.get_spent_status(&tx.get_id(), output_index as u32);
+                let is_unspent = self.is_output_unspent(&tx.get_id(), output_index as u32);
                 println!("\t+Output: {}", output_index);
                 println!("\t\t+Value: {}", output.value().into_atoms());
-                match spent_status {
-                    Some(OutputSpentState::Unspent) => println!("\t\t+Spend: Unspent"),
-                    Some(OutputSpentState::SpentBy(spender)) => {
-                        println!("\t\t+Spend: {:?}", spender)
-                    }
-                    None => println!("\t\t+Spend: Not in mainchain"),
+                if is_unspent {
+                    println!("\t\t+Spend: Unspent");
+                } else {
+                    println!("\t\t+Spend: Spent or not in mainchain");
                 }
             }
         }
@@ -237,30 +235,19 @@ impl<'a> BlockTestFramework {
         Ok(block_index)
     }
 
-    pub(in crate::detail::tests) fn get_spent_status(
+    pub(in crate::detail::tests) fn is_output_unspent(
         &self,
         tx_id: &Id<Transaction>,
         output_index: u32,
-    ) -> Option<OutputSpentState> {
-        let tx_index = self
-            .chainstate
-            .chainstate_storage
-            .get_mainchain_tx_index(&OutPointSourceId::from(tx_id.clone()))
-            .unwrap()?;
-        tx_index.get_spent_state(output_index).ok()
+    ) -> bool {
+        let outpoint = OutPoint::new(tx_id.clone().into(), output_index);
+        self.chainstate.chainstate_storage.get_utxo(&outpoint).unwrap().is_some()
     }
 
     fn check_spend_status(&self, tx: &Transaction, spend_status: &TestSpentStatus) {
         for (output_index, _) in tx.outputs().iter().enumerate() {
-            let is_spend_status_correct = if spend_status == &TestSpentStatus::Spent {
-                self.get_spent_status(&tx.get_id(), output_index as u32)
-                    != Some(OutputSpentState::Unspent)
-            } else {
-                self.get_spent_status(&tx.get_id(), output_index as u32)
-                    == Some(OutputSpentState::Unspent)
-            };
-
-            assert!(is_spend_status_correct);
+            let is_unspent = self.is_output_unspent(&tx.get_id(), output_index as u32);
+            assert_eq!(is_unspent, spend_status == &TestSpentStatus::Unspent);
         }
     }
 
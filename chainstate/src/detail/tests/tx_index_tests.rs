@@ -0,0 +1,130 @@
+// Copyright (c) 2022 RBB S.r.l
+// opensource@mintlayer.org
+// SPDX-License-Identifier: MIT
+// Licensed under the MIT License;
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// 	http://spdx.org/licenses/MIT
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::detail::tests::*;
+use crate::ChainstateError;
+
+fn assert_tx_in_block(chainstate: &Chainstate, block: &Block, height: BlockHeight) {
+    for tx in block.transactions() {
+        assert_eq!(
+            chainstate.get_transaction(&tx.get_id()),
+            Ok(Some((tx.clone(), block.get_id(), height)))
+        );
+    }
+}
+
+fn assert_tx_not_indexed(chainstate: &Chainstate, block: &Block) {
+    for tx in block.transactions() {
+        assert_eq!(chainstate.get_transaction(&tx.get_id()), Ok(None));
+    }
+}
+
+#[test]
+fn tx_index_follows_mainchain() {
+    common::concurrency::model(|| {
+        let mut chainstate = ChainstateBuilder::new().with_tx_index().build();
+        let genesis = chainstate.chain_config.genesis_block().clone();
+        assert_tx_in_block(&chainstate, &genesis, BlockHeight::new(0));
+
+        let block_first = produce_test_block(&genesis, false);
+        chainstate.process_block(block_first.clone(), BlockSource::Local).unwrap();
+        assert_tx_in_block(&chainstate, &block_first, BlockHeight::new(1));
+
+        // A block on a side chain is not indexed
+        let block_second = produce_test_block(&genesis, false);
+        chainstate.process_block(block_second.clone(), BlockSource::Local).unwrap();
+        assert_tx_not_indexed(&chainstate, &block_second);
+
+        // Reorg to the side chain
+        let block_third = produce_test_block(&block_second, false);
+        chainstate.process_block(block_third.clone(), BlockSource::Local).unwrap();
+        assert_tx_not_indexed(&chainstate, &block_first);
+        assert_tx_in_block(&chainstate, &genesis, BlockHeight::new(0));
+        assert_tx_in_block(&chainstate, &block_second, BlockHeight::new(1));
+        assert_tx_in_block(&chainstate, &block_third, BlockHeight::new(2));
+    });
+}
+
+#[test]
+fn tx_index_disabled() {
+    common::concurrency::model(|| {
+        let chainstate = setup_chainstate();
+        let genesis_tx_id = chainstate.chain_config.genesis_block().transactions()[0].get_id();
+        assert_eq!(
+            chainstate.get_transaction(&genesis_tx_id),
+            Err(PropertyQueryError::TxIndexDisabled)
+        );
+    });
+}
+
+#[test]
+fn tx_index_setting_cannot_change() {
+    common::concurrency::model(|| {
+        let config = Arc::new(create_unit_test_config());
+        let storage = Store::new_empty().unwrap();
+        let tx_index = ChainstateConfig {
+            tx_index_enabled: true,
+            ..ChainstateConfig::default()
+        };
+
+        let _chainstate = Chainstate::new(
+            Arc::clone(&config),
+            tx_index.clone(),
+            storage.clone(),
+            None,
+            Default::default(),
+        )
+        .unwrap();
+
+        let res = Chainstate::new(
+            Arc::clone(&config),
+            ChainstateConfig::default(),
+            storage.clone(),
+            None,
+            Default::default(),
+        );
+        assert!(matches!(
+            res,
+            Err(ChainstateError::FailedToInitializeChainstate(_))
+        ));
+
+        assert!(Chainstate::new(config, tx_index, storage, None, Default::default()).is_ok());
+    });
+}
+
+#[test]
+fn tx_index_not_enabled_on_unindexed_chain() {
+    use chainstate_storage::{BlockchainStorageRead, BlockchainStorageWrite};
+
+    common::concurrency::model(|| {
+        let config = Arc::new(create_unit_test_config());
+        // Blocks stored with no index setting recorded
+        let mut storage = Store::new_empty().unwrap();
+        storage.set_best_block_id(&config.genesis_block_id()).unwrap();
+        let tx_index = ChainstateConfig {
+            tx_index_enabled: true,
+            ..ChainstateConfig::default()
+        };
+
+        // The operator is told how to get the index built
+        let res = Chainstate::new(config, tx_index, storage.clone(), None, Default::default());
+        assert!(matches!(
+            res,
+            Err(ChainstateError::FailedToInitializeChainstate(msg))
+                if msg.contains("resync the chain from scratch into an empty data directory")
+        ));
+        assert_eq!(storage.get_is_mainchain_tx_index_enabled(), Ok(Some(false)));
+    });
+}
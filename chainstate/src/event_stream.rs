@@ -0,0 +1,200 @@
+//! Asynchronous subscription to chainstate events
+
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::task::{Context, Poll};
+
+use tokio::sync::mpsc;
+use utils::eventhandler::EventHandler;
+
+use crate::ChainstateEvent;
+
+/// Error receiving a chainstate event
+#[derive(thiserror::Error, Debug, PartialEq, Eq, Clone, Copy)]
+pub enum EventRecvError {
+    #[error("Subscriber lagged behind, {0} events were dropped")]
+    Lagged(u64),
+    #[error("Chainstate event source closed")]
+    Closed,
+}
+
+type Message = Result<ChainstateEvent, EventRecvError>;
+
+/// Receiving end of a chainstate event subscription.
+///
+/// Events are buffered up to the size given on subscription. If the subscriber does not keep up
+/// and the buffer is full, new events are dropped. The subscriber then receives
+/// [EventRecvError::Lagged] with the number of events dropped, in place of the missing events,
+/// and continues receiving events from there. The stream ends with [EventRecvError::Closed] once
+/// the chainstate shuts down.
+///
+/// The receiver can also be used as a [futures::Stream], which ends instead of yielding
+/// [EventRecvError::Closed]. Dropping the receiver cancels the subscription.
+pub struct EventReceiver {
+    rx: mpsc::Receiver<Message>,
+}
+
+impl EventReceiver {
+    /// Wait for the next event
+    pub async fn recv(&mut self) -> Result<ChainstateEvent, EventRecvError> {
+        self.rx.recv().await.unwrap_or(Err(EventRecvError::Closed))
+    }
+
+    /// Get the next event if there is one available without waiting
+    pub fn try_recv(&mut self) -> Result<Option<ChainstateEvent>, EventRecvError> {
+        match self.rx.try_recv() {
+            Ok(msg) => msg.map(Some),
+            Err(mpsc::error::TryRecvError::Empty) => Ok(None),
+            Err(mpsc::error::TryRecvError::Disconnected) => Err(EventRecvError::Closed),
+        }
+    }
+}
+
+impl futures::Stream for EventReceiver {
+    type Item = Result<ChainstateEvent, EventRecvError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.rx.poll_recv(cx)
+    }
+}
+
+// Sending end of a subscription
+struct EventSender {
+    tx: mpsc::Sender<Message>,
+    // Number of events dropped since the last one delivered
+    dropped: u64,
+}
+
+impl EventSender {
+    // Send an event, reporting the events dropped before it first. Returns false if the
+    // receiver is gone.
+    fn send(&mut self, event: ChainstateEvent) -> bool {
+        if self.dropped > 0 {
+            match self.tx.try_send(Err(EventRecvError::Lagged(self.dropped))) {
+                Ok(()) => self.dropped = 0,
+                Err(mpsc::error::TrySendError::Full(_)) => {
+                    self.dropped += 1;
+                    return true;
+                }
+                Err(mpsc::error::TrySendError::Closed(_)) => return false,
+            }
+        }
+        match self.tx.try_send(Ok(event)) {
+            Ok(()) => true,
+            Err(mpsc::error::TrySendError::Full(_)) => {
+                self.dropped += 1;
+                true
+            }
+            Err(mpsc::error::TrySendError::Closed(_)) => false,
+        }
+    }
+}
+
+/// Set of event stream subscriptions, all served by a single event handler.
+///
+/// Subscriptions whose receiver has been dropped are removed when the next event is delivered.
+#[derive(Clone, Default)]
+pub(crate) struct EventStreams(Arc<Mutex<Vec<EventSender>>>);
+
+impl EventStreams {
+    /// Add a subscription with a receiver buffering up to given number of events
+    pub(crate) fn subscribe(&self, buffer_size: usize) -> EventReceiver {
+        let (tx, rx) = mpsc::channel(std::cmp::max(buffer_size, 1));
+        self.senders().push(EventSender { tx, dropped: 0 });
+        EventReceiver { rx }
+    }
+
+    /// Create the event handler delivering events to all the subscriptions
+    pub(crate) fn handler(&self) -> EventHandler<ChainstateEvent> {
+        let streams = self.clone();
+        Arc::new(move |event: ChainstateEvent| {
+            streams.senders().retain_mut(|sender| sender.send(event.clone()))
+        })
+    }
+
+    fn senders(&self) -> MutexGuard<'_, Vec<EventSender>> {
+        self.0.lock().expect("Event streams mutex poisoned")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use common::chain::block::Block;
+    use common::primitives::{BlockHeight, Id, H256};
+
+    fn channel(buffer_size: usize) -> (EventHandler<ChainstateEvent>, EventReceiver) {
+        let streams = EventStreams::default();
+        let receiver = streams.subscribe(buffer_size);
+        (streams.handler(), receiver)
+    }
+
+    fn event(n: u64) -> ChainstateEvent {
+        ChainstateEvent::NewTip(
+            Id::<Block>::new(H256::from_low_u64_be(n)),
+            BlockHeight::new(n),
+        )
+    }
+
+    #[test]
+    fn events_in_order() {
+        let (handler, mut receiver) = channel(10);
+        assert_eq!(receiver.try_recv(), Ok(None));
+        (0..5).for_each(|n| handler(event(n)));
+        for n in 0..5 {
+            assert_eq!(receiver.try_recv(), Ok(Some(event(n))));
+        }
+        assert_eq!(receiver.try_recv(), Ok(None));
+    }
+
+    #[test]
+    fn slow_subscriber_lags() {
+        let (handler, mut receiver) = channel(3);
+        (0..8).for_each(|n| handler(event(n)));
+        for n in 0..3 {
+            assert_eq!(receiver.try_recv(), Ok(Some(event(n))));
+        }
+        assert_eq!(receiver.try_recv(), Ok(None));
+
+        // The events dropped are reported before the next event
+        (8..10).for_each(|n| handler(event(n)));
+        assert_eq!(receiver.try_recv(), Err(EventRecvError::Lagged(5)));
+        assert_eq!(receiver.try_recv(), Ok(Some(event(8))));
+        assert_eq!(receiver.try_recv(), Ok(Some(event(9))));
+        assert_eq!(receiver.try_recv(), Ok(None));
+    }
+
+    #[test]
+    fn closed() {
+        let (handler, mut receiver) = channel(3);
+        handler(event(0));
+        drop(handler);
+        assert_eq!(receiver.try_recv(), Ok(Some(event(0))));
+        assert_eq!(receiver.try_recv(), Err(EventRecvError::Closed));
+    }
+
+    #[test]
+    fn stream() {
+        use futures::StreamExt;
+
+        // The stream ends once the sending side is gone
+        let (handler, receiver) = channel(5);
+        (0..5).for_each(|n| handler(event(n)));
+        drop(handler);
+        let events = futures::executor::block_on(receiver.collect::<Vec<_>>());
+        assert_eq!(events, (0..5).map(|n| Ok(event(n))).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn dropped_receiver_unsubscribed() {
+        let streams = EventStreams::default();
+        let handler = streams.handler();
+        let mut receiver = streams.subscribe(3);
+        drop(streams.subscribe(3));
+        assert_eq!(streams.senders().len(), 2);
+
+        handler(event(0));
+        assert_eq!(streams.senders().len(), 1);
+        assert_eq!(receiver.try_recv(), Ok(Some(event(0))));
+    }
+}
@@ -23,34 +23,59 @@ pub mod chainstate_interface_impl;
 
 pub mod chainstate_interface;
 
+mod block_template;
+mod chain_tip;
+mod config;
+mod event_stream;
+
 pub use detail::ban_score;
 
 use std::sync::Arc;
 
+pub use block_template::BlockTemplate;
+pub use chain_tip::{ChainTip, ChainTipStatus};
 use chainstate_interface::ChainstateInterface;
 pub use chainstate_interface_impl::ChainstateInterfaceImpl;
 use common::{
     chain::{block::Block, ChainConfig},
     primitives::{BlockHeight, Id},
 };
+pub use config::ChainstateConfig;
 use detail::time_getter::TimeGetter;
-pub use detail::BlockError;
 use detail::PropertyQueryError;
+pub use detail::{BlockError, BlockGenerationError};
 pub use detail::{BlockSource, Chainstate};
+pub use event_stream::{EventReceiver, EventRecvError};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum ChainstateEvent {
+    /// The best block is now the block with given ID and height
     NewTip(Id<Block>, BlockHeight),
+    /// Block was connected to the main chain at given height
+    BlockConnected(Id<Block>, BlockHeight),
+    /// Block was disconnected from the main chain at given height. Its transactions are no longer
+    /// confirmed, unless also included in a block connected later.
+    BlockDisconnected(Id<Block>, BlockHeight),
+    /// The main chain switched to another branch. Follows the corresponding connect and
+    /// disconnect events.
+    Reorg {
+        /// Blocks disconnected from the old branch, from the old tip down
+        disconnected: Vec<Id<Block>>,
+        /// Blocks connected from the new branch, in the order they were connected
+        connected: Vec<Id<Block>>,
+    },
 }
 
 #[derive(thiserror::Error, Debug, PartialEq, Eq)]
 pub enum ChainstateError {
-    #[error("Initialization error")]
+    #[error("Initialization error: {0}")]
     FailedToInitializeChainstate(String),
     #[error("Block processing failed: `{0}`")]
     ProcessBlockError(BlockError),
     #[error("Property read error: `{0}`")]
     FailedToReadProperty(PropertyQueryError),
+    #[error("Block generation failed: `{0}`")]
+    BlockGenerationFailed(BlockGenerationError),
 }
 
 impl subsystem::Subsystem for Box<dyn ChainstateInterface> {}
@@ -59,12 +84,14 @@ type ChainstateHandle = subsystem::Handle<Box<dyn ChainstateInterface>>;
 
 pub fn make_chainstate(
     chain_config: Arc<ChainConfig>,
+    chainstate_config: ChainstateConfig,
     chainstate_storage: chainstate_storage::Store,
     custom_orphan_error_hook: Option<Arc<detail::OrphanErrorHandler>>,
     time_getter: TimeGetter,
 ) -> Result<Box<dyn ChainstateInterface>, ChainstateError> {
     let cons = Chainstate::new(
         chain_config,
+        chainstate_config,
         chainstate_storage,
         custom_orphan_error_hook,
         time_getter,
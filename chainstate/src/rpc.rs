@@ -2,12 +2,123 @@
 
 use crate::ChainstateError;
 
-use crate::{Block, BlockSource};
-use common::primitives::BlockHeight;
-use serialization::Decode;
+use crate::ban_score::BanScore;
+use crate::{Block, BlockSource, BlockTemplate, ChainTip, ChainTipStatus};
+use chainstate_types::address_index::AddressHistoryEntry;
+use common::{
+    address::Address,
+    chain::Transaction,
+    primitives::{BlockHeight, Idable},
+};
+use serialization::{Decode, Encode};
 use subsystem::subsystem::CallError;
 
 type BlockId = common::primitives::Id<common::chain::block::Block>;
+type TxId = common::primitives::Id<Transaction>;
+
+/// Maximum number of outputs returned by a single address history query
+pub const MAX_ADDRESS_HISTORY_PAGE: usize = 1000;
+
+/// A mainchain transaction along with the block that contains it
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct TransactionInfo {
+    /// Hex-encoded transaction
+    pub transaction: String,
+    pub block_id: BlockId,
+    pub block_height: BlockHeight,
+}
+
+/// An output paid to an address, along with its spending status
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct AddressOutputInfo {
+    /// Hex-encoded outpoint
+    pub outpoint: String,
+    /// Hex-encoded output
+    pub output: String,
+    /// Height of the block that created the output
+    pub block_height: BlockHeight,
+    /// Hex-encoded ID of the transaction or block reward that spent the output, if any
+    pub spent_by: Option<String>,
+}
+
+/// The outcome of submitting a block
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct SubmitBlockResult {
+    pub block_id: BlockId,
+    /// Whether the block is now the tip of the main chain
+    pub is_tip: bool,
+    /// Why the block was rejected, if it was
+    pub reject_reason: Option<String>,
+    /// The ban score a peer sending the block would get
+    pub ban_score: u32,
+}
+
+/// The parameters of a new block on the best block
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct BlockTemplateInfo {
+    pub prev_block_id: BlockId,
+    pub height: BlockHeight,
+    /// The earliest timestamp the block may have, in seconds since the epoch
+    pub min_timestamp: u32,
+    /// The suggested timestamp of the block, in seconds since the epoch
+    pub timestamp: u32,
+    /// Compact proof of work target of a block with the suggested timestamp
+    pub bits: u32,
+    /// The amount the block reward outputs may pay in total, as a decimal number of atoms. It
+    /// does not always fit the integers JSON parsers handle.
+    pub reward: String,
+    /// Hex-encoded transactions to include in the block
+    pub transactions: Vec<String>,
+}
+
+impl From<BlockTemplate> for BlockTemplateInfo {
+    fn from(template: BlockTemplate) -> Self {
+        Self {
+            prev_block_id: template.prev_block_id,
+            height: template.height,
+            min_timestamp: template.min_timestamp.as_int_seconds(),
+            timestamp: template.timestamp.as_int_seconds(),
+            bits: template.bits.0,
+            reward: template.reward.into_atoms().to_string(),
+            transactions: template.transactions.iter().map(|tx| hex::encode(tx.encode())).collect(),
+        }
+    }
+}
+
+/// A block no other block builds on
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct ChainTipInfo {
+    pub block_id: BlockId,
+    pub height: BlockHeight,
+    /// Hex-encoded big endian chain trust
+    pub chain_trust: String,
+    /// Number of blocks between the tip and the point it forks off the main chain
+    pub branch_length: i64,
+    pub status: ChainTipStatus,
+}
+
+impl From<ChainTip> for ChainTipInfo {
+    fn from(tip: ChainTip) -> Self {
+        Self {
+            block_id: tip.block_id,
+            height: tip.height,
+            chain_trust: hex::encode(tip.chain_trust.to_be_bytes()),
+            branch_length: tip.branch_length.into(),
+            status: tip.status,
+        }
+    }
+}
+
+impl From<AddressHistoryEntry> for AddressOutputInfo {
+    fn from(entry: AddressHistoryEntry) -> Self {
+        Self {
+            outpoint: hex::encode(entry.outpoint().encode()),
+            output: hex::encode(entry.output().encode()),
+            block_height: entry.block_height(),
+            spent_by: entry.spent_by().map(|source_id| hex::encode(source_id.encode())),
+        }
+    }
+}
 
 #[rpc::rpc(server, namespace = "chainstate")]
 trait ChainstateRpc {
@@ -19,9 +130,14 @@ trait ChainstateRpc {
     #[method(name = "block_id_at_height")]
     async fn block_id_at_height(&self, height: BlockHeight) -> rpc::Result<Option<BlockId>>;
 
-    /// Submit a block to be included in the chain
+    /// Submit a block to be included in the chain. A block that fails validation is reported in
+    /// the result along with the reason, rather than as an error.
     #[method(name = "submit_block")]
-    async fn submit_block(&self, block_hex: String) -> rpc::Result<()>;
+    async fn submit_block(&self, block_hex: String) -> rpc::Result<SubmitBlockResult>;
+
+    /// Get the parameters of a new block on the best block, for external miners
+    #[method(name = "get_block_template")]
+    async fn get_block_template(&self) -> rpc::Result<BlockTemplateInfo>;
 
     /// Get block height in main chain
     #[method(name = "block_height_in_main_chain")]
@@ -33,6 +149,41 @@ trait ChainstateRpc {
     /// Get best block height in main chain
     #[method(name = "best_block_height")]
     async fn best_block_height(&self) -> rpc::Result<BlockHeight>;
+
+    /// Get a mainchain transaction along with its containing block. Requires the transaction index.
+    #[method(name = "get_transaction")]
+    async fn get_transaction(&self, tx_id: TxId) -> rpc::Result<Option<TransactionInfo>>;
+
+    /// Get the mainchain outputs paid to given address, oldest first. Skips `skip` outputs and
+    /// returns at most `limit` of them, capped at 1000. Requires the address index.
+    #[method(name = "address_history")]
+    async fn address_history(
+        &self,
+        address: String,
+        skip: usize,
+        limit: usize,
+    ) -> rpc::Result<Vec<AddressOutputInfo>>;
+
+    /// List all blocks no other block builds on, including the tips of forks
+    #[method(name = "chain_tips")]
+    async fn chain_tips(&self) -> rpc::Result<Vec<ChainTipInfo>>;
+
+    /// Get size and usage statistics of each storage column
+    #[method(name = "storage_stats")]
+    async fn storage_stats(&self) -> rpc::Result<chainstate_storage::Stats>;
+
+    /// Mark a block and its descendants as invalid, reorganizing away from it if needed
+    #[method(name = "invalidate_block")]
+    async fn invalidate_block(&self, block_id: BlockId) -> rpc::Result<()>;
+
+    /// Remove the invalid mark from a block and its ancestors and descendants
+    #[method(name = "reconsider_block")]
+    async fn reconsider_block(&self, block_id: BlockId) -> rpc::Result<()>;
+
+    /// Mine a block on the best block paying the block subsidy to given address, and submit it.
+    /// Only available on regtest and signet.
+    #[method(name = "generate_to_destination")]
+    async fn generate_to_destination(&self, address: String) -> rpc::Result<BlockId>;
 }
 
 #[async_trait::async_trait]
@@ -45,12 +196,38 @@ impl ChainstateRpcServer for super::ChainstateHandle {
         handle_error(self.call(move |this| this.get_block_id_from_height(&height)).await)
     }
 
-    async fn submit_block(&self, block_hex: String) -> rpc::Result<()> {
+    async fn submit_block(&self, block_hex: String) -> rpc::Result<SubmitBlockResult> {
         // TODO there should be a generic way of decoding SCALE-encoded hex json strings
         let block_data = hex::decode(block_hex).map_err(rpc::Error::to_call_error)?;
         let block = Block::decode(&mut &block_data[..]).map_err(rpc::Error::to_call_error)?;
-        let res = self.call_mut(move |this| this.process_block(block, BlockSource::Local)).await;
-        handle_error(res)
+        let block_id = block.get_id();
+        let res = self
+            .call_mut(move |this| {
+                this.process_block(block, BlockSource::Local)
+                    .and_then(|()| this.get_best_block_id())
+            })
+            .await
+            .map_err(rpc::Error::to_call_error)?;
+        match res {
+            Ok(best_block_id) => Ok(SubmitBlockResult {
+                is_tip: best_block_id == block_id,
+                block_id,
+                reject_reason: None,
+                ban_score: 0,
+            }),
+            Err(ChainstateError::ProcessBlockError(err)) => Ok(SubmitBlockResult {
+                block_id,
+                is_tip: false,
+                reject_reason: Some(err.to_string()),
+                ban_score: err.ban_score(),
+            }),
+            Err(err) => Err(rpc::Error::to_call_error(err)),
+        }
+    }
+
+    async fn get_block_template(&self) -> rpc::Result<BlockTemplateInfo> {
+        let template = handle_error(self.call(|this| this.get_block_template()).await)?;
+        Ok(template.into())
     }
 
     async fn block_height_in_main_chain(
@@ -63,6 +240,50 @@ impl ChainstateRpcServer for super::ChainstateHandle {
     async fn best_block_height(&self) -> rpc::Result<BlockHeight> {
         handle_error(self.call(move |this| this.get_best_block_height()).await)
     }
+
+    async fn get_transaction(&self, tx_id: TxId) -> rpc::Result<Option<TransactionInfo>> {
+        let res = handle_error(self.call(move |this| this.get_transaction(&tx_id)).await)?;
+        Ok(res.map(|(tx, block_id, block_height)| TransactionInfo {
+            transaction: hex::encode(tx.encode()),
+            block_id,
+            block_height,
+        }))
+    }
+
+    async fn address_history(
+        &self,
+        address: String,
+        skip: usize,
+        limit: usize,
+    ) -> rpc::Result<Vec<AddressOutputInfo>> {
+        let address: Address = address.parse().map_err(rpc::Error::to_call_error)?;
+        let limit = limit.min(MAX_ADDRESS_HISTORY_PAGE);
+        let res = self.call(move |this| this.get_address_history(&address, skip, limit)).await;
+        Ok(handle_error(res)?.into_iter().map(Into::into).collect())
+    }
+
+    async fn chain_tips(&self) -> rpc::Result<Vec<ChainTipInfo>> {
+        let tips = handle_error(self.call(|this| this.get_chain_tips()).await)?;
+        Ok(tips.into_iter().map(Into::into).collect())
+    }
+
+    async fn storage_stats(&self) -> rpc::Result<chainstate_storage::Stats> {
+        handle_error(self.call(|this| this.get_storage_stats()).await)
+    }
+
+    async fn invalidate_block(&self, block_id: BlockId) -> rpc::Result<()> {
+        handle_error(self.call_mut(move |this| this.invalidate_block(&block_id)).await)
+    }
+
+    async fn reconsider_block(&self, block_id: BlockId) -> rpc::Result<()> {
+        handle_error(self.call_mut(move |this| this.reconsider_block(&block_id)).await)
+    }
+
+    async fn generate_to_destination(&self, address: String) -> rpc::Result<BlockId> {
+        let address: Address = address.parse().map_err(rpc::Error::to_call_error)?;
+        let res = self.call_mut(move |this| this.generate_block(&address)).await;
+        Ok(handle_error(res)?.get_id())
+    }
 }
 
 fn handle_error<T>(e: Result<Result<T, ChainstateError>, CallError>) -> rpc::Result<T> {
@@ -72,18 +293,30 @@ fn handle_error<T>(e: Result<Result<T, ChainstateError>, CallError>) -> rpc::Res
 #[cfg(test)]
 mod test {
     use super::*;
+    use common::chain::block::{timestamp::BlockTimestamp, ConsensusData};
+    use common::chain::config::{create_regtest, create_unit_test_config};
+    use common::chain::ChainConfig;
+    use common::primitives::{time, Id, H256};
+    use crypto::key::{KeyKind, PrivateKey};
     use serde_json::Value;
     use std::{future::Future, sync::Arc};
 
+    fn new_address(cfg: &ChainConfig) -> String {
+        let (_, public_key) = PrivateKey::new(KeyKind::RistrettoSchnorr);
+        Address::from_public_key(cfg, &public_key).unwrap().get().to_owned()
+    }
+
     async fn with_chainstate<F: 'static + Send + Future<Output = ()>>(
+        cfg: ChainConfig,
         proc: impl 'static + Send + FnOnce(crate::ChainstateHandle) -> F,
     ) {
         let storage = chainstate_storage::Store::new_empty().unwrap();
-        let cfg = Arc::new(common::chain::config::create_unit_test_config());
+        let cfg = Arc::new(cfg);
         let mut man = subsystem::Manager::new("rpctest");
         let handle = man.add_subsystem(
             "chainstate",
-            crate::make_chainstate(cfg, storage, None, Default::default()).unwrap(),
+            crate::make_chainstate(cfg, Default::default(), storage, None, Default::default())
+                .unwrap(),
         );
         let _ = man.add_raw_subsystem(
             "test",
@@ -94,7 +327,8 @@ mod test {
 
     #[tokio::test]
     async fn rpc_requests() {
-        with_chainstate(|handle| async {
+        let address = new_address(&create_unit_test_config());
+        with_chainstate(create_unit_test_config(), |handle| async move {
             let rpc = handle.into_rpc();
 
             let res = rpc.call("chainstate_best_block_height", [(); 0]).await;
@@ -119,6 +353,114 @@ mod test {
 
             let res: rpc::Result<Value> = rpc.call("chainstate_block_id_at_height", [1u32]).await;
             assert!(matches!(res, Ok(Value::Null)));
+
+            let res: rpc::Result<Value> = rpc.call("chainstate_chain_tips", [(); 0]).await;
+            let tips = res.unwrap();
+            assert_eq!(tips.as_array().unwrap().len(), 1);
+            assert_eq!(tips[0]["block_id"], Value::String(genesis_hash.clone()));
+            assert_eq!(tips[0]["height"], 0);
+            assert_eq!(tips[0]["branch_length"], 0);
+            assert_eq!(tips[0]["status"], "active");
+
+            let res: rpc::Result<Value> = rpc.call("chainstate_storage_stats", [(); 0]).await;
+            let stats = res.unwrap();
+            assert_eq!(stats["DBBlock"]["key_count"], 1);
+            assert_eq!(stats["DBBlockByHeight"]["key_count"], 1);
+            assert!(stats["DBBlock"]["value_bytes"].as_u64().unwrap() > 0);
+
+            let res: rpc::Result<Value> =
+                rpc.call("chainstate_get_transaction", [&genesis_hash]).await;
+            assert!(res.is_err(), "the transaction index is disabled by default");
+
+            let res: rpc::Result<Value> = rpc
+                .call(
+                    "chainstate_address_history",
+                    (&genesis_hash, 0usize, 10usize),
+                )
+                .await;
+            assert!(res.is_err(), "not an address");
+
+            let res: rpc::Result<Value> =
+                rpc.call("chainstate_invalidate_block", [&genesis_hash]).await;
+            assert!(res.is_err());
+
+            let res: rpc::Result<Value> =
+                rpc.call("chainstate_generate_to_destination", [&address]).await;
+            assert!(res.is_err(), "not a test chain");
+
+            let res: rpc::Result<Value> = rpc.call("chainstate_get_block_template", [(); 0]).await;
+            assert!(res.is_err(), "no proof of work");
+        })
+        .await
+    }
+
+    #[tokio::test]
+    async fn generate_to_destination() {
+        let address = new_address(&create_regtest());
+        with_chainstate(create_regtest(), |handle| async move {
+            let rpc = handle.into_rpc();
+
+            for height in 1..=3 {
+                let block_id: Value =
+                    rpc.call("chainstate_generate_to_destination", [&address]).await.unwrap();
+                let best_block_id: Value =
+                    rpc.call("chainstate_best_block_id", [(); 0]).await.unwrap();
+                assert_eq!(best_block_id, block_id);
+                let best_height: Value =
+                    rpc.call("chainstate_best_block_height", [(); 0]).await.unwrap();
+                assert_eq!(best_height, Value::from(height));
+            }
+
+            let res: rpc::Result<Value> =
+                rpc.call("chainstate_generate_to_destination", ["not an address"]).await;
+            assert!(res.is_err());
+
+            let best_block_id: Value = rpc.call("chainstate_best_block_id", [(); 0]).await.unwrap();
+            let template: Value = rpc.call("chainstate_get_block_template", [(); 0]).await.unwrap();
+            assert_eq!(template["prev_block_id"], best_block_id);
+            assert_eq!(template["height"], 4);
+            assert!(template["min_timestamp"].as_u64() <= template["timestamp"].as_u64());
+            assert!(template["bits"].is_u64());
+            let reward = template["reward"].as_str().unwrap();
+            assert!(reward.parse::<u128>().is_ok());
+            assert_eq!(template["transactions"], Value::Array(vec![]));
+        })
+        .await
+    }
+
+    #[tokio::test]
+    async fn submit_block() {
+        let cfg = create_unit_test_config();
+        let genesis_id = cfg.genesis_block_id();
+        let new_block = |prev_block_id| {
+            let timestamp = BlockTimestamp::from_duration_since_epoch(time::get()).unwrap();
+            Block::new(vec![], Some(prev_block_id), timestamp, ConsensusData::None).unwrap()
+        };
+        let block = new_block(genesis_id.clone());
+        let orphan = new_block(Id::new(H256::zero()));
+
+        with_chainstate(cfg, |handle| async move {
+            let rpc = handle.into_rpc();
+
+            let res: Value = rpc
+                .call("chainstate_submit_block", [hex::encode(block.encode())])
+                .await
+                .unwrap();
+            let best_block_id: Value = rpc.call("chainstate_best_block_id", [(); 0]).await.unwrap();
+            assert_eq!(res["block_id"], best_block_id);
+            assert_eq!(res["is_tip"], true);
+            assert_eq!(res["reject_reason"], Value::Null);
+            assert_eq!(res["ban_score"], 0);
+
+            let res: Value = rpc
+                .call("chainstate_submit_block", [hex::encode(orphan.encode())])
+                .await
+                .unwrap();
+            assert_eq!(res["is_tip"], false);
+            assert!(res["reject_reason"].is_string());
+
+            let res: rpc::Result<Value> = rpc.call("chainstate_submit_block", ["not hex"]).await;
+            assert!(res.is_err());
         })
         .await
     }
@@ -1,6 +1,6 @@
 use self::pubkeyhash::PublicKeyHash;
-use crate::chain::ChainConfig;
-use crate::primitives::{encoding, Bech32Error, DecodedArbitraryDataFromBech32};
+use crate::chain::{ChainConfig, Destination};
+use crate::primitives::{encoding, Bech32Error, DecodedArbitraryDataFromBech32, Id, H256};
 use crypto::key::PublicKey;
 pub mod pubkeyhash;
 use serialization::Encode;
@@ -21,10 +21,14 @@ pub trait AddressableData<T: AsRef<[u8]>> {
     fn set_data(&mut self, data: &[u8]);
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
 pub enum AddressError {
+    #[error("Bech32 encoding error: {0}")]
     Bech32EncodingError(Bech32Error),
+    #[error("Invalid address prefix: {0}")]
     InvalidPrefix(String),
+    #[error("Address data is neither a public key hash nor a script hash")]
+    InvalidData,
 }
 
 impl From<Bech32Error> for AddressError {
@@ -84,6 +88,29 @@ impl Address {
     pub fn get(&self) -> &str {
         &self.address
     }
+
+    /// The destination of outputs paid to the address, told apart by the length of its data
+    pub fn destination(&self, cfg: &ChainConfig) -> Result<Destination, AddressError> {
+        let data = self.data(cfg)?;
+        if data.len() == H256::len_bytes() {
+            return Ok(Destination::ScriptHash(Id::new(H256::from_slice(&data))));
+        }
+        PublicKeyHash::try_from(data)
+            .map(Destination::Address)
+            .map_err(|_| AddressError::InvalidData)
+    }
+}
+
+impl std::str::FromStr for Address {
+    type Err = AddressError;
+
+    /// Parse a bech32m address. The chain it belongs to is checked once its data is extracted.
+    fn from_str(address: &str) -> Result<Self, Self::Err> {
+        encoding::decode(address)?;
+        Ok(Self {
+            address: address.to_owned(),
+        })
+    }
 }
 
 #[cfg(test)]
@@ -106,6 +133,41 @@ mod tests {
         assert_eq!(public_key_hash_restored, public_key_hash);
     }
 
+    #[test]
+    fn parse() {
+        let cfg = create_mainnet();
+        let (_priv_key, pub_key) = PrivateKey::new(KeyKind::RistrettoSchnorr);
+        let address = Address::from_public_key(&cfg, &pub_key).unwrap();
+        assert_eq!(address.get().parse(), Ok(address.clone()));
+        assert!("not an address".parse::<Address>().is_err());
+
+        let other_chain = Address::new_with_hrp("xyz", address.data(&cfg).unwrap()).unwrap();
+        let parsed: Address = other_chain.get().parse().unwrap();
+        assert!(parsed.data(&cfg).is_err());
+    }
+
+    #[test]
+    fn destination() {
+        let cfg = create_mainnet();
+        let (_priv_key, pub_key) = PrivateKey::new(KeyKind::RistrettoSchnorr);
+        let public_key_hash = PublicKeyHash::from(&pub_key);
+        let address = Address::from_public_key_hash(&cfg, &public_key_hash).unwrap();
+        assert_eq!(
+            address.destination(&cfg),
+            Ok(Destination::Address(public_key_hash))
+        );
+
+        let script_hash = H256::random();
+        let address = Address::new(&cfg, script_hash.as_bytes()).unwrap();
+        assert_eq!(
+            address.destination(&cfg),
+            Ok(Destination::ScriptHash(Id::new(script_hash)))
+        );
+
+        let address = Address::new(&cfg, [0u8; 5]).unwrap();
+        assert_eq!(address.destination(&cfg), Err(AddressError::InvalidData));
+    }
+
     #[test]
     fn ensure_cfg_and_with_hrp_compatiblity() {
         let cfg = create_mainnet();
@@ -1,10 +1,13 @@
 use super::emission_schedule::{self, *};
-use super::{create_mainnet_genesis, create_unit_test_genesis, ChainConfig, ChainType};
+use super::{
+    create_mainnet_genesis, create_testnet_genesis, create_unit_test_genesis, ChainConfig,
+    ChainType,
+};
 
 use crate::chain::{
     block::Block, ConsensusUpgrade, Destination, NetUpgrades, PoWChainConfig, UpgradeVersion,
 };
-use crate::primitives::{semver::SemVer, BlockDistance, BlockHeight, Idable};
+use crate::primitives::{semver::SemVer, BlockDistance, BlockHeight, Id, Idable};
 
 use std::collections::BTreeMap;
 use std::time::Duration;
@@ -13,7 +16,7 @@ impl ChainType {
     fn default_genesis_init(&self) -> GenesisBlockInit {
         match self {
             ChainType::Mainnet => GenesisBlockInit::Mainnet,
-            ChainType::Testnet => todo!("Testnet genesis"),
+            ChainType::Testnet => GenesisBlockInit::Testnet,
             ChainType::Regtest => GenesisBlockInit::TEST,
             ChainType::Signet => GenesisBlockInit::TEST,
         }
@@ -21,7 +24,7 @@ impl ChainType {
 
     fn default_net_upgrades(&self) -> NetUpgrades<UpgradeVersion> {
         match self {
-            ChainType::Mainnet | ChainType::Regtest => {
+            ChainType::Mainnet | ChainType::Testnet | ChainType::Regtest => {
                 let pow_config = PoWChainConfig::new(*self);
                 let upgrades = vec![
                     (
@@ -37,10 +40,16 @@ impl ChainType {
                 ];
                 NetUpgrades::initialize(upgrades).expect("net upgrades")
             }
-            ChainType::Testnet => todo!("Testnet upgrades"),
             ChainType::Signet => NetUpgrades::unit_tests(),
         }
     }
+
+    fn default_max_reorg_depth(&self) -> Option<BlockDistance> {
+        match self {
+            ChainType::Mainnet | ChainType::Testnet => Some(super::DEFAULT_MAX_REORG_DEPTH),
+            ChainType::Regtest | ChainType::Signet => None,
+        }
+    }
 }
 
 // Builder support types
@@ -56,6 +65,7 @@ enum EmissionScheduleInit {
 enum GenesisBlockInit {
     UnitTest { premine_destination: Destination },
     Mainnet,
+    Testnet,
     Custom(Block),
 }
 
@@ -84,6 +94,8 @@ pub struct Builder {
     net_upgrades: NetUpgrades<UpgradeVersion>,
     genesis_block: GenesisBlockInit,
     emission_schedule: EmissionScheduleInit,
+    height_checkpoint_data: BTreeMap<BlockHeight, Id<Block>>,
+    max_reorg_depth: Option<BlockDistance>,
 }
 
 impl Builder {
@@ -106,6 +118,8 @@ impl Builder {
             genesis_block: chain_type.default_genesis_init(),
             emission_schedule: EmissionScheduleInit::Mainnet,
             net_upgrades: chain_type.default_net_upgrades(),
+            height_checkpoint_data: BTreeMap::new(),
+            max_reorg_depth: chain_type.default_max_reorg_depth(),
         }
     }
 
@@ -114,6 +128,7 @@ impl Builder {
         Self::new(ChainType::Mainnet)
             .net_upgrades(NetUpgrades::unit_tests())
             .genesis_unittest(Destination::AnyoneCanSpend)
+            .max_reorg_depth(None)
     }
 
     /// Build the chain config
@@ -135,6 +150,8 @@ impl Builder {
             genesis_block,
             emission_schedule,
             net_upgrades,
+            height_checkpoint_data,
+            max_reorg_depth,
         } = self;
 
         let emission_schedule = match emission_schedule {
@@ -147,6 +164,7 @@ impl Builder {
 
         let genesis_block = match genesis_block {
             GenesisBlockInit::Mainnet => create_mainnet_genesis(),
+            GenesisBlockInit::Testnet => create_testnet_genesis(),
             GenesisBlockInit::Custom(genesis) => genesis,
             GenesisBlockInit::UnitTest {
                 premine_destination,
@@ -169,9 +187,10 @@ impl Builder {
             rpc_port,
             genesis_block_id: genesis_block.get_id(),
             genesis_block,
-            height_checkpoint_data: BTreeMap::new(),
+            height_checkpoint_data,
             emission_schedule,
             net_upgrades,
+            max_reorg_depth,
         }
     }
 }
@@ -204,6 +223,8 @@ impl Builder {
     builder_method!(max_block_size_with_standard_txs: usize);
     builder_method!(max_block_size_with_smart_contracts: usize);
     builder_method!(net_upgrades: NetUpgrades<UpgradeVersion>);
+    builder_method!(height_checkpoint_data: BTreeMap<BlockHeight, Id<Block>>);
+    builder_method!(max_reorg_depth: Option<BlockDistance>);
 
     /// Set the genesis block to be the unit test version
     pub fn genesis_unittest(mut self, premine_destination: Destination) -> Self {
@@ -219,6 +240,12 @@ impl Builder {
         self
     }
 
+    /// Set genesis block to be the testnet genesis
+    pub fn genesis_testnet(mut self) -> Self {
+        self.genesis_block = GenesisBlockInit::Testnet;
+        self
+    }
+
     /// Specify a custom genesis block
     pub fn genesis_custom(mut self, genesis: Block) -> Self {
         self.genesis_block = GenesisBlockInit::Custom(genesis);
@@ -1,8 +1,10 @@
 mod builder;
 pub mod emission_schedule;
+pub mod spec;
 
 pub use builder::Builder;
 pub use emission_schedule::{EmissionSchedule, EmissionScheduleTabular, Mlt};
+pub use spec::{ChainSpec, ChainSpecError};
 
 use hex::FromHex;
 
@@ -36,8 +38,10 @@ pub const DEFAULT_TARGET_BLOCK_SPACING: Duration = Duration::from_secs(120);
     strum::Display,
     strum::EnumVariantNames,
     strum::EnumString,
+    serde::Deserialize,
 )]
 #[strum(serialize_all = "kebab-case")]
+#[serde(rename_all = "kebab-case")]
 pub enum ChainType {
     Mainnet,
     Testnet,
@@ -85,6 +89,7 @@ pub struct ChainConfig {
     max_block_header_size: usize,
     max_block_size_with_standard_txs: usize,
     max_block_size_with_smart_contracts: usize,
+    max_reorg_depth: Option<BlockDistance>,
 }
 
 impl ChainConfig {
@@ -172,10 +177,17 @@ impl ChainConfig {
     pub const fn blockreward_maturity(&self) -> &BlockDistance {
         &self.blockreward_maturity
     }
+
+    /// The maximum number of main chain blocks a reorg may disconnect, if limited
+    pub fn max_reorg_depth(&self) -> Option<BlockDistance> {
+        self.max_reorg_depth
+    }
 }
 
 // If block time is 2 minutes (which is my goal eventually), then 500 is equivalent to 100 in bitcoin's 10 minutes.
 const MAINNET_BLOCKREWARD_MATURITY: BlockDistance = BlockDistance::new(500);
+// About a day and a half of blocks with 2 minute block time
+const DEFAULT_MAX_REORG_DEPTH: BlockDistance = BlockDistance::new(1000);
 // DSA allows us to have blocks up to 1mb
 const MAX_BLOCK_HEADER_SIZE: usize = 1024;
 const MAX_BLOCK_TXS_SIZE: usize = 524_288;
@@ -220,6 +232,44 @@ fn create_mainnet_genesis() -> Block {
     .expect("Error creating genesis block")
 }
 
+fn create_testnet_genesis() -> Block {
+    use crate::chain::transaction::{TxInput, TxOutput};
+
+    // The key is public on purpose, so that the testnet premine can be used to fund faucets
+    // Private key: "00806e1594a9fc91b28f35bc76943a1d88ad121a8aacaddff0530e78d3fe31492600"
+    // Public key:  "008018bcf5974ceba473ec6756623c03273a560cd06ed25f0fbb0e74e6320fc53f4d"
+    // Public key hash:  "56f3cd392b95c95f7d05f9d488f0e78f765ccc0a"
+    // Destination:  "0056f3cd392b95c95f7d05f9d488f0e78f765ccc0a"
+    let genesis_mint_pubkeyhash_hex_encoded = "0056f3cd392b95c95f7d05f9d488f0e78f765ccc0a";
+    let genesis_mint_pubkeyhash_encoded = Vec::from_hex(genesis_mint_pubkeyhash_hex_encoded)
+        .expect("Hex decoding of pubkeyhash shouldn't fail");
+    let genesis_mint_destination = <Destination as parity_scale_codec::DecodeAll>::decode_all(
+        &mut genesis_mint_pubkeyhash_encoded.as_slice(),
+    )
+    .expect("Decoding genesis mint destination shouldn't fail");
+
+    let genesis_message = b"Mintlayer testnet".to_vec();
+    let input = TxInput::new(
+        Id::<Transaction>::new(H256::zero()).into(),
+        0,
+        InputWitness::NoSignature(Some(genesis_message)),
+    );
+    let output = TxOutput::new(
+        Amount::from_atoms(100000000000000),
+        OutputPurpose::Transfer(genesis_mint_destination),
+    );
+    let tx = Transaction::new(0, vec![input], vec![output], 0)
+        .expect("Failed to create genesis coinbase transaction");
+
+    Block::new(
+        vec![tx],
+        None,
+        BlockTimestamp::from_int_seconds(1661990400),
+        ConsensusData::None,
+    )
+    .expect("Error creating genesis block")
+}
+
 fn create_unit_test_genesis(premine_destination: Destination) -> Block {
     use crate::chain::transaction::{TxInput, TxOutput};
 
@@ -250,6 +300,10 @@ pub fn create_mainnet() -> ChainConfig {
     Builder::new(ChainType::Mainnet).build()
 }
 
+pub fn create_testnet() -> ChainConfig {
+    Builder::new(ChainType::Testnet).build()
+}
+
 pub fn create_regtest() -> ChainConfig {
     Builder::new(ChainType::Regtest).build()
 }
@@ -258,6 +312,7 @@ pub fn create_unit_test_config() -> ChainConfig {
     Builder::new(ChainType::Mainnet)
         .net_upgrades(NetUpgrades::unit_tests())
         .genesis_unittest(Destination::AnyoneCanSpend)
+        .max_reorg_depth(None)
         .build()
 }
 
@@ -274,6 +329,37 @@ mod tests {
         assert_eq!(config.chain_type(), &ChainType::Mainnet);
     }
 
+    #[test]
+    fn testnet_creation() {
+        let config = create_testnet();
+
+        assert_eq!(config.chain_type(), &ChainType::Testnet);
+        assert_eq!(config.address_prefix(), "tmt");
+        assert_eq!(2, config.net_upgrades.len());
+        assert!(config.max_reorg_depth().is_some());
+        assert_ne!(config.magic_bytes(), create_mainnet().magic_bytes());
+        assert_ne!(
+            config.genesis_block_id(),
+            create_mainnet().genesis_block_id()
+        );
+    }
+
+    // Changing the genesis block of a public network would split it from existing nodes
+    #[test]
+    fn genesis_ids() {
+        use std::str::FromStr;
+
+        let expected_id = |hex: &str| Id::<Block>::new(H256::from_str(hex).unwrap());
+        assert_eq!(
+            create_mainnet().genesis_block_id(),
+            expected_id("f05f40ab8178122c3dbf0d4f0e84a270ae9ce9b65d0a021c3958d74a71316b26")
+        );
+        assert_eq!(
+            create_testnet().genesis_block_id(),
+            expected_id("4a4a81991f72f6102e50891d7ef9e5888ad501f179069983dc758a033201135a")
+        );
+    }
+
     #[test]
     fn chain_type_names() {
         use strum::VariantNames;
@@ -294,4 +380,15 @@ mod tests {
 
         assert_ne!(config1.magic_bytes(), config2.magic_bytes());
     }
+
+    #[test]
+    fn max_reorg_depth() {
+        assert!(create_mainnet().max_reorg_depth().is_some());
+        assert_eq!(create_regtest().max_reorg_depth(), None);
+        assert_eq!(create_unit_test_config().max_reorg_depth(), None);
+
+        let depth = Some(BlockDistance::new(10));
+        let config = Builder::new(ChainType::Regtest).max_reorg_depth(depth).build();
+        assert_eq!(config.max_reorg_depth(), depth);
+    }
 }
@@ -0,0 +1,826 @@
+//! Chain specification files
+//!
+//! A chain spec describes a custom chain, such as a private or development network, in TOML or
+//! JSON. It starts off with the defaults of given chain type and overrides the settings it lists,
+//! mapping onto the methods of [Builder]. Durations are in seconds and MLT amounts are strings,
+//! which may contain fractions. An example in TOML:
+//!
+//! ```toml
+//! chain_type = "regtest"
+//! address_prefix = "dmt"
+//! magic_bytes = "d1e2f3a4"
+//! target_block_spacing = 60
+//! blockreward_maturity = 10
+//! checkpoints = []
+//!
+//! [genesis]
+//! timestamp = 1661990400
+//! message = "Devnet genesis"
+//! premine = [{ destination = "anyone-can-spend", amount = "1000000" }]
+//!
+//! [emission_schedule]
+//! initial_supply = "1000000"
+//! initial_subsidy = "100"
+//! periods = [{ height = 100000, subsidy = "50" }, { height = 200000, subsidy = "0" }]
+//!
+//! [[net_upgrades]]
+//! height = 0
+//! consensus = "ignore-consensus"
+//!
+//! [[net_upgrades]]
+//! height = 1
+//! consensus = "pow"
+//! initial_difficulty = 545259519
+//! ```
+//!
+//! Genesis premine destinations are addresses using the chain's address prefix, or
+//! `"anyone-can-spend"`, and the premine may not exceed the initial supply of the emission
+//! schedule. The initial difficulty of a PoW upgrade is in the compact format and defaults to the
+//! PoW limit of the chain type. The reorg depth limit is turned off with
+//! `max_reorg_depth = "none"`.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use super::emission_schedule::{EmissionScheduleTabular, Mlt};
+use super::{Builder, ChainConfig, ChainType};
+use crate::address::{Address, AddressError};
+use crate::chain::block::timestamp::BlockTimestamp;
+use crate::chain::block::{Block, ConsensusData};
+use crate::chain::signature::inputsig::InputWitness;
+use crate::chain::transaction::{Destination, Transaction, TxInput, TxOutput};
+use crate::chain::{ConsensusUpgrade, NetUpgrades, OutputPurpose, PoWChainConfig, UpgradeVersion};
+use crate::primitives::id::{Id, H256};
+use crate::primitives::{Amount, BlockDistance, BlockHeight, Compact};
+
+/// The premine destination which needs no address
+const ANYONE_CAN_SPEND: &str = "anyone-can-spend";
+
+#[derive(Debug, thiserror::Error)]
+pub enum ChainSpecError {
+    #[error("Cannot read chain spec {0}: {1}")]
+    Io(PathBuf, std::io::Error),
+    #[error("Chain spec {0} is neither a .toml nor a .json file")]
+    UnknownFormat(PathBuf),
+    #[error("Invalid TOML chain spec: {0}")]
+    Toml(#[from] toml::de::Error),
+    #[error("Invalid JSON chain spec: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("Invalid magic_bytes '{0}', expected 4 hex-encoded bytes")]
+    MagicBytes(String),
+    #[error("target_block_spacing must be at least one second")]
+    ZeroBlockSpacing,
+    #[error("Invalid MLT amount '{1}' in {0}")]
+    Amount(String, String),
+    #[error("{0} is {1}, which is out of range")]
+    OutOfRange(&'static str, u64),
+    #[error("The total supply of emission_schedule overflows at height {0}")]
+    SupplyOverflow(BlockHeight),
+    #[error("The total genesis.premine overflows")]
+    PremineOverflow,
+    #[error("The total genesis.premine {0} exceeds the initial supply {1}")]
+    PremineExceedsSupply(Mlt, Mlt),
+    #[error("Invalid destination '{1}' in {0}: {2}")]
+    Destination(String, String, AddressError),
+    #[error("Multiple {0} at height {1}")]
+    DuplicateHeight(&'static str, BlockHeight),
+    #[error("net_upgrades must not be empty")]
+    NoNetUpgrades,
+    #[error("net_upgrades must contain an upgrade at height 0")]
+    NoGenesisUpgrade,
+    #[error("Invalid net_upgrades: {0}")]
+    NetUpgrades(anyhow::Error),
+    #[error("net_upgrades[{0}] sets initial_difficulty, which only applies to PoW")]
+    UnexpectedDifficulty(usize),
+}
+
+/// Custom chain configuration, see the [module documentation](self) for the format
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ChainSpec {
+    chain_type: ChainType,
+    address_prefix: Option<String>,
+    magic_bytes: Option<String>,
+    rpc_port: Option<u16>,
+    p2p_port: Option<u16>,
+    blockreward_maturity: Option<u64>,
+    max_future_block_time_offset: Option<u64>,
+    target_block_spacing: Option<u64>,
+    coin_decimals: Option<u8>,
+    max_block_header_size: Option<usize>,
+    max_block_size_with_standard_txs: Option<usize>,
+    max_block_size_with_smart_contracts: Option<usize>,
+    max_reorg_depth: Option<MaxReorgDepthSpec>,
+    genesis: Option<GenesisSpec>,
+    emission_schedule: Option<EmissionScheduleSpec>,
+    net_upgrades: Option<Vec<NetUpgradeSpec>>,
+    checkpoints: Option<Vec<CheckpointSpec>>,
+}
+
+#[derive(Debug, Clone, Copy, serde::Deserialize)]
+#[serde(untagged)]
+enum MaxReorgDepthSpec {
+    Blocks(u64),
+    Off(OffSpec),
+}
+
+#[derive(Debug, Clone, Copy, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+enum OffSpec {
+    None,
+}
+
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct GenesisSpec {
+    timestamp: u32,
+    #[serde(default)]
+    message: String,
+    premine: Vec<PremineSpec>,
+}
+
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PremineSpec {
+    destination: String,
+    amount: String,
+}
+
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct EmissionScheduleSpec {
+    initial_supply: String,
+    initial_subsidy: String,
+    #[serde(default)]
+    periods: Vec<EmissionPeriodSpec>,
+}
+
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct EmissionPeriodSpec {
+    height: u64,
+    subsidy: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+enum ConsensusSpec {
+    IgnoreConsensus,
+    Pow,
+    Pos,
+    Dsa,
+}
+
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct NetUpgradeSpec {
+    height: u64,
+    consensus: ConsensusSpec,
+    initial_difficulty: Option<u32>,
+}
+
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CheckpointSpec {
+    height: u64,
+    block_id: Id<Block>,
+}
+
+impl ChainSpec {
+    pub fn from_toml_str(spec: &str) -> Result<Self, ChainSpecError> {
+        Ok(toml::from_str(spec)?)
+    }
+
+    pub fn from_json_str(spec: &str) -> Result<Self, ChainSpecError> {
+        Ok(serde_json::from_str(spec)?)
+    }
+
+    /// Load a chain spec, in the format given by the file extension
+    pub fn from_file(path: &Path) -> Result<Self, ChainSpecError> {
+        let from_str = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("toml") => Self::from_toml_str,
+            Some("json") => Self::from_json_str,
+            _ => return Err(ChainSpecError::UnknownFormat(path.to_owned())),
+        };
+        let spec = std::fs::read_to_string(path)
+            .map_err(|err| ChainSpecError::Io(path.to_owned(), err))?;
+        from_str(&spec)
+    }
+
+    /// A chain config builder with the settings of this spec applied
+    pub fn into_builder(self) -> Result<Builder, ChainSpecError> {
+        let Self {
+            chain_type,
+            address_prefix,
+            magic_bytes,
+            rpc_port,
+            p2p_port,
+            blockreward_maturity,
+            max_future_block_time_offset,
+            target_block_spacing,
+            coin_decimals,
+            max_block_header_size,
+            max_block_size_with_standard_txs,
+            max_block_size_with_smart_contracts,
+            max_reorg_depth,
+            genesis,
+            emission_schedule,
+            net_upgrades,
+            checkpoints,
+        } = self;
+
+        let mut builder = Builder::new(chain_type);
+        if let Some(address_prefix) = address_prefix {
+            builder = builder.address_prefix(address_prefix);
+        }
+        if let Some(magic_bytes) = magic_bytes {
+            builder = builder.magic_bytes(parse_magic_bytes(&magic_bytes)?);
+        }
+        if let Some(rpc_port) = rpc_port {
+            builder = builder.rpc_port(rpc_port);
+        }
+        if let Some(p2p_port) = p2p_port {
+            builder = builder.p2p_port(p2p_port);
+        }
+        if let Some(maturity) = blockreward_maturity {
+            builder =
+                builder.blockreward_maturity(parse_distance("blockreward_maturity", maturity)?);
+        }
+        if let Some(offset) = max_future_block_time_offset {
+            builder = builder.max_future_block_time_offset(Duration::from_secs(offset));
+        }
+        if let Some(spacing) = target_block_spacing {
+            if spacing == 0 {
+                return Err(ChainSpecError::ZeroBlockSpacing);
+            }
+            builder = builder.target_block_spacing(Duration::from_secs(spacing));
+        }
+        if let Some(coin_decimals) = coin_decimals {
+            builder = builder.coin_decimals(coin_decimals);
+        }
+        if let Some(size) = max_block_header_size {
+            builder = builder.max_block_header_size(size);
+        }
+        if let Some(size) = max_block_size_with_standard_txs {
+            builder = builder.max_block_size_with_standard_txs(size);
+        }
+        if let Some(size) = max_block_size_with_smart_contracts {
+            builder = builder.max_block_size_with_smart_contracts(size);
+        }
+        if let Some(depth) = max_reorg_depth {
+            let depth = match depth {
+                MaxReorgDepthSpec::Blocks(depth) => Some(parse_distance("max_reorg_depth", depth)?),
+                MaxReorgDepthSpec::Off(OffSpec::None) => None,
+            };
+            builder = builder.max_reorg_depth(depth);
+        }
+        if let Some(emission_schedule) = emission_schedule {
+            builder = builder.emission_schedule_tabular(emission_schedule.into_table()?);
+        }
+        if let Some(net_upgrades) = net_upgrades {
+            builder = builder.net_upgrades(net_upgrades_from_spec(chain_type, net_upgrades)?);
+        }
+        if let Some(checkpoints) = checkpoints {
+            builder = builder.height_checkpoint_data(checkpoints_from_spec(checkpoints)?);
+        }
+        if let Some(genesis) = genesis {
+            // Premine addresses are checked against the final address prefix
+            let genesis = genesis.into_block(&builder.clone().build())?;
+            builder = builder.genesis_custom(genesis);
+        }
+
+        Ok(builder)
+    }
+}
+
+impl GenesisSpec {
+    fn into_block(self, chain_config: &ChainConfig) -> Result<Block, ChainSpecError> {
+        let outputs = self
+            .premine
+            .into_iter()
+            .enumerate()
+            .map(|(index, premine)| {
+                let field = format!("genesis.premine[{index}]");
+                let destination = parse_destination(
+                    chain_config,
+                    format!("{field}.destination"),
+                    premine.destination,
+                )?;
+                let amount = parse_mlt(format!("{field}.amount"), premine.amount)?;
+                Ok(TxOutput::new(
+                    amount.to_amount_atoms(),
+                    OutputPurpose::Transfer(destination),
+                ))
+            })
+            .collect::<Result<Vec<_>, ChainSpecError>>()?;
+
+        let premine = outputs
+            .iter()
+            .map(|output| output.value())
+            .sum::<Option<Amount>>()
+            .ok_or(ChainSpecError::PremineOverflow)?;
+        let premine = Mlt::from_atoms(premine.into_atoms());
+        let initial_supply = chain_config.emission_schedule().amount_at(BlockHeight::zero());
+        if premine > initial_supply {
+            return Err(ChainSpecError::PremineExceedsSupply(
+                premine,
+                initial_supply,
+            ));
+        }
+
+        let input = TxInput::new(
+            Id::<Transaction>::new(H256::zero()).into(),
+            0,
+            InputWitness::NoSignature(Some(self.message.into_bytes())),
+        );
+        let tx = Transaction::new(0, vec![input], outputs, 0)
+            .expect("Failed to create genesis coinbase transaction");
+
+        Ok(Block::new(
+            vec![tx],
+            None,
+            BlockTimestamp::from_int_seconds(self.timestamp),
+            ConsensusData::None,
+        )
+        .expect("Error creating genesis block"))
+    }
+}
+
+impl EmissionScheduleSpec {
+    fn into_table(self) -> Result<EmissionScheduleTabular, ChainSpecError> {
+        let initial_supply = parse_mlt(
+            "emission_schedule.initial_supply".to_owned(),
+            self.initial_supply,
+        )?;
+        let initial_subsidy = parse_mlt(
+            "emission_schedule.initial_subsidy".to_owned(),
+            self.initial_subsidy,
+        )?;
+
+        let mut periods = BTreeMap::new();
+        for (index, period) in self.periods.into_iter().enumerate() {
+            let height = BlockHeight::new(period.height);
+            let field = format!("emission_schedule.periods[{index}].subsidy");
+            let subsidy = parse_mlt(field, period.subsidy)?;
+            if periods.insert(height, subsidy).is_some() {
+                return Err(ChainSpecError::DuplicateHeight(
+                    "emission_schedule.periods",
+                    height,
+                ));
+            }
+        }
+
+        // The supply at the start of each period has to be representable
+        let mut supply = initial_supply;
+        let mut subsidy = initial_subsidy;
+        let mut start = BlockHeight::zero();
+        for (&height, &next_subsidy) in &periods {
+            let n_blocks = u64::from(height) - u64::from(start);
+            supply = (subsidy * n_blocks as u128)
+                .and_then(|period_supply| supply + period_supply)
+                .ok_or(ChainSpecError::SupplyOverflow(height))?;
+            subsidy = next_subsidy;
+            start = height;
+        }
+
+        Ok(EmissionScheduleTabular::new(
+            initial_supply,
+            initial_subsidy,
+            periods,
+        ))
+    }
+}
+
+fn parse_magic_bytes(magic_bytes: &str) -> Result<[u8; 4], ChainSpecError> {
+    hex::decode(magic_bytes)
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| ChainSpecError::MagicBytes(magic_bytes.to_owned()))
+}
+
+fn parse_distance(field: &'static str, distance: u64) -> Result<BlockDistance, ChainSpecError> {
+    distance
+        .try_into()
+        .map(BlockDistance::new)
+        .map_err(|_| ChainSpecError::OutOfRange(field, distance))
+}
+
+fn parse_mlt(field: String, amount: String) -> Result<Mlt, ChainSpecError> {
+    amount.parse().map_err(|_| ChainSpecError::Amount(field, amount))
+}
+
+fn parse_destination(
+    chain_config: &ChainConfig,
+    field: String,
+    destination: String,
+) -> Result<Destination, ChainSpecError> {
+    if destination == ANYONE_CAN_SPEND {
+        return Ok(Destination::AnyoneCanSpend);
+    }
+    destination
+        .parse::<Address>()
+        .and_then(|address| address.destination(chain_config))
+        .map_err(|err| ChainSpecError::Destination(field, destination, err))
+}
+
+fn net_upgrades_from_spec(
+    chain_type: ChainType,
+    net_upgrades: Vec<NetUpgradeSpec>,
+) -> Result<NetUpgrades<UpgradeVersion>, ChainSpecError> {
+    let mut upgrades = BTreeMap::new();
+    for (index, upgrade) in net_upgrades.into_iter().enumerate() {
+        let consensus = match (upgrade.consensus, upgrade.initial_difficulty) {
+            (ConsensusSpec::Pow, initial_difficulty) => ConsensusUpgrade::PoW {
+                initial_difficulty: initial_difficulty
+                    .map(Compact)
+                    .unwrap_or_else(|| PoWChainConfig::new(chain_type).limit().into()),
+            },
+            (_, Some(_)) => return Err(ChainSpecError::UnexpectedDifficulty(index)),
+            (ConsensusSpec::IgnoreConsensus, None) => ConsensusUpgrade::IgnoreConsensus,
+            (ConsensusSpec::Pos, None) => ConsensusUpgrade::PoS,
+            (ConsensusSpec::Dsa, None) => ConsensusUpgrade::DSA,
+        };
+        let height = BlockHeight::new(upgrade.height);
+        let version = UpgradeVersion::ConsensusUpgrade(consensus);
+        if upgrades.insert(height, version).is_some() {
+            return Err(ChainSpecError::DuplicateHeight("net_upgrades", height));
+        }
+    }
+
+    match upgrades.keys().next() {
+        None => return Err(ChainSpecError::NoNetUpgrades),
+        Some(height) if *height != BlockHeight::zero() => {
+            return Err(ChainSpecError::NoGenesisUpgrade)
+        }
+        Some(_) => (),
+    }
+    NetUpgrades::initialize(upgrades.into_iter().collect()).map_err(ChainSpecError::NetUpgrades)
+}
+
+fn checkpoints_from_spec(
+    checkpoints: Vec<CheckpointSpec>,
+) -> Result<BTreeMap<BlockHeight, Id<Block>>, ChainSpecError> {
+    let mut result = BTreeMap::new();
+    for checkpoint in checkpoints {
+        let height = BlockHeight::new(checkpoint.height);
+        if result.insert(height, checkpoint.block_id).is_some() {
+            return Err(ChainSpecError::DuplicateHeight("checkpoints", height));
+        }
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::address::pubkeyhash::PublicKeyHash;
+    use crate::primitives::{Amount, Idable};
+    use crypto::key::{KeyKind, PrivateKey};
+
+    const DEVNET_TOML: &str = r#"
+        chain_type = "regtest"
+        address_prefix = "dmt"
+        magic_bytes = "d1e2f3a4"
+        target_block_spacing = 60
+        blockreward_maturity = 10
+        checkpoints = []
+
+        [genesis]
+        timestamp = 1661990400
+        message = "Devnet genesis"
+        premine = [{ destination = "anyone-can-spend", amount = "1000000" }]
+
+        [emission_schedule]
+        initial_supply = "1000000"
+        initial_subsidy = "100"
+        periods = [{ height = 100000, subsidy = "50" }, { height = 200000, subsidy = "0" }]
+
+        [[net_upgrades]]
+        height = 0
+        consensus = "ignore-consensus"
+
+        [[net_upgrades]]
+        height = 1
+        consensus = "pow"
+        initial_difficulty = 545259519
+    "#;
+
+    const DEVNET_JSON: &str = r#"{
+        "chain_type": "regtest",
+        "address_prefix": "dmt",
+        "magic_bytes": "d1e2f3a4",
+        "target_block_spacing": 60,
+        "blockreward_maturity": 10,
+        "checkpoints": [],
+        "genesis": {
+            "timestamp": 1661990400,
+            "message": "Devnet genesis",
+            "premine": [{ "destination": "anyone-can-spend", "amount": "1000000" }]
+        },
+        "emission_schedule": {
+            "initial_supply": "1000000",
+            "initial_subsidy": "100",
+            "periods": [{ "height": 100000, "subsidy": "50" }, { "height": 200000, "subsidy": "0" }]
+        },
+        "net_upgrades": [
+            { "height": 0, "consensus": "ignore-consensus" },
+            { "height": 1, "consensus": "pow", "initial_difficulty": 545259519 }
+        ]
+    }"#;
+
+    fn build(spec: &str) -> Result<ChainConfig, ChainSpecError> {
+        Ok(ChainSpec::from_toml_str(spec)?.into_builder()?.build())
+    }
+
+    #[test]
+    fn devnet() {
+        let config = build(DEVNET_TOML).unwrap();
+
+        assert_eq!(config.chain_type(), &ChainType::Regtest);
+        assert_eq!(config.address_prefix(), "dmt");
+        assert_eq!(config.magic_bytes(), &[0xd1, 0xe2, 0xf3, 0xa4]);
+        assert_eq!(config.target_block_spacing(), &Duration::from_secs(60));
+        assert_eq!(config.blockreward_maturity(), &BlockDistance::new(10));
+        assert!(config.height_checkpoints().is_empty());
+
+        // Settings not in the spec keep the defaults of the chain type
+        let regtest = super::super::create_regtest();
+        assert_eq!(config.max_reorg_depth(), regtest.max_reorg_depth());
+        assert_eq!(config.p2p_port(), regtest.p2p_port());
+
+        let genesis_tx = &config.genesis_block().transactions()[0];
+        assert_eq!(
+            config.genesis_block().timestamp(),
+            BlockTimestamp::from_int_seconds(1661990400)
+        );
+        assert_eq!(
+            genesis_tx.outputs(),
+            &vec![TxOutput::new(
+                Mlt::from_mlt(1_000_000).to_amount_atoms(),
+                OutputPurpose::Transfer(Destination::AnyoneCanSpend),
+            )]
+        );
+
+        let subsidy = |height| config.block_subsidy_at_height(&BlockHeight::new(height));
+        assert_eq!(subsidy(1), Mlt::from_mlt(100).to_amount_atoms());
+        assert_eq!(subsidy(150_000), Mlt::from_mlt(50).to_amount_atoms());
+        assert_eq!(subsidy(250_000), Amount::from_atoms(0));
+
+        assert_eq!(config.net_upgrade().len(), 2);
+        assert_eq!(
+            config.net_upgrade().consensus_status(BlockHeight::new(1)),
+            crate::chain::RequiredConsensus::PoW(crate::chain::PoWStatus::Threshold {
+                initial_difficulty: Compact(545259519)
+            })
+        );
+    }
+
+    #[test]
+    fn json_matches_toml() {
+        let from_toml = build(DEVNET_TOML).unwrap();
+        let from_json =
+            ChainSpec::from_json_str(DEVNET_JSON).unwrap().into_builder().unwrap().build();
+
+        assert_eq!(from_json.genesis_block_id(), from_toml.genesis_block_id());
+        assert_eq!(from_json.magic_bytes(), from_toml.magic_bytes());
+        assert_eq!(from_json.net_upgrade(), from_toml.net_upgrade());
+        assert_eq!(
+            from_json.block_subsidy_at_height(&BlockHeight::new(150_000)),
+            from_toml.block_subsidy_at_height(&BlockHeight::new(150_000))
+        );
+    }
+
+    #[test]
+    fn minimal() {
+        let config = build(r#"chain_type = "testnet""#).unwrap();
+        let testnet = super::super::create_testnet();
+        assert_eq!(config.genesis_block_id(), testnet.genesis_block_id());
+        assert_eq!(config.net_upgrade(), testnet.net_upgrade());
+    }
+
+    #[test]
+    fn premine_to_address() {
+        let (_, public_key) = PrivateKey::new(KeyKind::RistrettoSchnorr);
+        let public_key_hash = PublicKeyHash::from(&public_key);
+        let prefixed = |prefix: &str| {
+            let config = Builder::new(ChainType::Regtest).address_prefix(prefix.to_owned()).build();
+            Address::from_public_key_hash(&config, &public_key_hash).unwrap()
+        };
+        let spec = |address: &Address| {
+            format!(
+                r#"
+                chain_type = "regtest"
+                address_prefix = "dmt"
+                [genesis]
+                timestamp = 0
+                premine = [{{ destination = "{}", amount = "0.5" }}]
+                "#,
+                address.get()
+            )
+        };
+
+        let config = build(&spec(&prefixed("dmt"))).unwrap();
+        assert_eq!(
+            config.genesis_block().transactions()[0].outputs(),
+            &vec![TxOutput::new(
+                Amount::from_atoms(Mlt::ATOMS_PER_MLT / 2),
+                OutputPurpose::Transfer(Destination::Address(public_key_hash)),
+            )]
+        );
+
+        let address = prefixed("rmt");
+        assert!(matches!(
+            build(&spec(&address)),
+            Err(ChainSpecError::Destination(field, destination, AddressError::InvalidPrefix(_)))
+                if field == "genesis.premine[0].destination" && destination == address.get()
+        ));
+    }
+
+    #[test]
+    fn invalid_specs() {
+        let error = |spec: &str| build(spec).unwrap_err();
+
+        assert!(matches!(error(""), ChainSpecError::Toml(_)));
+        assert!(matches!(
+            error(r#"chain_type = "devnet""#),
+            ChainSpecError::Toml(_)
+        ));
+        let unknown_field = error("chain_type = \"regtest\"\nblock_spacing = 60");
+        assert!(unknown_field.to_string().contains("unknown field `block_spacing`"));
+
+        assert!(matches!(
+            error("chain_type = \"regtest\"\nmagic_bytes = \"d1e2f3\""),
+            ChainSpecError::MagicBytes(bytes) if bytes == "d1e2f3"
+        ));
+        assert!(matches!(
+            error("chain_type = \"regtest\"\ntarget_block_spacing = 0"),
+            ChainSpecError::ZeroBlockSpacing
+        ));
+        assert!(matches!(
+            error(
+                r#"
+                chain_type = "regtest"
+                [emission_schedule]
+                initial_supply = "100"
+                initial_subsidy = "1"
+                periods = [{ height = 10, subsidy = "x" }]
+                "#
+            ),
+            ChainSpecError::Amount(field, amount)
+                if field == "emission_schedule.periods[0].subsidy" && amount == "x"
+        ));
+        assert!(matches!(
+            error(
+                r#"
+                chain_type = "regtest"
+                [emission_schedule]
+                initial_supply = "100"
+                initial_subsidy = "1"
+                periods = [{ height = 10, subsidy = "0.5" }, { height = 10, subsidy = "0" }]
+                "#
+            ),
+            ChainSpecError::DuplicateHeight("emission_schedule.periods", height)
+                if height == BlockHeight::new(10)
+        ));
+        assert!(matches!(
+            error(
+                r#"
+                chain_type = "regtest"
+                net_upgrades = [{ height = 1, consensus = "pow" }]
+                "#
+            ),
+            ChainSpecError::NoGenesisUpgrade
+        ));
+        assert!(matches!(
+            error(
+                r#"
+                chain_type = "regtest"
+                net_upgrades = [
+                    { height = 0, consensus = "ignore-consensus" },
+                    { height = 1, consensus = "pos", initial_difficulty = 1 },
+                ]
+                "#
+            ),
+            ChainSpecError::UnexpectedDifficulty(1)
+        ));
+        assert!(matches!(
+            error(
+                r#"
+                chain_type = "regtest"
+                net_upgrades = [
+                    { height = 0, consensus = "ignore-consensus" },
+                    { height = 0, consensus = "pow" },
+                ]
+                "#
+            ),
+            ChainSpecError::DuplicateHeight("net_upgrades", _)
+        ));
+        assert!(matches!(
+            error("chain_type = \"regtest\"\nnet_upgrades = []"),
+            ChainSpecError::NoNetUpgrades
+        ));
+        assert!(matches!(
+            ChainSpec::from_json_str(r#"{ "chain_type": "regtest", "rpc_port": -1 }"#),
+            Err(ChainSpecError::Json(_))
+        ));
+        assert!(matches!(
+            error("chain_type = \"regtest\"\nblockreward_maturity = -1"),
+            ChainSpecError::Toml(_)
+        ));
+        assert!(matches!(
+            ChainSpec::from_json_str(
+                r#"{ "chain_type": "regtest", "max_reorg_depth": 18446744073709551615 }"#
+            )
+            .unwrap()
+            .into_builder(),
+            Err(ChainSpecError::OutOfRange("max_reorg_depth", u64::MAX))
+        ));
+        assert!(matches!(
+            error(
+                r#"
+                chain_type = "regtest"
+                [emission_schedule]
+                initial_supply = "100"
+                initial_subsidy = "1000000000"
+                periods = [{ height = 9223372036854775807, subsidy = "0" }]
+                "#
+            ),
+            ChainSpecError::SupplyOverflow(height) if height == BlockHeight::new(i64::MAX as u64)
+        ));
+        assert!(matches!(
+            error(
+                r#"
+                chain_type = "regtest"
+                [genesis]
+                timestamp = 0
+                premine = [{ destination = "anyone-can-spend", amount = "1000001" }]
+                [emission_schedule]
+                initial_supply = "1000000"
+                initial_subsidy = "1"
+                "#
+            ),
+            ChainSpecError::PremineExceedsSupply(premine, supply)
+                if premine == Mlt::from_mlt(1_000_001) && supply == Mlt::from_mlt(1_000_000)
+        ));
+    }
+
+    #[test]
+    fn max_reorg_depth() {
+        let config = build("chain_type = \"regtest\"\nmax_reorg_depth = 5").unwrap();
+        assert_eq!(config.max_reorg_depth(), Some(BlockDistance::new(5)));
+        let config = build("chain_type = \"regtest\"\nmax_reorg_depth = \"none\"").unwrap();
+        assert_eq!(config.max_reorg_depth(), None);
+        assert!(matches!(
+            build("chain_type = \"regtest\"\nmax_reorg_depth = \"off\""),
+            Err(ChainSpecError::Toml(_))
+        ));
+    }
+
+    #[test]
+    fn checkpoints() {
+        let genesis_id = super::super::create_regtest().genesis_block().get_id();
+        let spec = format!(
+            "chain_type = \"regtest\"\ncheckpoints = [{{ height = 0, block_id = \"{:x}\" }}]",
+            genesis_id.get()
+        );
+        let config = build(&spec).unwrap();
+        assert_eq!(
+            config.height_checkpoints(),
+            &BTreeMap::from([(BlockHeight::new(0), genesis_id)])
+        );
+    }
+
+    #[test]
+    fn spec_files() {
+        let dir = std::env::temp_dir().join(format!("chain-spec-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let toml_path = dir.join("devnet.toml");
+        std::fs::write(&toml_path, DEVNET_TOML).unwrap();
+        let json_path = dir.join("devnet.json");
+        std::fs::write(&json_path, DEVNET_JSON).unwrap();
+        let txt_path = dir.join("devnet.txt");
+        std::fs::write(&txt_path, DEVNET_TOML).unwrap();
+
+        let genesis_id = |path: &Path| {
+            ChainSpec::from_file(path)
+                .unwrap()
+                .into_builder()
+                .unwrap()
+                .build()
+                .genesis_block_id()
+        };
+        assert_eq!(genesis_id(&toml_path), genesis_id(&json_path));
+        assert!(matches!(
+            ChainSpec::from_file(&txt_path),
+            Err(ChainSpecError::UnknownFormat(path)) if path == txt_path
+        ));
+        assert!(matches!(
+            ChainSpec::from_file(&dir.join("missing.toml")),
+            Err(ChainSpecError::Io(..))
+        ));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
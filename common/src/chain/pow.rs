@@ -93,7 +93,7 @@ pub(crate) const fn limit(chain_type: ChainType) -> Uint256 {
 #[cfg(test)]
 mod tests {
     use crate::chain::block::ConsensusData;
-    use crate::chain::config::{create_mainnet, ChainType};
+    use crate::chain::config::{create_mainnet, create_testnet, ChainType};
     use crate::chain::pow::{allow_min_difficulty_blocks, limit, no_retargeting};
     use crate::Uint256;
 
@@ -136,4 +136,17 @@ mod tests {
             assert!(mainnet_cfg.limit() < target_max);
         }
     }
+
+    #[test]
+    fn check_testnet_powconfig() {
+        let cfg = create_testnet();
+        let testnet_cfg = cfg.get_proof_of_work_config();
+
+        // Retargets like mainnet, but allows min difficulty blocks when block production stalls
+        assert!(!testnet_cfg.no_retargeting());
+        assert!(testnet_cfg.allow_min_difficulty_blocks());
+        assert_eq!(testnet_cfg.limit(), limit(ChainType::Mainnet));
+
+        assert_eq!(&ConsensusData::None, cfg.genesis_block().consensus_data());
+    }
 }
@@ -4,7 +4,7 @@ use crate::chain::config::ChainType;
 use crate::chain::pow::limit;
 use crate::primitives::{BlockDistance, BlockHeight, Compact};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct NetUpgrades<T>(Vec<(BlockHeight, T)>);
 
 impl NetUpgrades<UpgradeVersion> {
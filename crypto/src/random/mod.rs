@@ -6,7 +6,7 @@ pub mod distributions {
 }
 
 pub mod rngs {
-    pub use rand::rngs::OsRng;
+    pub use rand::rngs::{OsRng, StdRng};
 }
 
 pub fn make_true_rng() -> impl rand::Rng + rand::CryptoRng {
@@ -15,6 +15,15 @@ pub struct Options {
     #[clap(long, value_name = "PATH")]
     pub log_path: Option<PathBuf>,
 
+    /// Directory to store the blockchain data in. If not given, the data is kept in memory only.
+    #[clap(long, value_name = "PATH")]
+    pub datadir: Option<PathBuf>,
+
+    /// Restore the blockchain data from given backup file into the data directory before starting.
+    /// The data directory must be empty.
+    #[clap(long, value_name = "FILE", requires = "datadir")]
+    pub restore_backup: Option<PathBuf>,
+
     /// Address to bind RPC to
     #[clap(long, value_name = "ADDR", default_value = "127.0.0.1:3030")]
     pub rpc_addr: SocketAddr,
@@ -23,9 +32,27 @@ pub struct Options {
     #[clap(long, possible_values = ChainType::VARIANTS, default_value = "mainnet")]
     pub net: ChainType,
 
+    /// Load a custom chain configuration from given TOML or JSON chain spec file, instead of
+    /// using the predefined chain selected by `--net`
+    #[clap(long, value_name = "FILE", conflicts_with = "net")]
+    pub chain_spec: Option<PathBuf>,
+
     /// Address to bind P2P to
     #[clap(long, value_name = "ADDR", default_value = "/ip6/::1/tcp/3031")]
     pub p2p_addr: String,
+
+    /// Maintain an index of the mainchain transactions, required by the `get_transaction` RPC.
+    /// The setting cannot be changed once the data directory has been initialized. Data
+    /// directories created without the index, including those upgraded from older versions, can
+    /// only get it by resyncing the chain into an empty `--datadir`.
+    #[clap(long)]
+    pub tx_index: bool,
+
+    /// Maintain an index of the outputs paid to each address, required by the `address_history`
+    /// RPC. Like the transaction index, the setting cannot be changed once the data directory has
+    /// been initialized, and the index can only be added by resyncing into an empty `--datadir`.
+    #[clap(long)]
+    pub address_index: bool,
 }
 
 impl Options {
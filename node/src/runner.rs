@@ -2,8 +2,9 @@
 
 use crate::options::Options;
 use chainstate::rpc::ChainstateRpcServer;
-use common::chain::config::ChainType;
+use common::chain::config::{ChainSpec, ChainType};
 use p2p::rpc::P2pRpcServer;
+use std::path::{Component, Path, PathBuf};
 use std::sync::Arc;
 
 #[derive(Debug, Ord, PartialOrd, PartialEq, Eq, Clone, Copy, thiserror::Error)]
@@ -12,16 +13,31 @@ enum Error {
     UnsupportedChain(ChainType),
 }
 
+/// Directory inside the data directory where backups requested over RPC are written
+const BACKUP_DIR_NAME: &str = "backups";
+
 /// Initialize the node, giving caller the opportunity to add more subsystems before start.
 pub async fn initialize(opts: Options) -> anyhow::Result<subsystem::Manager> {
     // Initialize storage and chain configuration
-    let storage = chainstate_storage::Store::new_empty()?;
+    let storage = match (&opts.datadir, &opts.restore_backup) {
+        (Some(path), Some(backup)) => {
+            logging::log::info!("Restoring blockchain data from {}", backup.display());
+            chainstate_storage::Store::restore(backup, path)?
+        }
+        (Some(path), None) => chainstate_storage::Store::open(path)?,
+        (None, _) => chainstate_storage::Store::new_empty()?,
+    };
 
     // Chain configuration
-    let chain_config = match opts.net {
-        ChainType::Mainnet => Arc::new(common::chain::config::create_mainnet()),
-        ChainType::Regtest => Arc::new(common::chain::config::create_regtest()),
-        chain_ty => return Err(Error::UnsupportedChain(chain_ty).into()),
+    let chain_config = match (&opts.chain_spec, opts.net) {
+        (Some(path), _) => {
+            logging::log::info!("Loading chain spec from {}", path.display());
+            Arc::new(ChainSpec::from_file(path)?.into_builder()?.build())
+        }
+        (None, ChainType::Mainnet) => Arc::new(common::chain::config::create_mainnet()),
+        (None, ChainType::Testnet) => Arc::new(common::chain::config::create_testnet()),
+        (None, ChainType::Regtest) => Arc::new(common::chain::config::create_regtest()),
+        (None, chain_ty) => return Err(Error::UnsupportedChain(chain_ty).into()),
     };
 
     // INITIALIZE SUBSYSTEMS
@@ -34,6 +50,10 @@ pub async fn initialize(opts: Options) -> anyhow::Result<subsystem::Manager> {
         "chainstate",
         chainstate::make_chainstate(
             Arc::clone(&chain_config),
+            chainstate::ChainstateConfig {
+                tx_index_enabled: opts.tx_index,
+                address_index_enabled: opts.address_index,
+            },
             storage.clone(),
             None,
             Default::default(),
@@ -57,7 +77,14 @@ pub async fn initialize(opts: Options) -> anyhow::Result<subsystem::Manager> {
         "rpc",
         rpc::Builder::new(opts.rpc_addr)
             .register(chainstate.clone().into_rpc())
-            .register(NodeRpc::new(manager.make_shutdown_trigger()).into_rpc())
+            .register(
+                NodeRpc::new(
+                    manager.make_shutdown_trigger(),
+                    storage,
+                    opts.datadir.map(|datadir| datadir.join(BACKUP_DIR_NAME)),
+                )
+                .into_rpc(),
+            )
             .register(p2p.clone().into_rpc())
             .build()
             .await?,
@@ -83,15 +110,53 @@ trait NodeRpc {
     /// Get node software version
     #[method(name = "version")]
     fn version(&self) -> rpc::Result<String>;
+
+    /// Write a backup of the blockchain data into a new file with given name, placed in the
+    /// `backups` directory inside the node's data directory
+    #[method(name = "backup")]
+    fn backup(&self, name: String) -> rpc::Result<()>;
+}
+
+#[derive(Debug, thiserror::Error)]
+enum BackupError {
+    #[error("Backups can only be made by a node with a data directory")]
+    NoDataDir,
+    #[error("Invalid backup file name '{0}', expected a plain file name")]
+    InvalidName(String),
+    #[error("Cannot create the backup directory: {0}")]
+    CreateDir(std::io::Error),
 }
 
 struct NodeRpc {
     shutdown_trigger: subsystem::manager::ShutdownTrigger,
+    storage: chainstate_storage::Store,
+    backup_dir: Option<PathBuf>,
 }
 
 impl NodeRpc {
-    fn new(shutdown_trigger: subsystem::manager::ShutdownTrigger) -> Self {
-        Self { shutdown_trigger }
+    fn new(
+        shutdown_trigger: subsystem::manager::ShutdownTrigger,
+        storage: chainstate_storage::Store,
+        backup_dir: Option<PathBuf>,
+    ) -> Self {
+        Self {
+            shutdown_trigger,
+            storage,
+            backup_dir,
+        }
+    }
+
+    // Resolve a backup file name inside the backup directory, which is created if needed.
+    // Anything but a single plain path component could point outside of the directory.
+    fn backup_path(&self, name: &str) -> Result<PathBuf, BackupError> {
+        let backup_dir = self.backup_dir.as_ref().ok_or(BackupError::NoDataDir)?;
+        let mut components = Path::new(name).components();
+        let file_name = match (components.next(), components.next()) {
+            (Some(Component::Normal(file_name)), None) => file_name,
+            _ => return Err(BackupError::InvalidName(name.to_owned())),
+        };
+        std::fs::create_dir_all(backup_dir).map_err(BackupError::CreateDir)?;
+        Ok(backup_dir.join(file_name))
     }
 }
 
@@ -104,4 +169,10 @@ impl NodeRpcServer for NodeRpc {
     fn version(&self) -> rpc::Result<String> {
         Ok(env!("CARGO_PKG_VERSION").into())
     }
+
+    fn backup(&self, name: String) -> rpc::Result<()> {
+        let path = self.backup_path(&name).map_err(rpc::Error::to_call_error)?;
+        logging::log::info!("Writing blockchain data backup to {}", path.display());
+        self.storage.backup(path).map_err(rpc::Error::to_call_error)
+    }
 }
@@ -29,7 +29,11 @@ use crate::{
 use chainstate::{
     ban_score::BanScore,
     chainstate_interface, BlockError,
-    ChainstateError::{FailedToInitializeChainstate, FailedToReadProperty, ProcessBlockError},
+    ChainstateError::{
+        BlockGenerationFailed, FailedToInitializeChainstate, FailedToReadProperty,
+        ProcessBlockError,
+    },
+    ChainstateEvent, EventRecvError,
 };
 use common::{
     chain::{block::Block, ChainConfig},
@@ -90,29 +94,15 @@ where
     }
 
     /// Subscribe to events
-    async fn subscribe_to_events(&mut self) -> crate::Result<mpsc::Receiver<Id<Block>>> {
-        let (tx, rx) = mpsc::channel(CHANNEL_SIZE);
-
-        let subscribe_func =
-            Arc::new(
-                move |chainstate_event: chainstate::ChainstateEvent| match chainstate_event {
-                    chainstate::ChainstateEvent::NewTip(block_id, _) => {
-                        futures::executor::block_on(async {
-                            if let Err(e) = tx.send(block_id).await {
-                                log::error!("PubSubMessageHandler closed: {:?}", e)
-                            }
-                        });
-                    }
-                },
-            );
-
-        self.chainstate_handle
-            .call_mut(|this| this.subscribe_to_events(subscribe_func))
+    async fn subscribe_to_events(&mut self) -> crate::Result<chainstate::EventReceiver> {
+        let event_rx = self
+            .chainstate_handle
+            .call_mut(|this| this.subscribe_to_event_stream(CHANNEL_SIZE))
             .await
             .map_err(|_| P2pError::SubsystemFailure)?;
 
         self.pubsub_handle.subscribe(&self.topics).await?;
-        Ok(rx)
+        Ok(event_rx)
     }
 
     /// Process block announcement from the network
@@ -171,9 +161,28 @@ where
                 err @ BlockError::BlockProofCalculationError(_) => {
                     (ValidationResult::Reject, err.ban_score())
                 }
+                err @ BlockError::BlockMarkedInvalid(_) => {
+                    (ValidationResult::Reject, err.ban_score())
+                }
+                err @ BlockError::InvalidAncestor(_, _) => {
+                    (ValidationResult::Reject, err.ban_score())
+                }
+                err @ BlockError::BlockNotFound(_) => (ValidationResult::Ignore, err.ban_score()),
+                err @ BlockError::InvalidateGenesis => (ValidationResult::Ignore, err.ban_score()),
+                err @ BlockError::BlockQueryError(_) => (ValidationResult::Ignore, err.ban_score()),
+                err @ BlockError::CheckpointMismatch(_, _, _) => {
+                    (ValidationResult::Reject, err.ban_score())
+                }
+                err @ BlockError::ForkBelowCheckpoint(_, _) => {
+                    (ValidationResult::Reject, err.ban_score())
+                }
+                err @ BlockError::ReorgTooDeep(_, _, _) => {
+                    (ValidationResult::Reject, err.ban_score())
+                }
             },
             Err(FailedToInitializeChainstate(_)) => (ValidationResult::Ignore, 0),
             Err(FailedToReadProperty(_)) => (ValidationResult::Ignore, 0),
+            Err(BlockGenerationFailed(_)) => (ValidationResult::Ignore, 0),
         };
 
         if score > 0 {
@@ -216,6 +225,17 @@ where
         }
     }
 
+    /// Announce the block with given ID, which chainstate reported as its new tip
+    async fn announce_tip(&mut self, block_id: Id<Block>) -> crate::Result<()> {
+        match self.chainstate_handle.call(move |this| this.get_block(block_id)).await?? {
+            Some(block) => self.announce_block(block).await,
+            None => {
+                log::error!("CRITICAL: best block not available");
+                Ok(())
+            }
+        }
+    }
+
     /// Run `PubSubMessageHandler` event loop
     pub async fn run(&mut self) -> crate::Result<void::Void> {
         match self.rx_pubsub.recv().await {
@@ -226,7 +246,7 @@ where
         }
 
         // subscribe to chainstate events and pubsub topics
-        let mut block_rx = self.subscribe_to_events().await?;
+        let mut event_rx = self.subscribe_to_events().await?;
 
         loop {
             tokio::select! {
@@ -244,13 +264,21 @@ where
                         }
                     }
                 },
-                block_id = block_rx.recv().fuse() => {
-                    let block_id = block_id.ok_or(P2pError::ChannelClosed)?;
-
-                    match self.chainstate_handle.call(|this| this.get_block(block_id)).await?? {
-                        Some(block) => self.announce_block(block).await?,
-                        None => log::error!("CRITICAL: best block not available"),
+                event = event_rx.recv().fuse() => match event {
+                    Ok(ChainstateEvent::NewTip(block_id, _)) => self.announce_tip(block_id).await?,
+                    Ok(_) => {}
+                    Err(EventRecvError::Lagged(count)) => {
+                        // The dropped events may include tip changes, announce the current
+                        // tip instead
+                        log::warn!(
+                            "PubSubMessageHandler lagged behind, {} chainstate events dropped",
+                            count
+                        );
+                        let best_block_id =
+                            self.chainstate_handle.call(|this| this.get_best_block_id()).await??;
+                        self.announce_tip(best_block_id).await?;
                     }
+                    Err(EventRecvError::Closed) => return Err(P2pError::ChannelClosed),
                 }
             }
         }
@@ -53,7 +53,7 @@ where
     let mut man = subsystem::Manager::new("TODO");
     let handle = man.add_subsystem(
         "consensus",
-        make_chainstate(cfg, storage, None, Default::default()).unwrap(),
+        make_chainstate(cfg, Default::default(), storage, None, Default::default()).unwrap(),
     );
     tokio::spawn(async move { man.main().await });
 
@@ -1,76 +1,281 @@
+use crate::persistent::{self, Journal};
 use crate::schema::{self, Schema};
+use crate::stats::{Counters, SizeChange, Sizes, Stats};
+use crate::traits::MapIter;
 use crate::Data;
 use common::sync;
-use std::collections::BTreeMap;
+use serialization::{Decode, Encode};
+use std::collections::{BTreeMap, BTreeSet};
+use std::iter::Peekable;
+use std::ops::RangeBounds;
 
 // These store the data
 type StoreMapSingle = BTreeMap<Data, Data>;
-type StoreMapMulti = BTreeMap<Data, Vec<Data>>;
+type StoreMapMulti = BTreeMap<Data, BTreeSet<Data>>;
 
+#[derive(Clone)]
 pub enum StoreMap {
     Single(StoreMapSingle),
     Multi(StoreMapMulti),
 }
 
 // Set of store maps, one per db index.
-type StoreMapSet = BTreeMap<&'static str, StoreMap>;
+pub(crate) type StoreMapSet = BTreeMap<&'static str, StoreMap>;
+
+// Immutable version of the store maps as of some commit. Each map is shared between consecutive
+// versions until it is modified.
+type Snapshot = sync::Arc<BTreeMap<&'static str, sync::Arc<StoreMap>>>;
+
+// Get mutable access to shared data, cloning it first unless this is the only reference to it
+fn make_mut<T: Clone>(arc: &mut sync::Arc<T>) -> &mut T {
+    if sync::Arc::get_mut(arc).is_none() {
+        *arc = sync::Arc::new(T::clone(arc));
+    }
+    sync::Arc::get_mut(arc).expect("reference to be unique")
+}
+
+impl StoreMap {
+    // Get the size of the map contents
+    fn sizes(&self) -> Sizes {
+        let mut sizes = Sizes::default();
+        match self {
+            StoreMap::Single(store) => {
+                store.iter().for_each(|(key, val)| sizes.add_entry(key, val.len()));
+            }
+            StoreMap::Multi(store) => store.iter().for_each(|(key, vals)| {
+                sizes.add_entry(key, set_value_bytes(vals));
+            }),
+        }
+        sizes
+    }
+
+    // Express the full contents of the map as a delta to be applied to an empty map
+    pub(crate) fn to_delta(&self) -> DeltaMap {
+        match self {
+            StoreMap::Single(store) => DeltaMap::Single(
+                store.iter().map(|(key, val)| (key.clone(), Some(val.clone()))).collect(),
+            ),
+            StoreMap::Multi(store) => DeltaMap::Multi(
+                store
+                    .iter()
+                    .map(|(key, vals)| (key.clone(), SetDelta::Set(vals.clone())))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+// Total size of the values in a multi-map entry
+fn set_value_bytes(vals: &BTreeSet<Data>) -> usize {
+    vals.iter().map(Vec::len).sum()
+}
 
 // These store changes to the data that could be commited or discarded
 type DeltaMapSingle = BTreeMap<Data, Option<Data>>;
 
-// Vector delta
-#[allow(unused)]
-enum VecDelta<T> {
-    Set(Vec<T>),
-    Modify { add: Vec<T>, del: Vec<T> },
+// Set delta
+#[derive(Encode, Decode)]
+pub(crate) enum SetDelta<T: Ord> {
+    // Replace the original set with given one
+    Set(BTreeSet<T>),
+    // Add and remove given elements to/from the original set. The two sets are kept disjoint.
+    Modify { add: BTreeSet<T>, del: BTreeSet<T> },
 }
 
-type DeltaMapMulti = BTreeMap<Data, VecDelta<Data>>;
+impl<T: Ord> Default for SetDelta<T> {
+    fn default() -> Self {
+        Self::Modify {
+            add: BTreeSet::new(),
+            del: BTreeSet::new(),
+        }
+    }
+}
+
+impl<T: Ord> SetDelta<T> {
+    // Record insertion of an element
+    fn insert(&mut self, elem: T) {
+        match self {
+            Self::Set(set) => {
+                set.insert(elem);
+            }
+            Self::Modify { add, del } => {
+                del.remove(&elem);
+                add.insert(elem);
+            }
+        }
+    }
 
-enum DeltaMap {
+    // Record removal of an element
+    fn remove(&mut self, elem: T) {
+        match self {
+            Self::Set(set) => {
+                set.remove(&elem);
+            }
+            Self::Modify { add, del } => {
+                add.remove(&elem);
+                del.insert(elem);
+            }
+        }
+    }
+
+    // Apply the delta to given set
+    fn apply_to(self, orig: &mut BTreeSet<T>) {
+        match self {
+            Self::Set(set) => *orig = set,
+            Self::Modify { mut add, del } => {
+                orig.retain(|elem| !del.contains(elem));
+                orig.append(&mut add);
+            }
+        }
+    }
+}
+
+impl SetDelta<Data> {
+    // Get the result of applying the delta to given set, in order
+    fn view<'a>(&'a self, orig: Option<&'a BTreeSet<Data>>) -> crate::traits::ValuesIter<'a> {
+        let orig = orig.into_iter().flatten();
+        match self {
+            Self::Set(set) => Box::new(set.iter().map(AsRef::as_ref)),
+            Self::Modify { add, del } => {
+                let result: BTreeSet<&[u8]> = orig
+                    .filter(|val| !del.contains(*val))
+                    .chain(add.iter())
+                    .map(AsRef::as_ref)
+                    .collect();
+                Box::new(result.into_iter())
+            }
+        }
+    }
+}
+
+type DeltaMapMulti = BTreeMap<Data, SetDelta<Data>>;
+
+#[derive(Encode, Decode)]
+pub(crate) enum DeltaMap {
     Single(DeltaMapSingle),
     Multi(DeltaMapMulti),
 }
 
 // Set of delta maps, one per db index.
-type DeltaMapSet = BTreeMap<&'static str, DeltaMap>;
+pub(crate) type DeltaMapSet = BTreeMap<&'static str, DeltaMap>;
 
 impl DeltaMap {
-    // Apply given delta to the store map
-    fn apply_to(self, store: &mut StoreMap) {
+    // Check whether the delta contains no changes
+    pub(crate) fn is_empty(&self) -> bool {
+        match self {
+            DeltaMap::Single(delta) => delta.is_empty(),
+            DeltaMap::Multi(delta) => delta.is_empty(),
+        }
+    }
+
+    // Check whether the delta can be applied to given store map
+    pub(crate) fn is_compatible_with(&self, store: &StoreMap) -> bool {
+        matches!(
+            (self, store),
+            (DeltaMap::Single(_), StoreMap::Single(_)) | (DeltaMap::Multi(_), StoreMap::Multi(_))
+        )
+    }
+
+    // Apply given delta to the store map, returning how the map contents changed
+    pub(crate) fn apply_to(self, store: &mut StoreMap) -> SizeChange {
+        let mut change = SizeChange::default();
         match (self, store) {
             (DeltaMap::Single(delta), StoreMap::Single(store)) => {
                 delta.into_iter().for_each(|(key, val)| {
+                    if let Some(orig) = store.get(&key) {
+                        change.removed.add_entry(&key, orig.len());
+                    }
                     match val {
-                        Some(val) => store.insert(key, val),
-                        None => store.remove(&key),
-                    };
+                        Some(val) => {
+                            change.added.add_entry(&key, val.len());
+                            store.insert(key, val);
+                        }
+                        None => {
+                            store.remove(&key);
+                        }
+                    }
                 })
             }
-            (DeltaMap::Multi(_delta), StoreMap::Multi(_store)) => {
-                todo!("Key-multivalue map not implemented")
+            (DeltaMap::Multi(delta), StoreMap::Multi(store)) => {
+                delta.into_iter().for_each(|(key, vals_delta)| {
+                    let mut vals = store.remove(&key).unwrap_or_default();
+                    if !vals.is_empty() {
+                        change.removed.add_entry(&key, set_value_bytes(&vals));
+                    }
+                    vals_delta.apply_to(&mut vals);
+                    if !vals.is_empty() {
+                        change.added.add_entry(&key, set_value_bytes(&vals));
+                        store.insert(key, vals);
+                    }
+                })
             }
             _ => unreachable!("Map type mismatch"),
         }
+        change
+    }
+}
+
+// Data held by the store
+struct StoreState {
+    // The most recently committed version of the key-value maps
+    snapshot: sync::Mutex<Snapshot>,
+    // Log of committed changes on disk, present if the store is persistent. The lock is held by
+    // read-write transactions for their whole duration so there is at most one at a time.
+    journal: sync::Mutex<Option<Journal>>,
+    // Operation counters for each map
+    counters: BTreeMap<&'static str, Counters>,
+}
+
+impl StoreState {
+    fn new(maps: StoreMapSet, journal: Option<Journal>) -> Self {
+        let counters = maps.iter().map(|(name, map)| (*name, Counters::new(map.sizes()))).collect();
+        let maps = maps.into_iter().map(|(name, map)| (name, sync::Arc::new(map))).collect();
+        Self {
+            snapshot: sync::Mutex::new(sync::Arc::new(maps)),
+            journal: sync::Mutex::new(journal),
+            counters,
+        }
+    }
+
+    fn counters(&self, name: &str) -> &Counters {
+        self.counters.get(name).expect("Counters present for each map")
+    }
+
+    fn snapshot(&self) -> Snapshot {
+        sync::Arc::clone(&self.snapshot.lock().expect("Mutex locked by a crashed thread"))
     }
 }
 
 /// Store is a collection of key-(multi)value maps
+///
+/// All the data is kept in memory. A store created using [Store::open] is additionally backed by
+/// a directory on disk. All changes committed to such store are persisted and loaded again next
+/// time the store is opened.
+///
+/// Transactions are snapshot-isolated. A read-only transaction sees the store as of the last
+/// commit before it started and never waits for a read-write transaction in progress. Read-write
+/// transactions are executed one at a time. A commit does not modify data in use by readers. If
+/// readers still use the maps being modified, it makes a copy of them and then swaps it in.
 pub struct Store<Sch: Schema> {
-    maps: sync::Arc<sync::RwLock<StoreMapSet>>,
+    state: sync::Arc<StoreState>,
     _phantom: std::marker::PhantomData<fn() -> Sch>,
 }
 
 impl<Sch: Schema> Clone for Store<Sch> {
     fn clone(&self) -> Self {
         Self {
-            maps: sync::Arc::clone(&self.maps),
+            state: sync::Arc::clone(&self.state),
             _phantom: Default::default(),
         }
     }
 }
 
-impl<Sch: 'static + Schema> crate::traits::Backend<Sch> for Store<Sch> {}
+impl<Sch: 'static + Schema> crate::traits::Backend<Sch> for Store<Sch> {
+    fn stats(&self) -> crate::Result<Stats> {
+        let stats = self.state.counters.iter().map(|(name, counters)| (*name, counters.stats()));
+        Ok(stats.collect())
+    }
+}
 
 pub trait InitStore: Schema {
     fn init() -> BTreeMap<&'static str, StoreMap>;
@@ -82,26 +287,84 @@ impl InitStore for () {
     }
 }
 
-impl<DBIdx: schema::DBIndex, Rest: InitStore> InitStore for (DBIdx, Rest) {
+impl<DBIdx, Rest> InitStore for (DBIdx, Rest)
+where
+    DBIdx: schema::DBIndex,
+    DBIdx::Kind: InitMap,
+    Rest: InitStore,
+{
     fn init() -> BTreeMap<&'static str, StoreMap> {
         let mut map = Rest::init();
-        // TODO support multi
-        let orig = map.insert(DBIdx::NAME, StoreMap::Single(BTreeMap::new()));
+        let orig = map.insert(DBIdx::NAME, DBIdx::Kind::init());
         assert!(orig.is_none(), "DB index names are not unique");
         map
     }
 }
 
+/// Map kinds that can be used in the store
+pub trait InitMap: schema::MapKind {
+    fn init() -> StoreMap;
+}
+
+impl InitMap for schema::Single {
+    fn init() -> StoreMap {
+        StoreMap::Single(BTreeMap::new())
+    }
+}
+
+impl InitMap for schema::Multi {
+    fn init() -> StoreMap {
+        StoreMap::Multi(BTreeMap::new())
+    }
+}
+
 impl<Sch: InitStore> Store<Sch> {
-    /// New empty store
+    /// New empty in-memory store
     pub fn new() -> Self {
+        Self::from_state(StoreState::new(Sch::init(), None))
+    }
+
+    /// Open a persistent store in given directory, creating it if it does not exist.
+    ///
+    /// The directory must not be used by any other store at the same time.
+    pub fn open(path: impl AsRef<std::path::Path>) -> crate::Result<Self> {
+        let mut maps = Sch::init();
+        let journal = Journal::open(path.as_ref(), &mut maps)?;
+        Ok(Self::from_state(StoreState::new(maps, Some(journal))))
+    }
+
+    /// Set up a persistent store in given directory from a backup made using [Store::backup].
+    ///
+    /// The store can be opened using [Store::open] afterwards. Any store previously contained in
+    /// the directory is replaced. The directory must not be in use by another store.
+    pub fn restore(
+        backup: impl AsRef<std::path::Path>,
+        path: impl AsRef<std::path::Path>,
+    ) -> crate::Result<()> {
+        persistent::restore(backup.as_ref(), path.as_ref(), &mut Sch::init())
+    }
+
+    fn from_state(state: StoreState) -> Self {
         Self {
-            maps: sync::Arc::new(sync::RwLock::new(Sch::init())),
+            state: sync::Arc::new(state),
             _phantom: Default::default(),
         }
     }
 }
 
+impl<Sch: Schema> Store<Sch> {
+    /// Write a snapshot of the store contents into given file, which must not exist yet.
+    ///
+    /// The snapshot captures the store as seen by a single read-only transaction, so writers are
+    /// not held off while the backup is in progress. Works for both in-memory and persistent
+    /// stores.
+    pub fn backup(&self, path: impl AsRef<std::path::Path>) -> crate::Result<()> {
+        let maps = self.state.snapshot();
+        let snapshot = persistent::snapshot(maps.iter().map(|(name, map)| (*name, &**map)));
+        persistent::write_backup(path.as_ref(), &snapshot)
+    }
+}
+
 impl<'tx, Sch: 'static + Schema> crate::traits::Transactional<'tx, Sch> for Store<Sch> {
     type TransactionRo = TransactionRo<'tx, Sch>;
     type TransactionRw = TransactionRw<'tx, Sch>;
@@ -122,17 +385,20 @@ impl<Sch: InitStore> Default for Store<Sch> {
 }
 
 /// Store read-only transaction.
-pub struct TransactionRo<'st, Sch> {
-    store: sync::RwLockReadGuard<'st, StoreMapSet>,
+///
+/// Holds a snapshot of the store taken when the transaction started.
+pub struct TransactionRo<'st, Sch: Schema> {
+    state: &'st StoreState,
+    maps: Snapshot,
     _phantom: std::marker::PhantomData<fn() -> Sch>,
 }
 
 impl<'st, Sch: Schema> TransactionRo<'st, Sch> {
     // Start a transaction on given store
     fn start(store: &'st Store<Sch>) -> Self {
-        let store = store.maps.read().expect("Mutex locked by a crashed thread");
         Self {
-            store,
+            state: &*store.state,
+            maps: store.state.snapshot(),
             _phantom: Default::default(),
         }
     }
@@ -140,10 +406,19 @@ impl<'st, Sch: Schema> TransactionRo<'st, Sch> {
 
 impl<'st, 'm, Sch: Schema> crate::traits::GetMapRef<'m, Sch> for TransactionRo<'st, Sch> {
     type MapRef = SingleMapView<'m>;
+    type MultiMapRef = MultiMapView<'m>;
 
     fn get<'tx: 'm, DBIdx: schema::DBIndex, I>(&'tx self) -> Self::MapRef {
-        if let Some(StoreMap::Single(store)) = self.store.get(DBIdx::NAME) {
-            SingleMapView::new(store)
+        if let Some(StoreMap::Single(store)) = self.maps.get(DBIdx::NAME).map(AsRef::as_ref) {
+            SingleMapView::new(store, self.state.counters(DBIdx::NAME))
+        } else {
+            panic!("Unexpected map kind")
+        }
+    }
+
+    fn get_multi<'tx: 'm, DBIdx: schema::DBIndex, I>(&'tx self) -> Self::MultiMapRef {
+        if let Some(StoreMap::Multi(store)) = self.maps.get(DBIdx::NAME).map(AsRef::as_ref) {
+            MultiMapView::new(store, self.state.counters(DBIdx::NAME))
         } else {
             panic!("Unexpected map kind")
         }
@@ -152,11 +427,15 @@ impl<'st, 'm, Sch: Schema> crate::traits::GetMapRef<'m, Sch> for TransactionRo<'
 
 /// Store read/write transaction.
 ///
-/// Contains a pointer to the original store and a set of changes to it. If the transaction is
+/// Contains a snapshot of the original store and a set of changes to it. If the transaction is
 /// commited, the changes are flushed to the store. If the transaction is aborted, the changes are
 /// discarded.
 pub struct TransactionRw<'st, Sch: Schema> {
-    store: sync::RwLockWriteGuard<'st, StoreMapSet>,
+    state: &'st StoreState,
+    // Held for the duration of the transaction to keep other writers out
+    journal: sync::MutexGuard<'st, Option<Journal>>,
+    // No other writer can commit while this transaction is running, so this is the latest version
+    maps: Snapshot,
     delta: DeltaMapSet,
     _phantom: std::marker::PhantomData<fn() -> Sch>,
 }
@@ -164,11 +443,13 @@ pub struct TransactionRw<'st, Sch: Schema> {
 impl<'st, Sch: Schema> TransactionRw<'st, Sch> {
     // Start a transaction on given store
     fn start(store: &'st Store<Sch>) -> Self {
-        let store = store.maps.write().expect("Mutex locked by a crashed thread");
-        let delta = store
+        let state = &*store.state;
+        let journal = state.journal.lock().expect("Mutex locked by a crashed thread");
+        let maps = state.snapshot();
+        let delta = maps
             .iter()
             .map(|(&k, v)| {
-                let dm = match v {
+                let dm = match v.as_ref() {
                     StoreMap::Single(_) => DeltaMap::Single(Default::default()),
                     StoreMap::Multi(_) => DeltaMap::Multi(Default::default()),
                 };
@@ -177,7 +458,9 @@ impl<'st, Sch: Schema> TransactionRw<'st, Sch> {
             .collect();
         let _phantom = Default::default();
         Self {
-            store,
+            state,
+            journal,
+            maps,
             delta,
             _phantom,
         }
@@ -186,11 +469,27 @@ impl<'st, Sch: Schema> TransactionRw<'st, Sch> {
 
 impl<'st, 'm, Sch: Schema> crate::traits::GetMapRef<'m, Sch> for TransactionRw<'st, Sch> {
     type MapRef = SingleMapRef<'m>;
+    type MultiMapRef = MultiMapRef<'m>;
 
     fn get<'tx: 'm, DBIdx: schema::DBIndex, I>(&'tx self) -> Self::MapRef {
-        match (self.store.get(DBIdx::NAME), self.delta.get(DBIdx::NAME)) {
+        match (
+            self.maps.get(DBIdx::NAME).map(AsRef::as_ref),
+            self.delta.get(DBIdx::NAME),
+        ) {
             (Some(StoreMap::Single(store)), Some(DeltaMap::Single(delta))) => {
-                SingleMapRef::new(store, delta)
+                SingleMapRef::new(store, delta, self.state.counters(DBIdx::NAME))
+            }
+            _ => panic!("Unexpected map kind"),
+        }
+    }
+
+    fn get_multi<'tx: 'm, DBIdx: schema::DBIndex, I>(&'tx self) -> Self::MultiMapRef {
+        match (
+            self.maps.get(DBIdx::NAME).map(AsRef::as_ref),
+            self.delta.get(DBIdx::NAME),
+        ) {
+            (Some(StoreMap::Multi(store)), Some(DeltaMap::Multi(delta))) => {
+                MultiMapRef::new(store, delta, self.state.counters(DBIdx::NAME))
             }
             _ => panic!("Unexpected map kind"),
         }
@@ -199,11 +498,27 @@ impl<'st, 'm, Sch: Schema> crate::traits::GetMapRef<'m, Sch> for TransactionRw<'
 
 impl<'st, 'm, Sch: Schema> crate::traits::GetMapMut<'m, Sch> for TransactionRw<'st, Sch> {
     type MapMut = SingleMapMut<'m>;
+    type MultiMapMut = MultiMapMut<'m>;
 
     fn get_mut<'tx: 'm, DBIdx: schema::DBIndex, I>(&'tx mut self) -> Self::MapMut {
-        match (self.store.get(DBIdx::NAME), self.delta.get_mut(DBIdx::NAME)) {
+        match (
+            self.maps.get(DBIdx::NAME).map(AsRef::as_ref),
+            self.delta.get_mut(DBIdx::NAME),
+        ) {
             (Some(StoreMap::Single(store)), Some(DeltaMap::Single(delta))) => {
-                SingleMapMut::new(store, delta)
+                SingleMapMut::new(store, delta, self.state.counters(DBIdx::NAME))
+            }
+            _ => panic!("Unexpected map kind"),
+        }
+    }
+
+    fn get_multi_mut<'tx: 'm, DBIdx: schema::DBIndex, I>(&'tx mut self) -> Self::MultiMapMut {
+        match (
+            self.maps.get(DBIdx::NAME).map(AsRef::as_ref),
+            self.delta.get_mut(DBIdx::NAME),
+        ) {
+            (Some(StoreMap::Multi(store)), Some(DeltaMap::Multi(delta))) => {
+                MultiMapMut::new(store, delta, self.state.counters(DBIdx::NAME))
             }
             _ => panic!("Unexpected map kind"),
         }
@@ -222,13 +537,42 @@ impl<'st, Sch: Schema> crate::transaction::TransactionRw for TransactionRw<'st,
     type Error = crate::Error;
 
     /// Commit a transaction
-    fn commit(mut self) -> Result<(), Self::Error> {
-        self.store
-            .values_mut()
-            .zip(self.delta.into_values())
-            .for_each(|(store, delta)| {
-                delta.apply_to(store);
-            });
+    fn commit(self) -> Result<(), Self::Error> {
+        let Self {
+            state,
+            mut journal,
+            maps,
+            delta,
+            _phantom,
+        } = self;
+
+        // Persist the changes first so nothing is applied if that fails
+        if let Some(journal) = &mut *journal {
+            journal.append(&delta)?;
+        }
+
+        // Release our reference to the current version so it can be modified in place
+        drop(maps);
+        let mut snapshot = state.snapshot.lock().expect("Mutex locked by a crashed thread");
+        if let Some(maps) = sync::Arc::get_mut(&mut *snapshot) {
+            let modified_maps_unused = maps
+                .values_mut()
+                .zip(delta.values())
+                .all(|(store, delta)| delta.is_empty() || sync::Arc::get_mut(store).is_some());
+            if modified_maps_unused {
+                // Only takes time proportional to the size of the changes
+                apply_delta(state, maps, delta);
+                return Ok(());
+            }
+        }
+
+        // Readers still use the maps to be modified. They are copied outside of the lock so
+        // readers starting meanwhile are not held up. No other writer can commit in the meantime.
+        let mut new_maps = BTreeMap::clone(&snapshot);
+        drop(snapshot);
+        apply_delta(state, &mut new_maps, delta);
+        *state.snapshot.lock().expect("Mutex locked by a crashed thread") =
+            sync::Arc::new(new_maps);
         Ok(())
     }
 
@@ -238,18 +582,46 @@ impl<'st, Sch: Schema> crate::transaction::TransactionRw for TransactionRw<'st,
     }
 }
 
+// Apply the changes made by a transaction to a version of the store maps, copying the modified maps
+// if they are shared with other versions
+fn apply_delta(
+    state: &StoreState,
+    maps: &mut BTreeMap<&'static str, sync::Arc<StoreMap>>,
+    delta: DeltaMapSet,
+) {
+    maps.iter_mut()
+        .zip(delta.into_values())
+        .filter(|(_, delta)| !delta.is_empty())
+        .for_each(|((name, store), delta)| {
+            let change = delta.apply_to(make_mut(store));
+            state.counters(name).commit(&change);
+        });
+}
+
 /// Represents an immutable store with keys mapping to one value inside a read-only transaction.
-pub struct SingleMapView<'tx>(&'tx StoreMapSingle);
+pub struct SingleMapView<'tx> {
+    store: &'tx StoreMapSingle,
+    counters: &'tx Counters,
+}
 
 impl<'tx> SingleMapView<'tx> {
-    fn new(store: &'tx StoreMapSingle) -> Self {
-        Self(store)
+    fn new(store: &'tx StoreMapSingle, counters: &'tx Counters) -> Self {
+        Self { store, counters }
     }
 }
 
 impl crate::traits::MapRef for SingleMapView<'_> {
     fn get(&self, key: &[u8]) -> crate::Result<Option<&[u8]>> {
-        Ok(self.0.get(key).map(AsRef::as_ref))
+        self.counters.read();
+        Ok(self.store.get(key).map(AsRef::as_ref))
+    }
+
+    fn range_iter<R: RangeBounds<Data>>(&self, range: R) -> crate::Result<MapIter<'_>> {
+        self.counters.read();
+        let iter = self.store.range::<Data, _>((range.start_bound(), range.end_bound()));
+        Ok(Box::new(
+            iter.map(|(key, val)| (key.as_ref(), val.as_ref())),
+        ))
     }
 }
 
@@ -257,21 +629,32 @@ impl crate::traits::MapRef for SingleMapView<'_> {
 pub struct SingleMapRef<'tx> {
     store: &'tx StoreMapSingle,
     delta: &'tx DeltaMapSingle,
+    counters: &'tx Counters,
 }
 
 impl<'tx> SingleMapRef<'tx> {
-    fn new(store: &'tx StoreMapSingle, delta: &'tx DeltaMapSingle) -> Self {
-        Self { store, delta }
+    fn new(
+        store: &'tx StoreMapSingle,
+        delta: &'tx DeltaMapSingle,
+        counters: &'tx Counters,
+    ) -> Self {
+        Self {
+            store,
+            delta,
+            counters,
+        }
     }
 }
 
 impl crate::traits::MapRef for SingleMapRef<'_> {
     fn get(&self, key: &[u8]) -> crate::Result<Option<&[u8]>> {
-        let res = match &self.delta.get(key) {
-            Some(val) => val.as_ref(),
-            None => self.store.get(key),
-        };
-        Ok(res.map(AsRef::as_ref))
+        self.counters.read();
+        Ok(single_map_get(self.store, self.delta, key))
+    }
+
+    fn range_iter<R: RangeBounds<Data>>(&self, range: R) -> crate::Result<MapIter<'_>> {
+        self.counters.read();
+        Ok(single_map_range(self.store, self.delta, range))
     }
 }
 
@@ -279,32 +662,214 @@ impl crate::traits::MapRef for SingleMapRef<'_> {
 pub struct SingleMapMut<'tx> {
     store: &'tx StoreMapSingle,
     delta: &'tx mut DeltaMapSingle,
+    counters: &'tx Counters,
 }
 
 impl<'tx> SingleMapMut<'tx> {
-    fn new(store: &'tx StoreMapSingle, delta: &'tx mut DeltaMapSingle) -> Self {
-        Self { store, delta }
+    fn new(
+        store: &'tx StoreMapSingle,
+        delta: &'tx mut DeltaMapSingle,
+        counters: &'tx Counters,
+    ) -> Self {
+        Self {
+            store,
+            delta,
+            counters,
+        }
     }
 }
 
 impl crate::traits::MapRef for SingleMapMut<'_> {
     fn get(&self, key: &[u8]) -> crate::Result<Option<&[u8]>> {
-        let res = match &self.delta.get(key) {
-            Some(val) => val.as_ref(),
-            None => self.store.get(key),
-        };
-        Ok(res.map(AsRef::as_ref))
+        self.counters.read();
+        Ok(single_map_get(self.store, self.delta, key))
+    }
+
+    fn range_iter<R: RangeBounds<Data>>(&self, range: R) -> crate::Result<MapIter<'_>> {
+        self.counters.read();
+        Ok(single_map_range(self.store, self.delta, range))
     }
 }
 
 impl crate::traits::MapMut for SingleMapMut<'_> {
     fn put(&mut self, key: Data, val: Data) -> crate::Result<()> {
+        self.counters.write();
         self.delta.insert(key, Some(val));
         Ok(())
     }
 
     fn del(&mut self, key: &[u8]) -> crate::Result<()> {
+        self.counters.write();
         self.delta.insert(key.to_vec(), None);
         Ok(())
     }
 }
+
+// Get value associated with a key in a single-valued map, taking uncommitted changes into account
+fn single_map_get<'a>(
+    store: &'a StoreMapSingle,
+    delta: &'a DeltaMapSingle,
+    key: &[u8],
+) -> Option<&'a [u8]> {
+    let res = match delta.get(key) {
+        Some(val) => val.as_ref(),
+        None => store.get(key),
+    };
+    res.map(AsRef::as_ref)
+}
+
+// Iterate over a range of a single-valued map, taking uncommitted changes into account
+fn single_map_range<'a, R: RangeBounds<Data>>(
+    store: &'a StoreMapSingle,
+    delta: &'a DeltaMapSingle,
+    range: R,
+) -> MapIter<'a> {
+    let bounds = (range.start_bound(), range.end_bound());
+    Box::new(MergedIter {
+        store: store.range::<Data, _>(bounds).peekable(),
+        delta: delta.range::<Data, _>(bounds).peekable(),
+    })
+}
+
+// Iterator over a single-valued map with uncommitted changes merged in
+struct MergedIter<S: Iterator, D: Iterator> {
+    store: Peekable<S>,
+    delta: Peekable<D>,
+}
+
+impl<'a, S, D> Iterator for MergedIter<S, D>
+where
+    S: Iterator<Item = (&'a Data, &'a Data)>,
+    D: Iterator<Item = (&'a Data, &'a Option<Data>)>,
+{
+    type Item = (&'a [u8], &'a [u8]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let order = match (self.store.peek(), self.delta.peek()) {
+                (None, None) => return None,
+                (Some(_), None) => std::cmp::Ordering::Less,
+                (None, Some(_)) => std::cmp::Ordering::Greater,
+                (Some((store_key, _)), Some((delta_key, _))) => store_key.cmp(delta_key),
+            };
+            match order {
+                std::cmp::Ordering::Less => {
+                    return self.store.next().map(|(key, val)| (key.as_ref(), val.as_ref()));
+                }
+                // The original entry is overridden by the delta
+                std::cmp::Ordering::Equal => drop(self.store.next()),
+                std::cmp::Ordering::Greater => (),
+            }
+            // Take the entry from the delta unless it has been deleted
+            if let Some((key, Some(val))) = self.delta.next() {
+                return Some((key.as_ref(), val.as_ref()));
+            }
+        }
+    }
+}
+
+/// Represents an immutable store with keys mapping to multiple values inside a read-only
+/// transaction.
+pub struct MultiMapView<'tx> {
+    store: &'tx StoreMapMulti,
+    counters: &'tx Counters,
+}
+
+impl<'tx> MultiMapView<'tx> {
+    fn new(store: &'tx StoreMapMulti, counters: &'tx Counters) -> Self {
+        Self { store, counters }
+    }
+}
+
+impl crate::traits::MultiMapRef for MultiMapView<'_> {
+    fn get(&self, key: &[u8]) -> crate::Result<crate::traits::ValuesIter<'_>> {
+        self.counters.read();
+        let vals = self.store.get(key).into_iter().flatten();
+        Ok(Box::new(vals.map(AsRef::as_ref)))
+    }
+}
+
+/// Represents an immutable key-value store with keys mapping to multiple values.
+pub struct MultiMapRef<'tx> {
+    store: &'tx StoreMapMulti,
+    delta: &'tx DeltaMapMulti,
+    counters: &'tx Counters,
+}
+
+impl<'tx> MultiMapRef<'tx> {
+    fn new(store: &'tx StoreMapMulti, delta: &'tx DeltaMapMulti, counters: &'tx Counters) -> Self {
+        Self {
+            store,
+            delta,
+            counters,
+        }
+    }
+}
+
+impl crate::traits::MultiMapRef for MultiMapRef<'_> {
+    fn get(&self, key: &[u8]) -> crate::Result<crate::traits::ValuesIter<'_>> {
+        self.counters.read();
+        Ok(multi_map_get(self.store, self.delta, key))
+    }
+}
+
+/// Represents a mutable key-value store with keys mapping to multiple values.
+pub struct MultiMapMut<'tx> {
+    store: &'tx StoreMapMulti,
+    delta: &'tx mut DeltaMapMulti,
+    counters: &'tx Counters,
+}
+
+impl<'tx> MultiMapMut<'tx> {
+    fn new(
+        store: &'tx StoreMapMulti,
+        delta: &'tx mut DeltaMapMulti,
+        counters: &'tx Counters,
+    ) -> Self {
+        Self {
+            store,
+            delta,
+            counters,
+        }
+    }
+}
+
+impl crate::traits::MultiMapRef for MultiMapMut<'_> {
+    fn get(&self, key: &[u8]) -> crate::Result<crate::traits::ValuesIter<'_>> {
+        self.counters.read();
+        Ok(multi_map_get(self.store, self.delta, key))
+    }
+}
+
+impl crate::traits::MultiMapMut for MultiMapMut<'_> {
+    fn put(&mut self, key: Data, val: Data) -> crate::Result<()> {
+        self.counters.write();
+        self.delta.entry(key).or_default().insert(val);
+        Ok(())
+    }
+
+    fn del(&mut self, key: &[u8], val: &[u8]) -> crate::Result<()> {
+        self.counters.write();
+        self.delta.entry(key.to_vec()).or_default().remove(val.to_vec());
+        Ok(())
+    }
+
+    fn del_all(&mut self, key: &[u8]) -> crate::Result<()> {
+        self.counters.write();
+        self.delta.insert(key.to_vec(), SetDelta::Set(BTreeSet::new()));
+        Ok(())
+    }
+}
+
+// Get values associated with a key in a key-multivalue map, taking uncommitted changes into account
+fn multi_map_get<'a>(
+    store: &'a StoreMapMulti,
+    delta: &'a DeltaMapMulti,
+    key: &[u8],
+) -> crate::traits::ValuesIter<'a> {
+    let orig = store.get(key);
+    match delta.get(key) {
+        Some(vals_delta) => vals_delta.view(orig),
+        None => Box::new(orig.into_iter().flatten().map(AsRef::as_ref)),
+    }
+}
@@ -0,0 +1,71 @@
+//! Simulated crashes, for testing crash recovery of persistent stores
+//!
+//! Each I/O operation a persistent store performs on disk consumes some units of a per-thread
+//! budget. Writing data costs one unit per byte, any other operation (sync, rename, truncation)
+//! costs one unit. Once the budget is exhausted, the thread panics with [Crash] right in the
+//! middle of the operation, leaving the files on disk in the state they would be in if the
+//! process had been killed at that point. Writes are cut short, so a crash may leave a partially
+//! written record behind.
+//!
+//! Running an operation repeatedly with budgets 0, 1, 2, ... until it completes exercises every
+//! point at which the process could be killed while the operation is in progress.
+
+use std::cell::Cell;
+use std::panic;
+
+thread_local! {
+    // Number of I/O units left before a crash, None means no crash is scheduled
+    static BUDGET: Cell<Option<usize>> = const { Cell::new(None) };
+}
+
+/// Panic payload used to signal a simulated crash
+#[derive(Debug)]
+pub struct Crash;
+
+/// Run given function, crashing it after it has consumed given number of I/O units.
+///
+/// Returns `None` if the crash happened or the result of the function if it finished before the
+/// budget ran out. Panics other than simulated crashes are propagated.
+pub fn run_crashing_after<R>(units: usize, func: impl FnOnce() -> R) -> Option<R> {
+    install_panic_hook();
+    BUDGET.with(|budget| budget.set(Some(units)));
+    let result = panic::catch_unwind(panic::AssertUnwindSafe(func));
+    BUDGET.with(|budget| budget.set(None));
+    match result {
+        Ok(result) => Some(result),
+        Err(payload) if payload.is::<Crash>() => None,
+        Err(payload) => panic::resume_unwind(payload),
+    }
+}
+
+/// Take up to `units` I/O units from the budget, returning how many of them were granted.
+pub(crate) fn take(units: usize) -> usize {
+    BUDGET.with(|budget| match budget.get() {
+        Some(left) => {
+            let granted = std::cmp::min(left, units);
+            budget.set(Some(left - granted));
+            granted
+        }
+        None => units,
+    })
+}
+
+/// Crash if fewer units were granted than requested.
+pub(crate) fn check(granted: usize, requested: usize) {
+    if granted < requested {
+        panic::panic_any(Crash);
+    }
+}
+
+// Silence the panic messages for simulated crashes, there may be a lot of them
+fn install_panic_hook() {
+    static INSTALL: std::sync::Once = std::sync::Once::new();
+    INSTALL.call_once(|| {
+        let default_hook = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            if !info.payload().is::<Crash>() {
+                default_hook(info)
+            }
+        }));
+    });
+}
@@ -8,6 +8,9 @@
 //! For now, only basic storage implementation is provided. It is to be replaced with a proper one
 //! abstracting over storage backend and a more complete feature set.
 //!
+//! The basic store keeps all its data in memory. It can either be purely in-memory, created using
+//! [Store::new], or persistent, opened from a directory using [Store::open].
+//!
 //! # Example
 //!
 //! ```
@@ -64,8 +67,12 @@
 //! ```
 
 mod basic;
+#[cfg(any(test, feature = "crash-test"))]
+pub mod crash;
 pub mod error;
+mod persistent;
 pub mod schema;
+pub mod stats;
 pub mod traits;
 pub mod transaction;
 
@@ -84,6 +91,7 @@ mod test {
     crate::decl_schema! {
         MySchema {
             MyMap: Single,
+            MyMultiMap: Multi,
         }
     }
 
@@ -96,18 +104,450 @@ mod test {
         })
     }
 
+    fn generic_abort<St: Backend<MySchema>>(store: &St) {
+        let r = generic_aborted_write(store);
+        assert_eq!(r, Ok(()));
+
+        let r = store
+            .transaction_ro()
+            .run(|tx| Ok(tx.get::<MyMap, _>().get(b"hello")?.is_some()));
+        assert_eq!(r, Ok(false));
+    }
+
     #[test]
     fn test_abort() {
         common::concurrency::model(|| {
             let store = MyStore::default();
+            generic_abort(&store);
+        })
+    }
+
+    #[test]
+    #[cfg(not(loom))]
+    fn test_abort_persistent() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = MyStore::open(dir.path()).unwrap();
+        generic_abort(&store);
+    }
+
+    fn collect_entries(iter: MapIter<'_>) -> Vec<(Vec<u8>, Vec<u8>)> {
+        iter.map(|(k, v)| (k.to_vec(), v.to_vec())).collect()
+    }
+
+    fn entries(items: &[(&[u8], &[u8])]) -> Vec<(Vec<u8>, Vec<u8>)> {
+        items.iter().map(|(k, v)| (k.to_vec(), v.to_vec())).collect()
+    }
 
-            let r = generic_aborted_write(&store);
+    fn generic_iteration<St: Backend<MySchema>>(store: &St) {
+        let r = store.transaction_rw().run(|tx| {
+            let mut col = tx.get_mut::<MyMap, _>();
+            col.put(b"b1".to_vec(), b"1".to_vec())?;
+            col.put(b"a".to_vec(), b"2".to_vec())?;
+            col.put(b"b3".to_vec(), b"3".to_vec())?;
+            col.put(b"c".to_vec(), b"4".to_vec())?;
+            crate::commit(())
+        });
+        assert_eq!(r, Ok(()));
+
+        let r = store.transaction_ro().run(|tx| {
+            let col = tx.get::<MyMap, _>();
+            assert_eq!(
+                collect_entries(col.iter()?),
+                entries(&[(b"a", b"2"), (b"b1", b"1"), (b"b3", b"3"), (b"c", b"4")]),
+            );
+            assert_eq!(
+                collect_entries(col.prefix_iter(b"b")?),
+                entries(&[(b"b1", b"1"), (b"b3", b"3")]),
+            );
+            assert_eq!(
+                collect_entries(col.range_iter(b"b2".to_vec()..b"c".to_vec())?),
+                entries(&[(b"b3", b"3")]),
+            );
+            assert_eq!(
+                collect_entries(col.range_iter(b"b1".to_vec()..=b"c".to_vec())?),
+                entries(&[(b"b1", b"1"), (b"b3", b"3"), (b"c", b"4")]),
+            );
+            Ok(())
+        });
+        assert_eq!(r, Ok(()));
+
+        // Uncommitted changes are merged into the iteration
+        let r = store.transaction_rw().run(|tx| {
+            let mut col = tx.get_mut::<MyMap, _>();
+            col.put(b"b2".to_vec(), b"5".to_vec())?;
+            col.put(b"b3".to_vec(), b"6".to_vec())?;
+            col.del(b"b1")?;
+            col.del(b"x")?;
+            col.put(b"d".to_vec(), b"7".to_vec())?;
+            assert_eq!(
+                collect_entries(col.iter()?),
+                entries(&[(b"a", b"2"), (b"b2", b"5"), (b"b3", b"6"), (b"c", b"4"), (b"d", b"7")]),
+            );
+            assert_eq!(
+                collect_entries(col.prefix_iter(b"b")?),
+                entries(&[(b"b2", b"5"), (b"b3", b"6")]),
+            );
+            assert_eq!(
+                collect_entries(col.range_iter(b"b3".to_vec()..)?),
+                entries(&[(b"b3", b"6"), (b"c", b"4"), (b"d", b"7")]),
+            );
+            crate::commit(())
+        });
+        assert_eq!(r, Ok(()));
+
+        let r = store
+            .transaction_ro()
+            .run(|tx| Ok(collect_entries(tx.get::<MyMap, _>().iter()?)));
+        assert_eq!(
+            r,
+            Ok(entries(&[
+                (b"a", b"2"),
+                (b"b2", b"5"),
+                (b"b3", b"6"),
+                (b"c", b"4"),
+                (b"d", b"7")
+            ])),
+        );
+    }
+
+    #[test]
+    fn test_iteration() {
+        common::concurrency::model(|| {
+            let store = MyStore::default();
+            generic_iteration(&store);
+        })
+    }
+
+    #[test]
+    #[cfg(not(loom))]
+    fn test_iteration_persistent() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = MyStore::open(dir.path()).unwrap();
+        generic_iteration(&store);
+    }
+
+    #[test]
+    fn test_prefix_iteration_edge_cases() {
+        common::concurrency::model(|| {
+            let store = MyStore::default();
+            let keys: [&[u8]; 6] = [b"", b"\x00", b"\xfe", b"\xfe\xff", b"\xff", b"\xff\x00"];
+            let r = store.transaction_rw().run(|tx| {
+                let mut col = tx.get_mut::<MyMap, _>();
+                for key in keys {
+                    col.put(key.to_vec(), b"x".to_vec())?;
+                }
+                crate::commit(())
+            });
             assert_eq!(r, Ok(()));
 
-            let r = store
+            let prefix_keys = |prefix: &[u8]| {
+                store
+                    .transaction_ro()
+                    .run(|tx| {
+                        let col = tx.get::<MyMap, _>();
+                        let keys: Vec<Vec<u8>> =
+                            col.prefix_iter(prefix)?.map(|(k, _)| k.to_vec()).collect();
+                        Ok(keys)
+                    })
+                    .unwrap()
+            };
+            assert_eq!(prefix_keys(b""), keys.map(ToOwned::to_owned));
+            assert_eq!(
+                prefix_keys(b"\xfe"),
+                [b"\xfe".to_vec(), b"\xfe\xff".to_vec()]
+            );
+            assert_eq!(
+                prefix_keys(b"\xff"),
+                [b"\xff".to_vec(), b"\xff\x00".to_vec()]
+            );
+            assert_eq!(prefix_keys(b"\x01"), Vec::<Vec<u8>>::new());
+        })
+    }
+
+    fn get_values<St: Backend<MySchema>>(store: &St, key: &[u8]) -> Vec<Vec<u8>> {
+        store
+            .transaction_ro()
+            .run(|tx| {
+                Ok(tx.get_multi::<MyMultiMap, _>().get(key)?.map(ToOwned::to_owned).collect())
+            })
+            .unwrap()
+    }
+
+    fn generic_multi_map<St: Backend<MySchema>>(store: &St) {
+        let r = store.transaction_rw().run(|tx| {
+            let mut col = tx.get_multi_mut::<MyMultiMap, _>();
+            col.put(b"foo".to_vec(), b"3".to_vec())?;
+            col.put(b"foo".to_vec(), b"1".to_vec())?;
+            col.put(b"foo".to_vec(), b"2".to_vec())?;
+            col.put(b"foo".to_vec(), b"1".to_vec())?;
+            col.put(b"bar".to_vec(), b"5".to_vec())?;
+            assert_eq!(col.get(b"foo")?.collect::<Vec<_>>(), [b"1", b"2", b"3"]);
+            crate::commit(())
+        });
+        assert_eq!(r, Ok(()));
+        assert_eq!(get_values(store, b"foo"), [b"1", b"2", b"3"]);
+        assert_eq!(get_values(store, b"bar"), [b"5"]);
+        assert!(get_values(store, b"baz").is_empty());
+
+        // Uncommitted changes are visible inside the transaction only
+        let r = store.transaction_rw().run(|tx| {
+            let mut col = tx.get_multi_mut::<MyMultiMap, _>();
+            col.del(b"foo", b"2")?;
+            col.put(b"foo".to_vec(), b"0".to_vec())?;
+            col.del(b"foo", b"4")?;
+            col.del_all(b"bar")?;
+            assert_eq!(col.get(b"foo")?.collect::<Vec<_>>(), [b"0", b"1", b"3"]);
+            assert_eq!(col.get(b"bar")?.count(), 0);
+            crate::abort(())
+        });
+        assert_eq!(r, Ok(()));
+        assert_eq!(get_values(store, b"foo"), [b"1", b"2", b"3"]);
+        assert_eq!(get_values(store, b"bar"), [b"5"]);
+
+        // Remove values and re-add some of them
+        let r = store.transaction_rw().run(|tx| {
+            let mut col = tx.get_multi_mut::<MyMultiMap, _>();
+            col.del(b"foo", b"2")?;
+            col.put(b"foo".to_vec(), b"2".to_vec())?;
+            col.del(b"foo", b"3")?;
+            col.del_all(b"bar")?;
+            col.put(b"bar".to_vec(), b"6".to_vec())?;
+            crate::commit(())
+        });
+        assert_eq!(r, Ok(()));
+        assert_eq!(get_values(store, b"foo"), [b"1", b"2"]);
+        assert_eq!(get_values(store, b"bar"), [b"6"]);
+
+        // Removing all values removes the key
+        let r = store.transaction_rw().run(|tx| {
+            let mut col = tx.get_multi_mut::<MyMultiMap, _>();
+            col.del(b"bar", b"6")?;
+            crate::commit(())
+        });
+        assert_eq!(r, Ok(()));
+        assert!(get_values(store, b"bar").is_empty());
+    }
+
+    #[test]
+    fn test_multi_map() {
+        common::concurrency::model(|| {
+            let store = MyStore::default();
+            generic_multi_map(&store);
+        })
+    }
+
+    #[test]
+    #[cfg(not(loom))]
+    fn test_multi_map_persistent() {
+        let dir = tempfile::tempdir().unwrap();
+        {
+            let store = MyStore::open(dir.path()).unwrap();
+            generic_multi_map(&store);
+        }
+        let store = MyStore::open(dir.path()).unwrap();
+        assert_eq!(get_values(&store, b"foo"), [b"1", b"2"]);
+        assert!(get_values(&store, b"bar").is_empty());
+    }
+
+    fn get_value<St: Backend<MySchema>>(store: &St, key: &[u8]) -> Option<Vec<u8>> {
+        store
+            .transaction_ro()
+            .run(|tx| Ok(tx.get::<MyMap, _>().get(key)?.map(<[u8]>::to_vec)))
+            .unwrap()
+    }
+
+    #[test]
+    fn reader_not_blocked_by_writer() {
+        common::concurrency::model(|| {
+            let store = MyStore::default();
+            let mut tx_rw = store.transaction_rw();
+            tx_rw.get_mut::<MyMap, _>().put(b"key".to_vec(), b"new".to_vec()).unwrap();
+
+            // The uncommitted change is not visible to readers
+            assert_eq!(get_value(&store, b"key"), None);
+            let tx_ro = store.transaction_ro();
+            tx_rw.commit().unwrap();
+
+            // Neither is the committed one to a reader which started earlier
+            assert_eq!(tx_ro.get::<MyMap, _>().get(b"key"), Ok(None));
+            tx_ro.finalize().unwrap();
+            assert_eq!(get_value(&store, b"key"), Some(b"new".to_vec()));
+        })
+    }
+
+    #[test]
+    fn snapshot_survives_commits() {
+        common::concurrency::model(|| {
+            let store = MyStore::default();
+            let r = store.transaction_rw().run(|tx| {
+                let mut col = tx.get_mut::<MyMap, _>();
+                col.put(b"a".to_vec(), b"1".to_vec())?;
+                col.put(b"b".to_vec(), b"2".to_vec())?;
+                crate::commit(())
+            });
+            assert_eq!(r, Ok(()));
+
+            let tx_ro = store.transaction_ro();
+            let r = store.transaction_rw().run(|tx| {
+                let mut col = tx.get_mut::<MyMap, _>();
+                col.del(b"a")?;
+                col.put(b"b".to_vec(), b"3".to_vec())?;
+                col.put(b"c".to_vec(), b"4".to_vec())?;
+                crate::commit(())
+            });
+            assert_eq!(r, Ok(()));
+
+            let col = tx_ro.get::<MyMap, _>();
+            assert_eq!(
+                collect_entries(col.iter().unwrap()),
+                entries(&[(b"a", b"1"), (b"b", b"2")]),
+            );
+            tx_ro.finalize().unwrap();
+            assert_eq!(get_value(&store, b"a"), None);
+            assert_eq!(get_value(&store, b"c"), Some(b"4".to_vec()));
+        })
+    }
+
+    #[test]
+    fn concurrent_reader_sees_whole_commits() {
+        common::concurrency::model(|| {
+            let store = MyStore::default();
+            let writer = {
+                let store = store.clone();
+                common::thread::spawn(move || {
+                    let r = store.transaction_rw().run(|tx| {
+                        let mut col = tx.get_mut::<MyMap, _>();
+                        col.put(b"a".to_vec(), b"1".to_vec())?;
+                        col.put(b"b".to_vec(), b"1".to_vec())?;
+                        crate::commit(())
+                    });
+                    assert_eq!(r, Ok(()));
+                })
+            };
+
+            let (a, b) = store
                 .transaction_ro()
-                .run(|tx| Ok(tx.get::<MyMap, _>().get(b"hello")?.is_some()));
-            assert_eq!(r, Ok(false));
+                .run(|tx| {
+                    let col = tx.get::<MyMap, _>();
+                    Ok((
+                        col.get(b"a")?.map(<[u8]>::to_vec),
+                        col.get(b"b")?.map(<[u8]>::to_vec),
+                    ))
+                })
+                .unwrap();
+            assert_eq!(a, b);
+
+            writer.join().unwrap();
+            assert_eq!(get_value(&store, b"b"), Some(b"1".to_vec()));
+        })
+    }
+
+    #[test]
+    fn concurrent_writers_serialized() {
+        common::concurrency::model(|| {
+            let store = MyStore::default();
+            let increment = |store: &MyStore| {
+                let r = store.transaction_rw().run(|tx| {
+                    let mut col = tx.get_mut::<MyMap, _>();
+                    let n = col.get(b"counter")?.map_or(0, |v| v[0]);
+                    col.put(b"counter".to_vec(), vec![n + 1])?;
+                    crate::commit(())
+                });
+                assert_eq!(r, Ok(()));
+            };
+
+            let writer = {
+                let store = store.clone();
+                common::thread::spawn(move || increment(&store))
+            };
+            increment(&store);
+            writer.join().unwrap();
+
+            assert_eq!(get_value(&store, b"counter"), Some(vec![2]));
+        })
+    }
+
+    #[test]
+    fn test_stats() {
+        common::concurrency::model(|| {
+            let store = MyStore::default();
+            let r = store.transaction_rw().run(|tx| {
+                let mut col = tx.get_mut::<MyMap, _>();
+                col.put(b"a".to_vec(), b"123".to_vec())?;
+                col.put(b"bb".to_vec(), b"4".to_vec())?;
+                let mut col = tx.get_multi_mut::<MyMultiMap, _>();
+                col.put(b"x".to_vec(), b"12".to_vec())?;
+                col.put(b"x".to_vec(), b"345".to_vec())?;
+                crate::commit(())
+            });
+            assert_eq!(r, Ok(()));
+            assert_eq!(generic_aborted_write(&store), Ok(()));
+            assert_eq!(get_value(&store, b"a"), Some(b"123".to_vec()));
+
+            let stats = store.stats().unwrap();
+            assert_eq!(
+                stats.get("MyMap"),
+                Some(&crate::stats::MapStats {
+                    key_count: 2,
+                    key_bytes: 3,
+                    value_bytes: 4,
+                    reads: 1,
+                    writes: 3,
+                    commits: 1,
+                })
+            );
+            assert_eq!(
+                stats.get("MyMultiMap"),
+                Some(&crate::stats::MapStats {
+                    key_count: 1,
+                    key_bytes: 1,
+                    value_bytes: 5,
+                    reads: 0,
+                    writes: 2,
+                    commits: 1,
+                })
+            );
         })
     }
+
+    #[test]
+    #[cfg(not(loom))]
+    fn test_stats_follow_changes_persistent() {
+        let sizes = |store: &MyStore, name| {
+            let stats = store.stats().unwrap()[name].clone();
+            (stats.key_count, stats.key_bytes, stats.value_bytes)
+        };
+
+        let dir = tempfile::tempdir().unwrap();
+        let store = MyStore::open(dir.path()).unwrap();
+        let r = store.transaction_rw().run(|tx| {
+            let mut col = tx.get_mut::<MyMap, _>();
+            col.put(b"a".to_vec(), b"123".to_vec())?;
+            col.put(b"bb".to_vec(), b"4".to_vec())?;
+            let mut col = tx.get_multi_mut::<MyMultiMap, _>();
+            col.put(b"x".to_vec(), b"12".to_vec())?;
+            col.put(b"x".to_vec(), b"345".to_vec())?;
+            col.put(b"yy".to_vec(), b"6".to_vec())?;
+            crate::commit(())
+        });
+        assert_eq!(r, Ok(()));
+        let r = store.transaction_rw().run(|tx| {
+            let mut col = tx.get_mut::<MyMap, _>();
+            col.put(b"a".to_vec(), b"12345".to_vec())?;
+            col.del(b"bb")?;
+            col.del(b"ccc")?;
+            let mut col = tx.get_multi_mut::<MyMultiMap, _>();
+            col.del(b"x", b"12")?;
+            col.del(b"yy", b"6")?;
+            crate::commit(())
+        });
+        assert_eq!(r, Ok(()));
+        assert_eq!(sizes(&store, "MyMap"), (1, 1, 5));
+        assert_eq!(sizes(&store, "MyMultiMap"), (1, 1, 3));
+
+        drop(store);
+        let store = MyStore::open(dir.path()).unwrap();
+        assert_eq!(sizes(&store, "MyMap"), (1, 1, 5));
+        assert_eq!(sizes(&store, "MyMultiMap"), (1, 1, 3));
+    }
 }
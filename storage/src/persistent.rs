@@ -0,0 +1,669 @@
+//! On-disk persistence for the store
+//!
+//! A persistent store lives in a directory containing a single log file. Committing a transaction
+//! appends a record with all the changes made by the transaction to the log. When the store is
+//! opened, the records are replayed to reconstruct the contents of the store. The log is then
+//! compacted into a single record so it does not grow indefinitely across restarts.
+//!
+//! Each record is framed by its length and checksum and synced to disk before the commit
+//! completes. If the process is killed while a record is being written, the partially written
+//! record is detected by the framing when the store is next opened and discarded, so the commit
+//! either takes effect fully or not at all. The length has a check value of its own, so a damaged
+//! length is reported as corruption rather than mistaken for a record cut short at the end.
+//! Compaction writes the new log into a temporary file which then atomically replaces the old log.
+
+use crate::basic::{DeltaMap, DeltaMapSet, StoreMap, StoreMapSet};
+use crate::error::{Error, Fatal, Recoverable};
+use crypto::hash::{self, Blake2b32};
+use serialization::{DecodeAll, Encode};
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+
+#[cfg(any(test, feature = "crash-test"))]
+use crate::crash;
+
+#[cfg(not(any(test, feature = "crash-test")))]
+mod crash {
+    pub fn take(units: usize) -> usize {
+        units
+    }
+
+    pub fn check(_granted: usize, _requested: usize) {}
+}
+
+// Name of the log file inside the store directory
+const LOG_FILE_NAME: &str = "data.log";
+
+// Name of the temporary file used while compacting the log
+const TMP_LOG_FILE_NAME: &str = "data.log.tmp";
+
+// Size of the record checksum
+const CHECKSUM_SIZE: usize = 32;
+
+// Size of the check value of the record length
+const LEN_CHECK_SIZE: usize = 4;
+
+// Size of the record frame header, consisting of the payload length, its check value and the
+// payload checksum
+const HEADER_SIZE: usize = 4 + LEN_CHECK_SIZE + CHECKSUM_SIZE;
+
+// A log record, consisting of changes to individual maps identified by map name
+type Record = Vec<(String, DeltaMap)>;
+
+/// Log of changes committed to a persistent store
+pub struct Journal {
+    file: fs::File,
+    // Length of the valid part of the log
+    len: u64,
+    // Set if the state of the log on disk is unknown after an I/O error
+    poisoned: bool,
+}
+
+impl Journal {
+    /// Open the journal in given directory, loading its contents into given store maps.
+    pub fn open(path: &Path, maps: &mut StoreMapSet) -> crate::Result<Self> {
+        fs::create_dir_all(path).map_err(io_error)?;
+        let log_path = path.join(LOG_FILE_NAME);
+        let tmp_path = path.join(TMP_LOG_FILE_NAME);
+
+        // A leftover temporary file means compaction has been interrupted before the log was
+        // replaced. The log itself is intact so the temporary file is just thrown away.
+        match io_step(|| fs::remove_file(&tmp_path)) {
+            Ok(()) => logging::log::warn!("Removing incomplete compacted storage log"),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => (),
+            Err(e) => return Err(io_error(e)),
+        }
+
+        let data = match fs::read(&log_path) {
+            Ok(data) => data,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(io_error(e)),
+        };
+
+        let mut input = &data[..];
+        let mut num_records = 0usize;
+        while !input.is_empty() {
+            let payload = match next_frame(&mut input) {
+                Ok(payload) => payload,
+                Err(FrameError::Torn) => {
+                    logging::log::warn!(
+                        "Discarding incomplete record at the end of the storage log ({} bytes)",
+                        input.len(),
+                    );
+                    break;
+                }
+                Err(FrameError::Corrupted) => return Err(Error::Fatal(Fatal::DatabaseCorrupted)),
+            };
+            apply_record(decode_record(payload)?, maps)?;
+            num_records += 1;
+        }
+
+        // Rewrite the log if it has multiple records or an incomplete record at the end
+        if num_records > 1 || !input.is_empty() {
+            let maps = maps.iter().map(|(name, map)| (*name, map));
+            replace_file_synced(&log_path, &tmp_path, &snapshot(maps)).map_err(io_error)?;
+        }
+
+        let file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&log_path)
+            .map_err(io_error)?;
+        let len = file.metadata().map_err(io_error)?.len();
+        Ok(Self {
+            file,
+            len,
+            poisoned: false,
+        })
+    }
+
+    /// Append changes committed in a transaction to the log.
+    ///
+    /// If the record cannot be written, the log is rolled back to its previous state and the
+    /// transaction fails. If the log cannot be rolled back or synced, it is not known what ends
+    /// up on the disk and all subsequent commits fail too.
+    pub fn append(&mut self, delta: &DeltaMapSet) -> crate::Result<()> {
+        if self.poisoned {
+            return Err(Error::Fatal(Fatal::InternalError));
+        }
+
+        let record: Vec<_> = delta.iter().filter(|(_, d)| !d.is_empty()).collect();
+        if record.is_empty() {
+            return Ok(());
+        }
+        let frame = encode_frame(&record.encode());
+
+        if let Err(e) = write_data(&mut self.file, &frame) {
+            logging::log::error!("Failed to write storage log record: {}", e);
+            let len = self.len;
+            return match io_step(|| self.file.set_len(len)) {
+                Ok(()) => Err(Error::Recoverable(Recoverable::TransactionFailed)),
+                Err(e) => {
+                    self.poisoned = true;
+                    Err(io_error(e))
+                }
+            };
+        }
+
+        // The kernel may drop the unsynced data after a failed sync, so it cannot be retried.
+        if let Err(e) = io_step(|| self.file.sync_data()) {
+            self.poisoned = true;
+            return Err(io_error(e));
+        }
+
+        self.len += frame.len() as u64;
+        Ok(())
+    }
+}
+
+/// Encode the contents of given maps as a log consisting of a single record.
+pub fn snapshot<'a>(maps: impl Iterator<Item = (&'a str, &'a StoreMap)>) -> Vec<u8> {
+    let record: Vec<_> = maps.map(|(name, map)| (name, map.to_delta())).collect();
+    encode_frame(&record.encode())
+}
+
+/// Write a snapshot into a new backup file. An existing file is never overwritten.
+pub fn write_backup(path: &Path, snapshot: &[u8]) -> crate::Result<()> {
+    let mut tmp_path = path.as_os_str().to_owned();
+    tmp_path.push(".tmp");
+    create_file_synced(path, Path::new(&tmp_path), snapshot).map_err(io_error)
+}
+
+/// Set up a persistent store in given directory using a backup file, replacing any store
+/// already present there.
+///
+/// The backup is loaded into given maps first to make sure it is intact and matches the schema.
+pub fn restore(backup: &Path, path: &Path, maps: &mut StoreMapSet) -> crate::Result<()> {
+    let data = fs::read(backup).map_err(io_error)?;
+
+    // Backups are never appended to, so unlike in the log, an incomplete record means damage
+    let mut input = &data[..];
+    while !input.is_empty() {
+        let payload = next_frame(&mut input).map_err(|_| Error::Fatal(Fatal::DatabaseCorrupted))?;
+        apply_record(decode_record(payload)?, maps)?;
+    }
+
+    fs::create_dir_all(path).map_err(io_error)?;
+    let log_path = path.join(LOG_FILE_NAME);
+    let tmp_path = path.join(TMP_LOG_FILE_NAME);
+    replace_file_synced(&log_path, &tmp_path, &data).map_err(io_error)
+}
+
+// Reasons a log record frame could not be read
+enum FrameError {
+    // The frame is the last one in the log and has not been written completely
+    Torn,
+    // The frame is damaged
+    Corrupted,
+}
+
+// Wrap record data in a frame with length and checksum
+fn encode_frame(payload: &[u8]) -> Vec<u8> {
+    let len = u32::try_from(payload.len())
+        .expect("Storage log record too large")
+        .to_le_bytes();
+    let mut frame = Vec::with_capacity(HEADER_SIZE + payload.len());
+    frame.extend_from_slice(&len);
+    frame.extend_from_slice(&len_check(&len));
+    frame.extend_from_slice(&checksum(payload));
+    frame.extend_from_slice(payload);
+    frame
+}
+
+// Read the next frame from the input, returning its payload.
+//
+// A frame with an intact header that extends up to or past the end of the input but is
+// incomplete or has a bad checksum is the result of an interrupted write, as only the last frame
+// can be affected by one. The input is left pointing to it. A damaged header is corruption,
+// unless the header itself is cut short by the end of the input.
+fn next_frame<'i>(input: &mut &'i [u8]) -> Result<&'i [u8], FrameError> {
+    if input.len() < HEADER_SIZE {
+        return Err(FrameError::Torn);
+    }
+    let (len, rest) = input.split_at(4);
+    let (expected_len_check, rest) = rest.split_at(LEN_CHECK_SIZE);
+    if len_check(len) != expected_len_check {
+        return Err(FrameError::Corrupted);
+    }
+    let len = u32::from_le_bytes(len.try_into().expect("length is 4 bytes")) as usize;
+    let (expected_checksum, rest) = rest.split_at(CHECKSUM_SIZE);
+    if rest.len() < len {
+        return Err(FrameError::Torn);
+    }
+    let (payload, rest) = rest.split_at(len);
+    if checksum(payload) != expected_checksum {
+        return Err(if rest.is_empty() {
+            FrameError::Torn
+        } else {
+            FrameError::Corrupted
+        });
+    }
+    *input = rest;
+    Ok(payload)
+}
+
+fn checksum(data: &[u8]) -> [u8; CHECKSUM_SIZE] {
+    hash::hash::<Blake2b32, _>(data).into()
+}
+
+fn len_check(len: &[u8]) -> [u8; LEN_CHECK_SIZE] {
+    checksum(len)[..LEN_CHECK_SIZE]
+        .try_into()
+        .expect("checksum is longer than the check value")
+}
+
+fn decode_record(payload: &[u8]) -> crate::Result<Record> {
+    Record::decode_all(&mut &payload[..]).map_err(|_| Error::Fatal(Fatal::DatabaseCorrupted))
+}
+
+// Apply changes in a log record to the store maps
+fn apply_record(record: Record, maps: &mut StoreMapSet) -> crate::Result<()> {
+    for (name, delta) in record {
+        match maps.get_mut(name.as_str()) {
+            Some(map) if delta.is_compatible_with(map) => {
+                delta.apply_to(map);
+            }
+            _ => return Err(Error::Fatal(Fatal::SchemaMismatch)),
+        }
+    }
+    Ok(())
+}
+
+// Write given data into a new file, making sure it reaches the disk
+fn write_file_synced(path: &Path, data: &[u8]) -> io::Result<()> {
+    let mut file = io_step(|| fs::File::create(path))?;
+    write_data(&mut file, data)?;
+    io_step(|| file.sync_all())
+}
+
+// Atomically replace a file with given data via a temporary file, making sure it reaches the disk
+fn replace_file_synced(path: &Path, tmp_path: &Path, data: &[u8]) -> io::Result<()> {
+    write_file_synced(tmp_path, data)?;
+    io_step(|| fs::rename(tmp_path, path))?;
+    sync_parent_dir(path)
+}
+
+// Atomically create a file with given data via a temporary file, making sure it reaches the
+// disk. Fails if either file already exists, leaving it untouched.
+fn create_file_synced(path: &Path, tmp_path: &Path, data: &[u8]) -> io::Result<()> {
+    let mut file = io_step(|| fs::OpenOptions::new().write(true).create_new(true).open(tmp_path))?;
+    let result = write_data(&mut file, data)
+        .and_then(|()| io_step(|| file.sync_all()))
+        .and_then(|()| io_step(|| fs::hard_link(tmp_path, path)));
+    io_step(|| fs::remove_file(tmp_path))?;
+    result?;
+    sync_parent_dir(path)
+}
+
+// Make sure the directory entry of given file reaches the disk
+fn sync_parent_dir(path: &Path) -> io::Result<()> {
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    io_step(|| fs::File::open(dir)?.sync_all())
+}
+
+// Write data to a file. A simulated crash may leave only a part of the data written.
+fn write_data(file: &mut fs::File, data: &[u8]) -> io::Result<()> {
+    let granted = crash::take(data.len());
+    file.write_all(&data[..granted])?;
+    crash::check(granted, data.len());
+    Ok(())
+}
+
+// Perform an I/O operation that either happens or does not in case of a simulated crash
+fn io_step<T>(op: impl FnOnce() -> io::Result<T>) -> io::Result<T> {
+    crash::check(crash::take(1), 1);
+    op()
+}
+
+// Convert an I/O error into a storage error
+fn io_error(err: io::Error) -> Error {
+    logging::log::error!("Storage I/O error: {}", err);
+    Error::Fatal(Fatal::InternalError)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::crash;
+    use crate::traits::*;
+
+    crate::decl_schema! {
+        MySchema {
+            MyMap1: Single,
+            MyMap2: Single,
+        }
+    }
+
+    type MyStore = crate::Store<MySchema>;
+
+    fn put<St: Backend<MySchema>>(store: &St, key: &[u8], val: &[u8]) -> crate::Result<()> {
+        store.transaction_rw().run(|tx| {
+            tx.get_mut::<MyMap1, _>().put(key.to_vec(), val.to_vec())?;
+            crate::commit(())
+        })
+    }
+
+    fn get<St: Backend<MySchema>>(store: &St, key: &[u8]) -> crate::Result<Option<Vec<u8>>> {
+        store
+            .transaction_ro()
+            .run(|tx| Ok(tx.get::<MyMap1, _>().get(key)?.map(ToOwned::to_owned)))
+    }
+
+    #[test]
+    fn reopen_keeps_committed_data() {
+        let dir = tempfile::tempdir().unwrap();
+
+        {
+            let store = MyStore::open(dir.path()).unwrap();
+            assert_eq!(put(&store, b"foo", b"bar"), Ok(()));
+            assert_eq!(put(&store, b"baz", b"xyz"), Ok(()));
+            let r = store.transaction_rw().run(|tx| {
+                tx.get_mut::<MyMap1, _>().del(b"baz")?;
+                tx.get_mut::<MyMap2, _>().put(b"foo".to_vec(), b"2".to_vec())?;
+                crate::commit(())
+            });
+            assert_eq!(r, Ok(()));
+        }
+
+        let store = MyStore::open(dir.path()).unwrap();
+        assert_eq!(get(&store, b"foo"), Ok(Some(b"bar".to_vec())));
+        assert_eq!(get(&store, b"baz"), Ok(None));
+        let r = store
+            .transaction_ro()
+            .run(|tx| Ok(tx.get::<MyMap2, _>().get(b"foo")?.map(ToOwned::to_owned)));
+        assert_eq!(r, Ok(Some(b"2".to_vec())));
+    }
+
+    #[test]
+    fn reopen_discards_aborted_data() {
+        let dir = tempfile::tempdir().unwrap();
+
+        {
+            let store = MyStore::open(dir.path()).unwrap();
+            let r = store.transaction_rw().run(|tx| {
+                tx.get_mut::<MyMap1, _>().put(b"foo".to_vec(), b"bar".to_vec())?;
+                crate::abort(())
+            });
+            assert_eq!(r, Ok(()));
+        }
+
+        let store = MyStore::open(dir.path()).unwrap();
+        assert_eq!(get(&store, b"foo"), Ok(None));
+    }
+
+    #[test]
+    fn reopen_repeatedly() {
+        let dir = tempfile::tempdir().unwrap();
+
+        for i in 0u8..5 {
+            let store = MyStore::open(dir.path()).unwrap();
+            for j in 0..i {
+                assert_eq!(get(&store, &[j]), Ok(Some(vec![j, i - 1])));
+            }
+            for j in 0..=i {
+                assert_eq!(put(&store, &[j], &[j, i]), Ok(()));
+            }
+        }
+    }
+
+    #[test]
+    fn corrupted_log() {
+        let dir = tempfile::tempdir().unwrap();
+
+        {
+            let store = MyStore::open(dir.path()).unwrap();
+            assert_eq!(put(&store, b"foo", b"bar"), Ok(()));
+            assert_eq!(put(&store, b"baz", b"xyz"), Ok(()));
+        }
+
+        // Damage the first of the two records
+        let log_path = dir.path().join(super::LOG_FILE_NAME);
+        let mut data = std::fs::read(&log_path).unwrap();
+        data[super::HEADER_SIZE] ^= 0x01;
+        std::fs::write(&log_path, data).unwrap();
+        assert_eq!(
+            MyStore::open(dir.path()).err(),
+            Some(crate::Error::Fatal(crate::error::Fatal::DatabaseCorrupted)),
+        );
+    }
+
+    #[test]
+    fn corrupted_record_length() {
+        let dir = tempfile::tempdir().unwrap();
+
+        {
+            let store = MyStore::open(dir.path()).unwrap();
+            assert_eq!(put(&store, b"foo", b"bar"), Ok(()));
+            assert_eq!(put(&store, b"baz", b"xyz"), Ok(()));
+        }
+
+        // Make the first record claim to extend past the end of the log
+        let log_path = dir.path().join(super::LOG_FILE_NAME);
+        let mut data = std::fs::read(&log_path).unwrap();
+        data[3] = 0xff;
+        std::fs::write(&log_path, data).unwrap();
+        assert_eq!(
+            MyStore::open(dir.path()).err(),
+            Some(crate::Error::Fatal(crate::error::Fatal::DatabaseCorrupted)),
+        );
+    }
+
+    #[test]
+    fn torn_record_discarded() {
+        let dir = tempfile::tempdir().unwrap();
+
+        {
+            let store = MyStore::open(dir.path()).unwrap();
+            assert_eq!(put(&store, b"foo", b"bar"), Ok(()));
+            assert_eq!(put(&store, b"baz", b"xyz"), Ok(()));
+        }
+
+        // Cut the last record short as if the process died while writing it
+        let log_path = dir.path().join(super::LOG_FILE_NAME);
+        let data = std::fs::read(&log_path).unwrap();
+        std::fs::write(&log_path, &data[..data.len() - 3]).unwrap();
+
+        {
+            let store = MyStore::open(dir.path()).unwrap();
+            assert_eq!(get(&store, b"foo"), Ok(Some(b"bar".to_vec())));
+            assert_eq!(get(&store, b"baz"), Ok(None));
+            assert_eq!(put(&store, b"baz", b"abc"), Ok(()));
+        }
+
+        let store = MyStore::open(dir.path()).unwrap();
+        assert_eq!(get(&store, b"foo"), Ok(Some(b"bar".to_vec())));
+        assert_eq!(get(&store, b"baz"), Ok(Some(b"abc".to_vec())));
+    }
+
+    #[test]
+    fn leftover_compaction_file_removed() {
+        let dir = tempfile::tempdir().unwrap();
+
+        {
+            let store = MyStore::open(dir.path()).unwrap();
+            assert_eq!(put(&store, b"foo", b"bar"), Ok(()));
+        }
+
+        let tmp_path = dir.path().join(super::TMP_LOG_FILE_NAME);
+        std::fs::write(&tmp_path, b"incomplete").unwrap();
+        let store = MyStore::open(dir.path()).unwrap();
+        assert_eq!(get(&store, b"foo"), Ok(Some(b"bar".to_vec())));
+        assert!(!tmp_path.exists());
+    }
+
+    #[test]
+    fn backup_and_restore() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let stores = [MyStore::new(), MyStore::open(dir.path().join("orig")).unwrap()];
+        for (i, store) in stores.into_iter().enumerate() {
+            let backup_path = dir.path().join(format!("backup{i}"));
+            assert_eq!(put(&store, b"foo", b"bar"), Ok(()));
+            assert_eq!(put(&store, b"baz", b"xyz"), Ok(()));
+            assert_eq!(store.backup(&backup_path), Ok(()));
+
+            // Later changes do not affect the backup
+            assert_eq!(put(&store, b"foo", b"new"), Ok(()));
+
+            let restored_path = dir.path().join("restored");
+            assert_eq!(MyStore::restore(&backup_path, &restored_path), Ok(()));
+            let restored = MyStore::open(&restored_path).unwrap();
+            assert_eq!(get(&restored, b"foo"), Ok(Some(b"bar".to_vec())));
+            assert_eq!(get(&restored, b"baz"), Ok(Some(b"xyz".to_vec())));
+
+            // The restored store is fully functional
+            assert_eq!(put(&restored, b"foo", b"restored"), Ok(()));
+            drop(restored);
+            let restored = MyStore::open(&restored_path).unwrap();
+            assert_eq!(get(&restored, b"foo"), Ok(Some(b"restored".to_vec())));
+        }
+    }
+
+    #[test]
+    fn backup_never_overwrites() {
+        let dir = tempfile::tempdir().unwrap();
+        let backup_path = dir.path().join("backup");
+        std::fs::write(&backup_path, b"precious").unwrap();
+        let other_path = dir.path().join("other");
+        let other_tmp_path = dir.path().join("other.tmp");
+        std::fs::write(&other_tmp_path, b"also precious").unwrap();
+
+        let store = MyStore::new();
+        assert_eq!(put(&store, b"foo", b"bar"), Ok(()));
+        assert!(store.backup(&backup_path).is_err());
+        assert!(store.backup(&other_path).is_err());
+        assert_eq!(std::fs::read(&backup_path).unwrap(), b"precious");
+        assert_eq!(std::fs::read(&other_tmp_path).unwrap(), b"also precious");
+        assert!(!other_path.exists());
+    }
+
+    #[test]
+    fn restore_damaged_backup() {
+        let dir = tempfile::tempdir().unwrap();
+        let backup_path = dir.path().join("backup");
+
+        let store = MyStore::new();
+        assert_eq!(put(&store, b"foo", b"bar"), Ok(()));
+        assert_eq!(store.backup(&backup_path), Ok(()));
+
+        let data = std::fs::read(&backup_path).unwrap();
+        std::fs::write(&backup_path, &data[..data.len() - 1]).unwrap();
+        let restored_path = dir.path().join("restored");
+        assert_eq!(
+            MyStore::restore(&backup_path, &restored_path),
+            Err(crate::Error::Fatal(crate::error::Fatal::DatabaseCorrupted)),
+        );
+        assert!(!restored_path.join(super::LOG_FILE_NAME).exists());
+    }
+
+    #[test]
+    fn restore_schema_mismatch() {
+        crate::decl_schema! {
+            OtherSchema {
+                OtherMap: Single,
+            }
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+        let backup_path = dir.path().join("backup");
+        let store = crate::Store::<OtherSchema>::new();
+        assert_eq!(store.backup(&backup_path), Ok(()));
+
+        assert_eq!(
+            MyStore::restore(&backup_path, dir.path().join("restored")),
+            Err(crate::Error::Fatal(crate::error::Fatal::SchemaMismatch)),
+        );
+    }
+
+    // Contents of both maps in the store
+    type Contents = (Vec<(Vec<u8>, Vec<u8>)>, Vec<(Vec<u8>, Vec<u8>)>);
+
+    fn contents(store: &MyStore) -> Contents {
+        fn to_vec((k, v): (&[u8], &[u8])) -> (Vec<u8>, Vec<u8>) {
+            (k.to_vec(), v.to_vec())
+        }
+        store
+            .transaction_ro()
+            .run(|tx| {
+                let map1 = tx.get::<MyMap1, _>().iter()?.map(to_vec).collect();
+                let map2 = tx.get::<MyMap2, _>().iter()?.map(to_vec).collect();
+                Ok((map1, map2))
+            })
+            .unwrap()
+    }
+
+    fn entries(items: &[(&[u8], &[u8])]) -> Vec<(Vec<u8>, Vec<u8>)> {
+        items.iter().map(|(k, v)| (k.to_vec(), v.to_vec())).collect()
+    }
+
+    #[test]
+    fn crash_during_commit() {
+        let before = (entries(&[(b"bar", b"1"), (b"foo", b"1")]), entries(&[]));
+        let after = (entries(&[(b"foo", b"2")]), entries(&[(b"baz", b"2")]));
+
+        for units in 0.. {
+            let dir = tempfile::tempdir().unwrap();
+            let store = MyStore::open(dir.path()).unwrap();
+            assert_eq!(put(&store, b"foo", b"1"), Ok(()));
+            assert_eq!(put(&store, b"bar", b"1"), Ok(()));
+
+            let result = crash::run_crashing_after(units, || {
+                store.transaction_rw().run(|tx| {
+                    tx.get_mut::<MyMap1, _>().put(b"foo".to_vec(), b"2".to_vec())?;
+                    tx.get_mut::<MyMap1, _>().del(b"bar")?;
+                    tx.get_mut::<MyMap2, _>().put(b"baz".to_vec(), b"2".to_vec())?;
+                    crate::commit(())
+                })
+            });
+            drop(store);
+
+            let recovered = contents(&MyStore::open(dir.path()).unwrap());
+            match result {
+                Some(result) => {
+                    assert_eq!(result, Ok(()));
+                    assert_eq!(recovered, after);
+                    break;
+                }
+                None => assert!(
+                    recovered == before || recovered == after,
+                    "Inconsistent state after crash at {} units: {:?}",
+                    units,
+                    recovered,
+                ),
+            }
+        }
+    }
+
+    #[test]
+    fn crash_during_open() {
+        let pristine = tempfile::tempdir().unwrap();
+        let expected = {
+            let store = MyStore::open(pristine.path()).unwrap();
+            for i in 0u8..4 {
+                assert_eq!(put(&store, &[i], &[i]), Ok(()));
+            }
+            contents(&store)
+        };
+        let log = std::fs::read(pristine.path().join(super::LOG_FILE_NAME)).unwrap();
+
+        for units in 0.. {
+            let dir = tempfile::tempdir().unwrap();
+            std::fs::write(dir.path().join(super::LOG_FILE_NAME), &log).unwrap();
+
+            let result = crash::run_crashing_after(units, || MyStore::open(dir.path()));
+            let done = result.is_some();
+            if let Some(result) = result {
+                assert_eq!(contents(&result.unwrap()), expected);
+            }
+
+            let store = MyStore::open(dir.path()).unwrap();
+            assert_eq!(contents(&store), expected);
+            if done {
+                break;
+            }
+        }
+    }
+}
@@ -0,0 +1,109 @@
+//! Storage usage statistics
+
+use common::sync::atomic::{AtomicU64, Ordering};
+use std::collections::BTreeMap;
+
+/// Size and usage statistics of a single map
+///
+/// Sizes reflect the committed contents of the map and are kept up to date by each commit, so
+/// collecting the statistics does not require going through the data. Operation counters are
+/// cumulative since the store was opened. Reads and writes are counted per operation, including
+/// those performed in transactions that were later aborted. Commits are only counted if they
+/// modified the map.
+#[derive(Debug, Default, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct MapStats {
+    /// Number of distinct keys
+    pub key_count: u64,
+    /// Total size of all the keys in bytes
+    pub key_bytes: u64,
+    /// Total size of all the values in bytes
+    pub value_bytes: u64,
+    /// Number of read operations
+    pub reads: u64,
+    /// Number of write operations
+    pub writes: u64,
+    /// Number of commits that modified the map
+    pub commits: u64,
+}
+
+/// Statistics for each map in the store, by map name
+pub type Stats = BTreeMap<&'static str, MapStats>;
+
+// Total size of a set of map entries
+#[derive(Default)]
+pub(crate) struct Sizes {
+    key_count: u64,
+    key_bytes: u64,
+    value_bytes: u64,
+}
+
+impl Sizes {
+    pub(crate) fn add_entry(&mut self, key: &[u8], value_bytes: usize) {
+        self.key_count += 1;
+        self.key_bytes += key.len() as u64;
+        self.value_bytes += value_bytes as u64;
+    }
+}
+
+// Change to the contents of a map. A modified entry is counted as removed and added again.
+#[derive(Default)]
+pub(crate) struct SizeChange {
+    pub(crate) removed: Sizes,
+    pub(crate) added: Sizes,
+}
+
+// Operation counters and committed content size of a single map
+pub(crate) struct Counters {
+    reads: AtomicU64,
+    writes: AtomicU64,
+    commits: AtomicU64,
+    key_count: AtomicU64,
+    key_bytes: AtomicU64,
+    value_bytes: AtomicU64,
+}
+
+impl Counters {
+    // Start counting for a map with contents of given size
+    pub(crate) fn new(sizes: Sizes) -> Self {
+        Self {
+            reads: AtomicU64::new(0),
+            writes: AtomicU64::new(0),
+            commits: AtomicU64::new(0),
+            key_count: AtomicU64::new(sizes.key_count),
+            key_bytes: AtomicU64::new(sizes.key_bytes),
+            value_bytes: AtomicU64::new(sizes.value_bytes),
+        }
+    }
+
+    pub(crate) fn read(&self) {
+        self.reads.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn write(&self) {
+        self.writes.fetch_add(1, Ordering::Relaxed);
+    }
+
+    // Record a commit making given change to the map contents. The additions are counted first
+    // so the sizes never drop below zero, even temporarily.
+    pub(crate) fn commit(&self, change: &SizeChange) {
+        self.commits.fetch_add(1, Ordering::Relaxed);
+        let SizeChange { removed, added } = change;
+        self.key_count.fetch_add(added.key_count, Ordering::Relaxed);
+        self.key_bytes.fetch_add(added.key_bytes, Ordering::Relaxed);
+        self.value_bytes.fetch_add(added.value_bytes, Ordering::Relaxed);
+        self.key_count.fetch_sub(removed.key_count, Ordering::Relaxed);
+        self.key_bytes.fetch_sub(removed.key_bytes, Ordering::Relaxed);
+        self.value_bytes.fetch_sub(removed.value_bytes, Ordering::Relaxed);
+    }
+
+    pub(crate) fn stats(&self) -> MapStats {
+        MapStats {
+            key_count: self.key_count.load(Ordering::Relaxed),
+            key_bytes: self.key_bytes.load(Ordering::Relaxed),
+            value_bytes: self.value_bytes.load(Ordering::Relaxed),
+            reads: self.reads.load(Ordering::Relaxed),
+            writes: self.writes.load(Ordering::Relaxed),
+            commits: self.commits.load(Ordering::Relaxed),
+        }
+    }
+}
@@ -2,35 +2,92 @@
 
 use crate::schema;
 pub use crate::transaction::{TransactionRo, TransactionRw};
+use std::ops::{Bound, RangeBounds};
 
-/// Get an immutable reference to given single-valued map
+/// Get an immutable reference to given map
 pub trait GetMapRef<'m, Sch: schema::Schema> {
-    /// Type representing the map reference
+    /// Type representing the single-valued map reference
     type MapRef: MapRef + 'm;
 
-    /// Get key-value store for given map mutably (key-to-single-value only for now)
+    /// Type representing the multi-valued map reference
+    type MultiMapRef: MultiMapRef + 'm;
+
+    /// Get key-value store for given single-valued map
     fn get<'c: 'm, DBIdx, I>(&'c self) -> Self::MapRef
     where
         DBIdx: schema::DBIndex<Kind = schema::Single>,
         Sch: schema::HasDBIndex<DBIdx, I>;
+
+    /// Get key-multivalue store for given multi-valued map
+    fn get_multi<'c: 'm, DBIdx, I>(&'c self) -> Self::MultiMapRef
+    where
+        DBIdx: schema::DBIndex<Kind = schema::Multi>,
+        Sch: schema::HasDBIndex<DBIdx, I>;
 }
 
-/// Get a mutable reference to given single-valued map
+/// Get a mutable reference to given map
 pub trait GetMapMut<'m, Sch: schema::Schema>: GetMapRef<'m, Sch> {
-    /// Type representing the map reference
+    /// Type representing the single-valued map reference
     type MapMut: MapMut + 'm;
 
-    /// Get key-value store for given map mutably (key-to-single-value only for now)
+    /// Type representing the multi-valued map reference
+    type MultiMapMut: MultiMapMut + 'm;
+
+    /// Get key-value store for given single-valued map mutably
     fn get_mut<'c: 'm, DBIdx, I>(&'c mut self) -> Self::MapMut
     where
         DBIdx: schema::DBIndex<Kind = schema::Single>,
         Sch: schema::HasDBIndex<DBIdx, I>;
+
+    /// Get key-multivalue store for given multi-valued map mutably
+    fn get_multi_mut<'c: 'm, DBIdx, I>(&'c mut self) -> Self::MultiMapMut
+    where
+        DBIdx: schema::DBIndex<Kind = schema::Multi>,
+        Sch: schema::HasDBIndex<DBIdx, I>;
 }
 
+/// Iterator over key-value pairs in a single-valued map
+pub type MapIter<'i> = Box<dyn Iterator<Item = (&'i [u8], &'i [u8])> + 'i>;
+
 /// Read operations on a single-valued map
 pub trait MapRef {
     /// Get value associated with given key
     fn get(&self, key: &[u8]) -> crate::Result<Option<&[u8]>>;
+
+    /// Iterate over entries with keys in given range, in ascending key order
+    ///
+    /// # Panics
+    ///
+    /// Panics if range start is greater than range end or if both are equal and excluded.
+    fn range_iter<R: RangeBounds<crate::Data>>(&self, range: R) -> crate::Result<MapIter<'_>>;
+
+    /// Iterate over all entries, in ascending key order
+    fn iter(&self) -> crate::Result<MapIter<'_>> {
+        self.range_iter(..)
+    }
+
+    /// Iterate over entries with keys starting with given prefix, in ascending key order
+    fn prefix_iter(&self, prefix: &[u8]) -> crate::Result<MapIter<'_>> {
+        self.range_iter(prefix_range(prefix))
+    }
+}
+
+// Get the range of keys starting with given prefix
+fn prefix_range(prefix: &[u8]) -> (Bound<crate::Data>, Bound<crate::Data>) {
+    // The first key past the range is the prefix with trailing 0xff bytes stripped and the last
+    // byte incremented. If there is no such key, the range is unbounded.
+    let mut end = prefix.to_vec();
+    while end.last() == Some(&0xff) {
+        end.pop();
+    }
+    let end = match end.last_mut() {
+        Some(last) => {
+            *last += 1;
+            Bound::Excluded(end)
+        }
+        None => Bound::Unbounded,
+    };
+    (Bound::Included(prefix.to_vec()), end)
 }
 
 /// Modifying operations on a single-valued map
@@ -42,6 +99,29 @@ pub trait MapMut: MapRef {
     fn del(&mut self, key: &[u8]) -> crate::Result<()>;
 }
 
+/// Iterator over values associated with a key in a multi-valued map
+pub type ValuesIter<'i> = Box<dyn Iterator<Item = &'i [u8]> + 'i>;
+
+/// Read operations on a multi-valued map
+///
+/// Values associated with each key form a set. They are unique and kept in ascending order.
+pub trait MultiMapRef {
+    /// Get all values associated with given key, in ascending order
+    fn get(&self, key: &[u8]) -> crate::Result<ValuesIter<'_>>;
+}
+
+/// Modifying operations on a multi-valued map
+pub trait MultiMapMut: MultiMapRef {
+    /// Associate a value with given key. Values already associated with the key are kept.
+    fn put(&mut self, key: crate::Data, val: crate::Data) -> crate::Result<()>;
+
+    /// Remove given value from the values associated with given key.
+    fn del(&mut self, key: &[u8], val: &[u8]) -> crate::Result<()>;
+
+    /// Remove all values associated with given key.
+    fn del_all(&mut self, key: &[u8]) -> crate::Result<()>;
+}
+
 /// A transaction over an immutable store
 pub trait StoreTxRo<Sch: schema::Schema>:
     TransactionRo<Error = crate::Error> + for<'m> GetMapRef<'m, Sch>
@@ -80,4 +160,7 @@ pub trait Transactional<'t, Sch: schema::Schema> {
 }
 
 /// Storage backend
-pub trait Backend<Sch: schema::Schema>: for<'tx> Transactional<'tx, Sch> {}
+pub trait Backend<Sch: schema::Schema>: for<'tx> Transactional<'tx, Sch> {
+    /// Get size and usage statistics for each map in the store
+    fn stats(&self) -> crate::Result<crate::stats::Stats>;
+}
@@ -7,7 +7,7 @@ pub use utxo_impl::*;
 use thiserror::Error;
 
 #[allow(dead_code)]
-#[derive(Error, Debug, Eq, PartialEq)]
+#[derive(Error, Debug, Eq, PartialEq, Clone)]
 pub enum Error {
     #[error("Attempted to overwrite an existing utxo")]
     OverwritingUtxo,
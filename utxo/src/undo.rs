@@ -37,16 +37,24 @@ impl TxUndo {
 pub struct BlockUndo {
     // determines at what height this undo file belongs to.
     height: BlockHeight,
+    // utxos spent by the block reward inputs, if it has any.
+    reward_undo: Option<TxUndo>,
     undos: Vec<TxUndo>,
 }
 
 impl BlockUndo {
-    pub fn new(tx_undos: Vec<TxUndo>, height: BlockHeight) -> Self {
+    pub fn new(reward_undo: Option<TxUndo>, tx_undos: Vec<TxUndo>, height: BlockHeight) -> Self {
         Self {
             height,
+            reward_undo,
             undos: tx_undos,
         }
     }
+
+    pub fn reward_undo(&self) -> Option<&TxUndo> {
+        self.reward_undo.as_ref()
+    }
+
     pub fn tx_undos(&self) -> &[TxUndo] {
         &self.undos
     }
@@ -99,7 +107,11 @@ pub mod test {
         let (utxo4, _) = create_utxo(4);
         let tx_undo1 = TxUndo::new(vec![utxo2, utxo3, utxo4]);
 
-        let blockundo = BlockUndo::new(vec![tx_undo0.clone(), tx_undo1.clone()], expected_height);
+        let blockundo = BlockUndo::new(
+            None,
+            vec![tx_undo0.clone(), tx_undo1.clone()],
+            expected_height,
+        );
 
         // check `inner()`
         {
@@ -31,7 +31,7 @@ impl UtxoSource {
         }
     }
 
-    fn blockchain_height(&self) -> Result<BlockHeight, Error> {
+    pub fn blockchain_height(&self) -> Result<BlockHeight, Error> {
         match self {
             UtxoSource::BlockChain(h) => Ok(*h),
             UtxoSource::MemPool => Err(crate::Error::NoBlockchainHeightFound),
@@ -375,7 +375,9 @@ impl<'a> UtxosCache<'a> {
         None
     }
 
-    fn consume(self) -> Result<ConsumedUtxoCache, Error> {
+    /// Take the modifications made in this cache, to be written to its parent using
+    /// [FlushableUtxoView::batch_write].
+    pub fn consume(self) -> Result<ConsumedUtxoCache, Error> {
         Ok(ConsumedUtxoCache {
             container: self.utxos,
             best_block: self.current_block_hash.ok_or(Error::CacheWithoutBestBlock)?,
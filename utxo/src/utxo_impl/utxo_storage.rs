@@ -315,7 +315,7 @@ mod test {
                     .iter()
                     .map(|tx| view.spend_utxos(tx, block_height).expect("should spend okay."))
                     .collect_vec();
-                BlockUndo::new(undos, block_height)
+                BlockUndo::new(None, undos, block_height)
             };
 
             // check that the block_undo contains the same utxos recorded as "spent",